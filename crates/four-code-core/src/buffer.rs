@@ -3,17 +3,107 @@
 //! The buffer is the core data structure for storing and manipulating text.
 //! It uses a rope data structure for efficient operations on large files.
 
+use crate::editorconfig::EditorconfigProperties;
+use crate::encoding::{self, Encoding};
+use crate::grapheme;
+use crate::hash::fnv1a;
+use crate::history::{Edit, EditKind, History};
+use crate::indent::{Indentation, DETECT_LINES};
+use crate::line_ending::{normalize_line_endings, LineEnding};
+use crate::load::{load_streaming, LoadOptions};
+use crate::save::{write_atomic, SaveOptions};
+use crate::Cursor;
 use ropey::Rope;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use thiserror::Error;
 
+/// Number of text edits remembered for [`Buffer::edits_since`]
+const MAX_EDITS: usize = 1024;
+
 #[derive(Error, Debug)]
 pub enum BufferError {
     #[error("Failed to read file: {0}")]
-    ReadError(#[from] std::io::Error),
+    ReadError(#[from] io::Error),
 
     #[error("Position out of bounds: line {line}, column {column}")]
     OutOfBounds { line: usize, column: usize },
+
+    #[error("Failed to write {}: {source}", path.display())]
+    WriteError { path: PathBuf, source: io::Error },
+
+    #[error("Failed to sync {} to disk: {source}", path.display())]
+    SyncError { path: PathBuf, source: io::Error },
+
+    #[error("Failed to replace {}: {source}", path.display())]
+    RenameError { path: PathBuf, source: io::Error },
+
+    #[error("Failed to preserve permissions of {}: {source}", path.display())]
+    PermissionError { path: PathBuf, source: io::Error },
+
+    #[error("Failed to write backup {}: {source}", path.display())]
+    BackupError { path: PathBuf, source: io::Error },
+
+    #[error("Character '{character}' cannot be saved as {encoding}")]
+    EncodeError {
+        encoding: &'static str,
+        character: char,
+    },
+
+    #[error("{} is a binary file", path.display())]
+    BinaryFile { path: PathBuf },
+
+    #[error("{} was changed on disk since it was loaded", path.display())]
+    ChangedOnDisk { path: PathBuf },
+}
+
+/// How a buffer's text is stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FileFormat {
+    /// Line ending (the rope always uses `\n`)
+    pub line_ending: LineEnding,
+    /// Character encoding
+    pub encoding: Encoding,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+}
+
+/// A change to the text, in bytes
+///
+/// Points are (line, byte column). This is the shape incremental parsers
+/// expect (e.g. tree-sitter's `InputEdit`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start_byte: usize,
+    pub old_end_byte: usize,
+    pub new_end_byte: usize,
+    pub start_point: (usize, usize),
+    pub old_end_point: (usize, usize),
+    pub new_end_point: (usize, usize),
+}
+
+/// State of the file on disk when it was loaded or saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiskState {
+    /// Modification time
+    modified: Option<SystemTime>,
+    /// File size in bytes
+    len: u64,
+    /// Hash of the file content (not computed for large files)
+    hash: Option<u64>,
+}
+
+impl DiskState {
+    /// Read the state of a file whose content has the given hash
+    fn read(path: &Path, hash: Option<u64>) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash,
+        })
+    }
 }
 
 /// A text buffer backed by a rope data structure
@@ -25,8 +115,41 @@ pub struct Buffer {
     /// Path to the file (if any)
     path: Option<PathBuf>,
 
-    /// Whether the buffer has been modified since last save
-    modified: bool,
+    /// Undo/redo history (also tracks the saved state)
+    history: History,
+
+    /// How the file is written on save
+    save_options: SaveOptions,
+
+    /// Format written on save
+    format: FileFormat,
+
+    /// Format of the file on disk
+    saved_format: FileFormat,
+
+    /// Loaded in large-file mode (see [`LoadOptions`])
+    large_file: bool,
+
+    /// File state on disk at load/save (to detect external modifications)
+    disk: Option<DiskState>,
+
+    /// Incremented on every content change
+    revision: u64,
+
+    /// Text edits of the last revisions, oldest first
+    edits: Vec<TextEdit>,
+
+    /// Revision before the first of `edits`
+    edits_revision: u64,
+
+    /// Indentation style (detected on load)
+    indentation: Indentation,
+
+    /// Number of cells between tab stops
+    tab_width: usize,
+
+    /// Settings from the `.editorconfig` files of the file
+    editorconfig: EditorconfigProperties,
 }
 
 impl Buffer {
@@ -35,35 +158,232 @@ impl Buffer {
         Self {
             rope: Rope::new(),
             path: None,
-            modified: false,
+            history: History::new(),
+            save_options: SaveOptions::default(),
+            format: FileFormat::default(),
+            saved_format: FileFormat::default(),
+            large_file: false,
+            disk: None,
+            revision: 0,
+            edits: Vec::new(),
+            edits_revision: 0,
+            indentation: Indentation::default(),
+            tab_width: grapheme::DEFAULT_TAB_WIDTH,
+            editorconfig: EditorconfigProperties::default(),
         }
     }
 
     /// Create a buffer with initial content
+    ///
+    /// Line endings are detected and normalised to `\n`, and the
+    /// indentation style is detected.
     pub fn with_content(text: &str) -> Self {
+        let format = FileFormat {
+            line_ending: LineEnding::detect(text),
+            ..FileFormat::default()
+        };
+        let rope = Rope::from_str(&normalize_line_endings(text));
         Self {
-            rope: Rope::from_str(text),
-            path: None,
-            modified: false,
+            indentation: detect_indentation(&rope),
+            rope,
+            format,
+            saved_format: format,
+            ..Self::new()
         }
     }
 
     /// Load a buffer from a file
+    ///
+    /// The encoding is detected (BOM, UTF-8, UTF-16, legacy 8-bit) and
+    /// remembered for saving. Binary files are refused with
+    /// [`BufferError::BinaryFile`].
     pub fn from_file(path: impl Into<PathBuf>) -> Result<Self, BufferError> {
+        Self::from_file_with_options(path, &LoadOptions::default())
+    }
+
+    /// Load a buffer from a file, using large-file mode above the threshold
+    ///
+    /// In large-file mode the file is streamed into the rope in chunks and
+    /// [`Buffer::is_large_file`] is set, so callers can turn off features
+    /// that need the whole buffer (highlighting, ...).
+    pub fn from_file_with_options(
+        path: impl Into<PathBuf>,
+        options: &LoadOptions,
+    ) -> Result<Self, BufferError> {
+        let path = path.into();
+        if std::fs::metadata(&path)?.len() >= options.large_file_threshold {
+            return Self::from_file_streaming(path, None);
+        }
+
+        let bytes = std::fs::read(&path)?;
+        if crate::is_binary(&bytes) {
+            return Err(BufferError::BinaryFile { path });
+        }
+        Ok(Self::from_decoded(path, encoding::decode(&bytes), &bytes))
+    }
+
+    /// Load a buffer from a file with a given encoding
+    pub fn from_file_with_encoding(
+        path: impl Into<PathBuf>,
+        encoding: Encoding,
+    ) -> Result<Self, BufferError> {
         let path = path.into();
-        let text = std::fs::read_to_string(&path)?;
+        let bytes = std::fs::read(&path)?;
+        Ok(Self::from_decoded(
+            path,
+            encoding::decode_with(&bytes, encoding),
+            &bytes,
+        ))
+    }
+
+    /// Stream a file into a large-file buffer
+    pub(crate) fn from_file_streaming(
+        path: PathBuf,
+        encoding: Option<Encoding>,
+    ) -> Result<Self, BufferError> {
+        let (rope, format) = load_streaming(&path, encoding)?;
         Ok(Self {
-            rope: Rope::from_str(&text),
+            indentation: detect_indentation(&rope),
+            rope,
+            disk: DiskState::read(&path, None),
             path: Some(path),
-            modified: false,
+            format,
+            saved_format: format,
+            large_file: true,
+            ..Self::new()
         })
     }
 
+    /// Build a file buffer from decoded content and the raw file bytes
+    fn from_decoded(path: PathBuf, decoded: encoding::Decoded, bytes: &[u8]) -> Self {
+        let mut buffer = Self::with_content(&decoded.text);
+        buffer.format.encoding = decoded.encoding;
+        buffer.format.bom = decoded.bom;
+        buffer.saved_format = buffer.format;
+        buffer.disk = DiskState::read(&path, Some(fnv1a(bytes.iter().copied())));
+        buffer.path = Some(path);
+        buffer
+    }
+
+    /// Reload the file with a different encoding
+    ///
+    /// Replaces the content and clears the undo history.
+    pub fn reopen_with_encoding(&mut self, encoding: Encoding) -> Result<(), BufferError> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        let reopened = if self.large_file {
+            Self::from_file_streaming(path, Some(encoding))?
+        } else {
+            Self::from_file_with_encoding(path, encoding)?
+        };
+        self.replace_with(reopened);
+        Ok(())
+    }
+
+    /// Reload the file from disk (e.g. after it was changed externally)
+    ///
+    /// The encoding is detected again. Replaces the content and clears the
+    /// undo history.
+    pub fn reload(&mut self) -> Result<(), BufferError> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        let reloaded = if self.large_file {
+            Self::from_file_streaming(path, None)?
+        } else {
+            Self::from_file(path)?
+        };
+        self.replace_with(reloaded);
+        self.apply_editorconfig_format();
+        Ok(())
+    }
+
+    /// Take over the content of a freshly loaded buffer
+    fn replace_with(&mut self, loaded: Buffer) {
+        self.rope = loaded.rope;
+        self.revision += 1;
+        // Not an edit: the whole text is new
+        self.edits.clear();
+        self.edits_revision = self.revision;
+        self.format = loaded.format;
+        self.saved_format = loaded.saved_format;
+        self.large_file = loaded.large_file;
+        self.disk = loaded.disk;
+        self.indentation = self.editorconfig.indentation(loaded.indentation);
+        self.history.clear();
+    }
+
+    /// Check if the file was changed on disk since it was loaded or saved
+    ///
+    /// A changed modification time alone (e.g. `touch`, or `git checkout`
+    /// restoring the same content) doesn't count if the content hash still
+    /// matches. A deleted file has nothing to overwrite and isn't changed.
+    pub fn is_changed_on_disk(&self) -> bool {
+        let (Some(path), Some(disk)) = (&self.path, &self.disk) else {
+            return false;
+        };
+        let Some(current) = DiskState::read(path, None) else {
+            return false;
+        };
+        if current.len != disk.len {
+            return true;
+        }
+        if current.modified == disk.modified {
+            return false;
+        }
+        match disk.hash {
+            Some(hash) => {
+                std::fs::read(path).map_or(true, |bytes| fnv1a(bytes.iter().copied()) != hash)
+            }
+            None => true,
+        }
+    }
+
+    /// Accept the file as it is on disk now as the base for saving
+    ///
+    /// Use this to keep the buffer's content after an external change; the
+    /// next [`Buffer::save`] then overwrites the file.
+    pub fn accept_disk_state(&mut self) {
+        if let Some(path) = &self.path {
+            // Large files aren't read again just for the hash
+            let hash = if self.large_file {
+                None
+            } else {
+                std::fs::read(path)
+                    .ok()
+                    .map(|bytes| fnv1a(bytes.iter().copied()))
+            };
+            self.disk = DiskState::read(path, hash);
+        }
+    }
+
+    /// Read the file's current content from disk (decoded, `\n` line endings)
+    pub fn disk_text(&self) -> Result<String, BufferError> {
+        let Some(path) = &self.path else {
+            return Ok(String::new());
+        };
+        let bytes = std::fs::read(path)?;
+        let decoded = encoding::decode_with(&bytes, self.format.encoding);
+        Ok(normalize_line_endings(&decoded.text).into_owned())
+    }
+
     /// Save the buffer to its file path
+    ///
+    /// The file is replaced atomically (see [`SaveOptions`]). Refuses with
+    /// [`BufferError::ChangedOnDisk`] if the file was changed by another
+    /// program since it was loaded; see [`Buffer::force_save`].
     pub fn save(&mut self) -> Result<(), BufferError> {
-        if let Some(path) = &self.path {
-            std::fs::write(path, self.rope.to_string())?;
-            self.modified = false;
+        if let (true, Some(path)) = (self.is_changed_on_disk(), &self.path) {
+            return Err(BufferError::ChangedOnDisk { path: path.clone() });
+        }
+        self.force_save()
+    }
+
+    /// Save the buffer even if the file was changed on disk
+    pub fn force_save(&mut self) -> Result<(), BufferError> {
+        if let Some(path) = self.path.clone() {
+            self.write_to(&path)?;
         }
         Ok(())
     }
@@ -71,12 +391,116 @@ impl Buffer {
     /// Save the buffer to a new path
     pub fn save_as(&mut self, path: impl Into<PathBuf>) -> Result<(), BufferError> {
         let path = path.into();
-        std::fs::write(&path, self.rope.to_string())?;
+        self.write_to(&path)?;
         self.path = Some(path);
-        self.modified = false;
         Ok(())
     }
 
+    /// Write the buffer to a path and remember it as saved
+    fn write_to(&mut self, path: &Path) -> Result<(), BufferError> {
+        let contents = self.contents()?;
+        write_atomic(path, &contents, &self.save_options)?;
+        // No hash for large files: checking it would read the whole file
+        let hash = (!self.large_file).then(|| fnv1a(contents.iter().copied()));
+        self.disk = DiskState::read(path, hash);
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// File contents as written on save (line ending, encoding and BOM)
+    fn contents(&self) -> Result<Vec<u8>, BufferError> {
+        let mut text = self.rope.to_string();
+        if self.format.line_ending != LineEnding::Lf {
+            text = text.replace('\n', self.format.line_ending.as_str());
+        }
+
+        let encoding = self.format.encoding;
+        let encoded = encoding
+            .encode(&text)
+            .map_err(|character| BufferError::EncodeError {
+                encoding: encoding.name(),
+                character,
+            })?;
+
+        if self.format.bom {
+            let mut bytes = encoding.bom().to_vec();
+            bytes.extend(encoded);
+            Ok(bytes)
+        } else {
+            Ok(encoded)
+        }
+    }
+
+    /// Remember the current state as the one on disk
+    fn mark_saved(&mut self) {
+        self.history.mark_saved();
+        self.saved_format = self.format;
+    }
+
+    /// Get the on-disk format (line ending, encoding, BOM)
+    pub fn format(&self) -> FileFormat {
+        self.format
+    }
+
+    /// Set the whole format used when saving (e.g. to restore an earlier one)
+    pub fn set_format(&mut self, format: FileFormat) {
+        self.format = format;
+    }
+
+    /// Get the line ending used when saving
+    pub fn line_ending(&self) -> LineEnding {
+        self.format.line_ending
+    }
+
+    /// Set the line ending used when saving (marks the buffer as modified)
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.format.line_ending = line_ending;
+    }
+
+    /// Get the encoding used when saving
+    pub fn encoding(&self) -> Encoding {
+        self.format.encoding
+    }
+
+    /// Set the encoding used when saving (marks the buffer as modified)
+    ///
+    /// A BOM is kept only for Unicode encodings.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.format.encoding = encoding;
+        self.format.bom = self.format.bom && !encoding.bom().is_empty();
+    }
+
+    /// Get the save options
+    pub fn save_options(&self) -> &SaveOptions {
+        &self.save_options
+    }
+
+    /// Set the save options (backup mode)
+    pub fn set_save_options(&mut self, options: SaveOptions) {
+        self.save_options = options;
+    }
+
+    /// Content revision (changes whenever the text changes)
+    ///
+    /// Cheap way to notice changes, e.g. for debounced background work.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Text edits since a revision, oldest first
+    ///
+    /// None if the edits are no longer known (too many, or the text was
+    /// replaced by a reload): the whole text has to be treated as new.
+    pub fn edits_since(&self, revision: u64) -> Option<&[TextEdit]> {
+        let skip = revision.checked_sub(self.edits_revision)?;
+        self.edits.get(usize::try_from(skip).ok()?..)
+    }
+
+    /// Check if the buffer was loaded in large-file mode
+    pub fn is_large_file(&self) -> bool {
+        self.large_file
+    }
+
     /// Get the total number of lines
     pub fn len_lines(&self) -> usize {
         self.rope.len_lines()
@@ -116,20 +540,264 @@ impl Buffer {
 
     /// Insert text at a character index
     pub fn insert(&mut self, char_idx: usize, text: &str) {
-        self.rope.insert(char_idx, text);
-        self.modified = true;
+        if text.is_empty() {
+            return;
+        }
+        self.insert_text(char_idx, text);
+        self.history.record(Edit::insert(char_idx, text));
     }
 
     /// Insert a single character at a character index
     pub fn insert_char(&mut self, char_idx: usize, ch: char) {
-        self.rope.insert_char(char_idx, ch);
-        self.modified = true;
+        self.insert_text(char_idx, ch.encode_utf8(&mut [0; 4]));
+        self.history.record(Edit::Insert {
+            at: char_idx,
+            text: ch.to_string(),
+            len: 1,
+        });
     }
 
     /// Remove a range of characters
     pub fn remove(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        let text = self.rope.slice(start..end).to_string();
+        self.remove_text(start, end);
+        self.history.record(Edit::Remove {
+            at: start,
+            text,
+            len: end - start,
+        });
+    }
+
+    // === Undo/Redo ===
+
+    /// Begin an undo transaction
+    ///
+    /// All edits until the matching [`Buffer::commit_transaction`] are undone
+    /// as one step. The cursors (primary first) are stored so they can be
+    /// restored on undo.
+    pub fn begin_transaction(&mut self, kind: EditKind, cursors: &[Cursor]) {
+        self.history.begin(kind, cursors);
+    }
+
+    /// Commit the current undo transaction
+    pub fn commit_transaction(&mut self, cursors: &[Cursor]) {
+        self.history.commit(cursors);
+    }
+
+    /// Undo the last transaction
+    ///
+    /// Returns the cursors to restore (primary first), or `None` if there was
+    /// nothing to undo.
+    pub fn undo(&mut self) -> Option<Vec<Cursor>> {
+        let transaction = self.history.undo()?.clone();
+        for edit in transaction.edits.iter().rev() {
+            self.apply(&edit.inverted());
+        }
+        let cursor = transaction
+            .cursor_before
+            .unwrap_or_else(|| self.cursor_at_edit(transaction.edits.first()));
+        Some([vec![cursor], transaction.extra_cursors_before].concat())
+    }
+
+    /// Redo the last undone transaction
+    ///
+    /// Returns the cursors to restore (primary first), or `None` if there was
+    /// nothing to redo.
+    pub fn redo(&mut self) -> Option<Vec<Cursor>> {
+        let transaction = self.history.redo()?.clone();
+        for edit in &transaction.edits {
+            self.apply(edit);
+        }
+        let cursor = transaction
+            .cursor_after
+            .unwrap_or_else(|| self.cursor_at_edit(transaction.edits.last()));
+        Some([vec![cursor], transaction.extra_cursors_after].concat())
+    }
+
+    /// Get the undo history
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Replace the undo history (e.g. with one restored from disk)
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+    }
+
+    /// Check if there is anything to undo
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    /// Check if there is anything to redo
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Apply an edit without recording it in the history
+    fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert { at, text, .. } => self.insert_text(*at, text),
+            Edit::Remove { at, len, .. } => self.remove_text(*at, *at + len),
+        }
+    }
+
+    /// Insert text into the rope as a new revision
+    fn insert_text(&mut self, char_idx: usize, text: &str) {
+        let start_byte = self.rope.char_to_byte(char_idx);
+        let start_point = self.byte_point(start_byte);
+        self.rope.insert(char_idx, text);
+        let new_end_byte = start_byte + text.len();
+        self.push_edit(TextEdit {
+            start_byte,
+            old_end_byte: start_byte,
+            new_end_byte,
+            start_point,
+            old_end_point: start_point,
+            new_end_point: self.byte_point(new_end_byte),
+        });
+    }
+
+    /// Remove a range of characters from the rope as a new revision
+    fn remove_text(&mut self, start: usize, end: usize) {
+        let start_byte = self.rope.char_to_byte(start);
+        let old_end_byte = self.rope.char_to_byte(end);
+        let start_point = self.byte_point(start_byte);
+        let old_end_point = self.byte_point(old_end_byte);
         self.rope.remove(start..end);
-        self.modified = true;
+        self.push_edit(TextEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte: start_byte,
+            start_point,
+            old_end_point,
+            new_end_point: start_point,
+        });
+    }
+
+    /// Remember an edit and start a new revision
+    fn push_edit(&mut self, edit: TextEdit) {
+        self.revision += 1;
+        if self.edits.len() == MAX_EDITS {
+            self.edits.drain(..MAX_EDITS / 2);
+            self.edits_revision += (MAX_EDITS / 2) as u64;
+        }
+        self.edits.push(edit);
+    }
+
+    /// (line, byte column) of a byte offset
+    fn byte_point(&self, byte: usize) -> (usize, usize) {
+        let line = self.rope.byte_to_line(byte);
+        (line, byte - self.rope.line_to_byte(line))
+    }
+
+    /// Cursor placed at the position of an edit (for edits recorded
+    /// outside a transaction)
+    fn cursor_at_edit(&self, edit: Option<&Edit>) -> Cursor {
+        let at = match edit {
+            Some(Edit::Insert { at, .. }) | Some(Edit::Remove { at, .. }) => *at,
+            None => 0,
+        };
+        let (line, column) = self.char_to_line_col(at.min(self.rope.len_chars()));
+        Cursor::at(line, column)
+    }
+
+    /// Get the indentation style
+    pub fn indentation(&self) -> Indentation {
+        self.indentation
+    }
+
+    /// Set the indentation style used for new indentation
+    ///
+    /// Existing lines are not changed (see [`crate::Editor::convert_indentation`]).
+    pub fn set_indentation(&mut self, indentation: Indentation) {
+        self.indentation = indentation;
+    }
+
+    /// Get the `.editorconfig` settings of the file
+    pub fn editorconfig(&self) -> &EditorconfigProperties {
+        &self.editorconfig
+    }
+
+    /// Resolve the `.editorconfig` chain of the file (see
+    /// [`EditorconfigProperties::resolve`]) and apply it
+    pub fn load_editorconfig(&mut self) {
+        if let Some(path) = &self.path {
+            self.set_editorconfig(EditorconfigProperties::resolve(path));
+        }
+    }
+
+    /// Apply `.editorconfig` settings
+    ///
+    /// Indentation, tab width, line ending and charset take effect right away
+    /// (whitespace rules are applied by [`crate::Editor::save`]). The line
+    /// ending and charset become the file's format: the file is converted on
+    /// the next save, but that alone doesn't count as a change. Call this
+    /// once after loading, so that later explicit format changes win.
+    pub fn set_editorconfig(&mut self, editorconfig: EditorconfigProperties) {
+        if let Some(tab_width) = editorconfig.tab_width {
+            self.set_tab_width(tab_width);
+        }
+        self.indentation = editorconfig.indentation(self.indentation);
+        self.editorconfig = editorconfig;
+        self.apply_editorconfig_format();
+    }
+
+    /// Take the `.editorconfig` line ending and charset as the file's format
+    fn apply_editorconfig_format(&mut self) {
+        if let Some(line_ending) = self.editorconfig.end_of_line {
+            self.format.line_ending = line_ending;
+        }
+        if let Some((encoding, bom)) = self.editorconfig.charset {
+            self.format.encoding = encoding;
+            self.format.bom = bom;
+        }
+        self.saved_format = self.format;
+    }
+
+    /// Get the number of cells between tab stops
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Set the number of cells between tab stops
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+    }
+
+    /// Get the column of the grapheme cluster boundary before a column
+    pub fn prev_grapheme_boundary(&self, line: usize, column: usize) -> usize {
+        self.line(line)
+            .map_or(0, |slice| grapheme::prev_boundary(slice, column))
+    }
+
+    /// Get the column of the grapheme cluster boundary after a column
+    pub fn next_grapheme_boundary(&self, line: usize, column: usize) -> usize {
+        self.line(line)
+            .map_or(0, |slice| grapheme::next_boundary(slice, column))
+    }
+
+    /// Move a column back to the start of the grapheme cluster containing it
+    pub fn floor_grapheme_boundary(&self, line: usize, column: usize) -> usize {
+        self.line(line)
+            .map_or(0, |slice| grapheme::floor_boundary(slice, column))
+    }
+
+    /// Get the display column (in terminal cells) of a line/column
+    pub fn display_column(&self, line: usize, column: usize) -> usize {
+        self.line(line).map_or(0, |slice| {
+            grapheme::display_column(slice, column, self.tab_width())
+        })
+    }
+
+    /// Get the column at a display column (in terminal cells) of a line
+    pub fn column_at_display(&self, line: usize, display: usize) -> usize {
+        self.line(line).map_or(0, |slice| {
+            grapheme::column_at_display(slice, display, self.tab_width())
+        })
     }
 
     /// Convert line/column to character index
@@ -151,6 +819,18 @@ impl Buffer {
         (line, col)
     }
 
+    /// Convert line/column to byte offset
+    pub fn line_col_to_byte(&self, line: usize, col: usize) -> Option<usize> {
+        self.line_col_to_char(line, col)
+            .map(|char_idx| self.rope.char_to_byte(char_idx))
+    }
+
+    /// Convert byte offset to line/column (clamped to the buffer)
+    pub fn byte_to_line_col(&self, byte: usize) -> (usize, usize) {
+        let char_idx = self.rope.byte_to_char(byte.min(self.rope.len_bytes()));
+        self.char_to_line_col(char_idx)
+    }
+
     /// Get the file path
     pub fn path(&self) -> Option<&PathBuf> {
         self.path.as_ref()
@@ -158,7 +838,7 @@ impl Buffer {
 
     /// Check if the buffer has been modified
     pub fn is_modified(&self) -> bool {
-        self.modified
+        !self.history.is_at_save_point() || self.format != self.saved_format
     }
 
     /// Get a reference to the underlying rope
@@ -170,6 +850,44 @@ impl Buffer {
     pub fn text(&self) -> String {
         self.rope.to_string()
     }
+
+    /// Hash of the buffer content (64-bit FNV-1a, stable across runs)
+    ///
+    /// Computed over the rope chunks, so the text is never copied.
+    pub fn content_hash(&self) -> u64 {
+        fnv1a(self.rope.chunks().flat_map(str::bytes))
+    }
+
+    /// Bytes from a byte offset to the end of its rope chunk
+    ///
+    /// Lets incremental readers (e.g. parsers) walk the text without copying
+    /// it. Empty at or past the end of the buffer.
+    pub fn chunk_at_byte(&self, byte: usize) -> &[u8] {
+        if byte >= self.rope.len_bytes() {
+            return &[];
+        }
+        let (chunk, start, _, _) = self.rope.chunk_at_byte(byte);
+        &chunk.as_bytes()[byte - start..]
+    }
+}
+
+/// Detect the indentation from the first lines of a rope
+fn detect_indentation(rope: &Rope) -> Indentation {
+    let indents: Vec<String> = rope
+        .lines()
+        .take(DETECT_LINES)
+        .filter_map(|line| {
+            let mut chars = line.chars().peekable();
+            let indent: String =
+                std::iter::from_fn(|| chars.next_if(|&c| c == ' ' || c == '\t')).collect();
+            // Blank lines don't tell anything
+            chars
+                .peek()
+                .is_some_and(|&c| c != '\n' && c != '\r')
+                .then_some(indent)
+        })
+        .collect();
+    Indentation::detect(indents.iter().map(String::as_str)).unwrap_or_default()
 }
 
 impl Default for Buffer {
@@ -212,6 +930,222 @@ mod tests {
         assert_eq!(buffer.text(), "HelloWorld");
     }
 
+    #[test]
+    fn test_crlf_normalized() {
+        let buffer = Buffer::with_content("Hello\r\nWorld\r\n");
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+        assert_eq!(buffer.text(), "Hello\nWorld\n");
+        assert_eq!(buffer.line_len(0), Some(5));
+    }
+
+    #[test]
+    fn test_save_preserves_line_ending() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.php");
+        std::fs::write(&path, "<?php\r\necho 1;\r\n").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        buffer.insert(buffer.len_chars(), "echo 2;\n");
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "<?php\r\necho 1;\r\necho 2;\r\n"
+        );
+
+        buffer.set_line_ending(LineEnding::Lf);
+        assert!(buffer.is_modified());
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "<?php\necho 1;\necho 2;\n"
+        );
+    }
+
+    #[test]
+    fn test_legacy_encoding_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.php");
+        // "// Grüße" in ISO-8859-1
+        std::fs::write(&path, b"// Gr\xFC\xDFe\n").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        assert_eq!(buffer.encoding(), Encoding::Iso8859_1);
+        assert_eq!(buffer.text(), "// Grüße\n");
+
+        buffer.insert(buffer.len_chars(), "// Äpfel\n");
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"// Gr\xFC\xDFe\n// \xC4pfel\n"
+        );
+
+        // € does not exist in Latin-1
+        buffer.insert(0, "€");
+        assert!(matches!(
+            buffer.save(),
+            Err(BufferError::EncodeError {
+                character: '€', ..
+            })
+        ));
+
+        buffer.set_encoding(Encoding::Utf8);
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "€// Grüße\n// Äpfel\n"
+        );
+    }
+
+    #[test]
+    fn test_reopen_with_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quotes.txt");
+        std::fs::write(&path, b"\x84Hallo\x93").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        assert_eq!(buffer.text(), "„Hallo“");
+
+        buffer.reopen_with_encoding(Encoding::Iso8859_1).unwrap();
+        assert_eq!(buffer.encoding(), Encoding::Iso8859_1);
+        assert_eq!(buffer.text(), "\u{84}Hallo\u{93}");
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn test_large_file_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        std::fs::write(&path, "GET /\r\nPOST /login\r\n").unwrap();
+
+        let options = LoadOptions {
+            large_file_threshold: 10,
+        };
+        let mut buffer = Buffer::from_file_with_options(&path, &options).unwrap();
+        assert!(buffer.is_large_file());
+        assert_eq!(buffer.text(), "GET /\nPOST /login\n");
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+
+        buffer.insert(0, "# ");
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# GET /\r\nPOST /login\r\n"
+        );
+        // Never hashed: checking the hash would read the whole file
+        assert_eq!(buffer.disk.unwrap().hash, None);
+        buffer.accept_disk_state();
+        assert_eq!(buffer.disk.unwrap().hash, None);
+        assert!(!Buffer::from_file(&path).unwrap().is_large_file());
+    }
+
+    #[test]
+    fn test_save_refuses_external_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("composer.json");
+        std::fs::write(&path, "{}").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        buffer.insert(1, "\"name\": \"a\"");
+        assert!(!buffer.is_changed_on_disk());
+
+        std::fs::write(&path, "{\"require\": {}}").unwrap();
+        assert!(buffer.is_changed_on_disk());
+        assert!(matches!(
+            buffer.save(),
+            Err(BufferError::ChangedOnDisk { .. })
+        ));
+        assert_eq!(buffer.disk_text().unwrap(), "{\"require\": {}}");
+
+        buffer.force_save().unwrap();
+        assert!(!buffer.is_changed_on_disk());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"name\": \"a\"}");
+
+        // Same content rewritten: not a change
+        std::fs::write(&path, "{\"name\": \"a\"}").unwrap();
+        assert!(!buffer.is_changed_on_disk());
+
+        std::fs::write(&path, "{}\n").unwrap();
+        buffer.reload().unwrap();
+        assert_eq!(buffer.text(), "{}\n");
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let mut buffer = Buffer::with_content("Hello");
+        let hash = buffer.content_hash();
+        assert_eq!(hash, Buffer::with_content("Hello").content_hash());
+
+        buffer.insert(5, "!");
+        assert_ne!(buffer.content_hash(), hash);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut buffer = Buffer::with_content("Hello");
+        buffer.insert(5, " World");
+        buffer.remove(0, 1);
+        assert_eq!(buffer.text(), "ello World");
+
+        buffer.undo();
+        assert_eq!(buffer.text(), "Hello World");
+        buffer.undo();
+        assert_eq!(buffer.text(), "Hello");
+        assert!(!buffer.is_modified());
+        assert!(buffer.undo().is_none());
+
+        buffer.redo();
+        assert_eq!(buffer.text(), "Hello World");
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn test_edits_since() {
+        let mut buffer = Buffer::with_content("ä\nb");
+        let start = buffer.revision();
+        buffer.insert(2, "xy\n");
+        buffer.remove(0, 1);
+        assert_eq!(buffer.text(), "\nxy\nb");
+
+        let edits = buffer.edits_since(start).unwrap();
+        assert_eq!(
+            edits,
+            [
+                TextEdit {
+                    start_byte: 3,
+                    old_end_byte: 3,
+                    new_end_byte: 6,
+                    start_point: (1, 0),
+                    old_end_point: (1, 0),
+                    new_end_point: (2, 0),
+                },
+                TextEdit {
+                    start_byte: 0,
+                    old_end_byte: 2,
+                    new_end_byte: 0,
+                    start_point: (0, 0),
+                    old_end_point: (0, 2),
+                    new_end_point: (0, 0),
+                },
+            ]
+        );
+        assert_eq!(buffer.edits_since(start + 1).unwrap(), &edits[1..]);
+        assert!(buffer.edits_since(buffer.revision()).unwrap().is_empty());
+        assert!(buffer.edits_since(buffer.revision() + 1).is_none());
+
+        // Undo is an edit too
+        buffer.undo();
+        assert_eq!(buffer.edits_since(start).unwrap().len(), 3);
+
+        // Only the last edits are kept
+        for _ in 0..MAX_EDITS {
+            buffer.insert_char(0, 'a');
+        }
+        assert!(buffer.edits_since(start).is_none());
+        let recent = buffer.revision() - 10;
+        assert_eq!(buffer.edits_since(recent).unwrap().len(), 10);
+    }
+
     #[test]
     fn test_line_col_conversion() {
         let buffer = Buffer::with_content("Hello\nWorld\nTest");
@@ -224,4 +1158,45 @@ mod tests {
         assert_eq!(buffer.char_to_line_col(6), (1, 0));
         assert_eq!(buffer.char_to_line_col(14), (2, 2));
     }
+
+    #[test]
+    fn test_editorconfig_applies_on_save() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(crate::EDITORCONFIG_FILE),
+            "root = true\n[*.txt]\nend_of_line = crlf\nindent_style = tab\ntab_width = 8\n",
+        )
+        .unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "a\n    b\n").unwrap();
+
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        assert_eq!(buffer.indentation(), Indentation::spaces(4));
+        buffer.load_editorconfig();
+        assert_eq!(buffer.indentation(), Indentation::tabs());
+        assert_eq!(buffer.tab_width(), 8);
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+        assert!(!buffer.is_modified());
+
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"a\r\n    b\r\n");
+
+        // An explicit choice wins over the `.editorconfig`
+        buffer.set_line_ending(LineEnding::Lf);
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"a\n    b\n");
+        assert_eq!(buffer.line_ending(), LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_detects_indentation() {
+        let buffer = Buffer::with_content("a:\n  b:\n    c: 1\n\n  d: 2\n");
+        assert_eq!(buffer.indentation(), Indentation::spaces(2));
+
+        let buffer = Buffer::with_content("all:\r\n\tcc a.c\r\n\r\n\trm a.o\r\n");
+        assert_eq!(buffer.indentation(), Indentation::tabs());
+
+        let buffer = Buffer::with_content("no indentation\n");
+        assert_eq!(buffer.indentation(), Indentation::default());
+    }
 }
@@ -0,0 +1,262 @@
+//! User configuration
+//!
+//! Loaded from `~/.config/four-code/config.toml`. Every key is optional and a
+//! missing file means all defaults.
+//!
+//! ```toml
+//! [editor]
+//! rainbow_brackets = true                 # colour brackets by nesting depth
+//!
+//! [files]
+//! backup = "suffix"                      # "none", "suffix" or { directory = "..." }
+//! large_file_threshold_mb = 50            # large-file mode from this size on
+//!
+//! [search]
+//! exclude = ["vendor/", "var/cache/"]     # not searched in the project (globs)
+//!
+//! [languages.php]                         # per file extension
+//! word_chars = "$"                        # word characters besides letters, digits, `_`
+//! auto_pairs = "()[]{}\"\"''"               # auto-closed pairs (opening, closing)
+//! indent_after = "{([:"                   # Enter indents further after these
+//! auto_indent = true                      # keep indentation on Enter, dedent closers
+//! tab_width = 4                           # cells between tab stops
+//! ```
+
+use crate::editing::parse_pairs;
+use crate::{
+    BackupMode, EditingRules, LoadOptions, WordChars, DEFAULT_LARGE_FILE_THRESHOLD,
+    DEFAULT_TAB_WIDTH,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config: {0}")]
+    ReadError(#[from] std::io::Error),
+
+    #[error("Invalid config: {0}")]
+    ParseError(#[from] toml::de::Error),
+}
+
+/// Top-level configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Editor display and behaviour
+    pub editor: EditorConfig,
+
+    /// File handling
+    pub files: FilesConfig,
+
+    /// Project search
+    pub search: SearchConfig,
+
+    /// Per-language settings, keyed by file extension (`[languages.php]`)
+    pub languages: HashMap<String, LanguageConfig>,
+}
+
+/// Per-language configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// Characters that are part of words besides letters, digits and `_`
+    /// (built-in defaults: `$` for PHP, `-` for CSS)
+    pub word_chars: Option<String>,
+
+    /// Auto-closed pairs, written as consecutive characters (`"()[]{}"`)
+    pub auto_pairs: Option<String>,
+
+    /// Characters at a line end after which Enter indents further
+    pub indent_after: Option<String>,
+
+    /// Keep indentation on Enter and dedent closers
+    pub auto_indent: Option<bool>,
+
+    /// Number of cells between tab stops
+    pub tab_width: Option<usize>,
+}
+
+/// Editor configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    /// Colour brackets by nesting depth
+    pub rainbow_brackets: bool,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            rainbow_brackets: true,
+        }
+    }
+}
+
+/// File handling configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FilesConfig {
+    /// Backup of the previous content when saving
+    pub backup: BackupMode,
+
+    /// File size in MiB from which files open in large-file mode
+    pub large_file_threshold_mb: u64,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            backup: BackupMode::default(),
+            large_file_threshold_mb: DEFAULT_LARGE_FILE_THRESHOLD / (1024 * 1024),
+        }
+    }
+}
+
+/// Project search configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Globs excluded from project search by default (besides `.gitignore`d
+    /// files)
+    pub exclude: Vec<String>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            exclude: vec![String::from("vendor/")],
+        }
+    }
+}
+
+impl FilesConfig {
+    /// Options for loading files
+    pub fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            large_file_threshold: self.large_file_threshold_mb.saturating_mul(1024 * 1024),
+        }
+    }
+}
+
+impl Config {
+    /// Path of the user config file
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("four-code").join("config.toml"))
+    }
+
+    /// Load the user config (defaults if there is no config file)
+    pub fn load() -> Result<Self, ConfigError> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_toml(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Parse a config from TOML
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Settings for a file's language (by extension)
+    fn language<'a>(&'a self, path: &'a Path) -> (&'a str, Option<&'a LanguageConfig>) {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        (extension, self.languages.get(extension))
+    }
+
+    /// Word characters for a file (configured, else built-in)
+    pub fn word_chars(&self, path: &Path) -> WordChars {
+        let (extension, language) = self.language(path);
+        match language.and_then(|language| language.word_chars.as_deref()) {
+            Some(extra) => WordChars::new(extra),
+            None => WordChars::for_extension(extension),
+        }
+    }
+
+    /// Editing rules for a file (built-in, with configured overrides)
+    pub fn editing_rules(&self, path: &Path) -> EditingRules {
+        let (extension, language) = self.language(path);
+        let mut rules = EditingRules::for_extension(extension);
+        if let Some(language) = language {
+            if let Some(pairs) = &language.auto_pairs {
+                rules.pairs = parse_pairs(pairs);
+            }
+            if let Some(indent_after) = &language.indent_after {
+                rules.indent_after = indent_after.chars().collect();
+            }
+            if let Some(auto_indent) = language.auto_indent {
+                rules.auto_indent = auto_indent;
+            }
+        }
+        rules
+    }
+
+    /// Tab width for a file (configured, else the default)
+    pub fn tab_width(&self, path: &Path) -> usize {
+        let (_, language) = self.language(path);
+        language
+            .and_then(|language| language.tab_width)
+            .unwrap_or(DEFAULT_TAB_WIDTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config() {
+        let config = Config::from_toml("").unwrap();
+        assert!(config.editor.rainbow_brackets);
+        assert_eq!(config.files.backup, BackupMode::None);
+        assert_eq!(config.search.exclude, ["vendor/"]);
+        assert_eq!(
+            config.files.load_options().large_file_threshold,
+            DEFAULT_LARGE_FILE_THRESHOLD
+        );
+    }
+
+    #[test]
+    fn test_editing_rules_per_language() {
+        let config =
+            Config::from_toml("[languages.php]\nauto_pairs = \"()\"\nauto_indent = false").unwrap();
+        let rules = config.editing_rules(Path::new("index.php"));
+        assert_eq!(rules.pairs, [('(', ')')]);
+        assert!(!rules.auto_indent);
+        assert_eq!(config.tab_width(Path::new("index.php")), DEFAULT_TAB_WIDTH);
+        assert_eq!(
+            config.editing_rules(Path::new("app.js")),
+            EditingRules::for_extension("js")
+        );
+    }
+
+    #[test]
+    fn test_word_chars_per_language() {
+        let config = Config::from_toml("[languages.js]\nword_chars = \"$\"").unwrap();
+        assert!(config.word_chars(Path::new("app.js")).is_word_char('$'));
+        assert!(config.word_chars(Path::new("index.php")).is_word_char('$'));
+        assert!(!config.word_chars(Path::new("main.rs")).is_word_char('$'));
+    }
+
+    #[test]
+    fn test_backup_modes() {
+        let config = Config::from_toml("[files]\nbackup = \"suffix\"").unwrap();
+        assert_eq!(config.files.backup, BackupMode::Suffix);
+
+        let config =
+            Config::from_toml("[files]\nbackup = { directory = \"/tmp/backups\" }").unwrap();
+        assert_eq!(
+            config.files.backup,
+            BackupMode::Directory(PathBuf::from("/tmp/backups"))
+        );
+    }
+}
@@ -2,8 +2,15 @@
 //!
 //! Provides cursor movement and position tracking for the editor.
 
+use serde::{Deserialize, Serialize};
+use std::ops::{Range, RangeInclusive};
+
 /// A position in the buffer (line, column)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+///
+/// Positions order by line, then column (document order).
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
 pub struct Position {
     /// Line number (0-indexed)
     pub line: usize,
@@ -24,7 +31,7 @@ impl Position {
 }
 
 /// A cursor in the buffer with optional selection
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Cursor {
     /// Current cursor position
     pub position: Position,
@@ -33,8 +40,9 @@ pub struct Cursor {
     /// When set, text between anchor and position is selected
     pub anchor: Option<Position>,
 
-    /// Preferred column for vertical movement
-    /// Remembers the column when moving through shorter lines
+    /// Preferred (sticky) display column for vertical movement
+    /// Remembers the column when moving through shorter lines; reset by
+    /// horizontal motions and edits
     preferred_column: Option<usize>,
 }
 
@@ -63,47 +71,69 @@ impl Cursor {
         self.preferred_column = None;
     }
 
-    /// Move cursor up by n lines
-    pub fn move_up(&mut self, n: usize, line_lengths: impl Fn(usize) -> usize) {
+    /// Get the preferred (sticky) display column, if one is remembered
+    pub fn preferred_column(&self) -> Option<usize> {
+        self.preferred_column
+    }
+
+    /// Set the preferred display column for vertical movement
+    pub fn set_preferred_column(&mut self, display_column: usize) {
+        self.preferred_column = Some(display_column);
+    }
+
+    /// Forget the preferred column (after an edit)
+    pub fn reset_preferred_column(&mut self) {
+        self.preferred_column = None;
+    }
+
+    /// Move cursor up by n lines, keeping the preferred display column
+    ///
+    /// `display_column(line, column)` converts a column to a display column,
+    /// `column_at(line, display_column)` converts back (clamped to the line).
+    pub fn move_up(
+        &mut self,
+        n: usize,
+        display_column: impl Fn(usize, usize) -> usize,
+        column_at: impl Fn(usize, usize) -> usize,
+    ) {
         if self.position.line == 0 {
             return;
         }
-
-        // Remember preferred column
-        if self.preferred_column.is_none() {
-            self.preferred_column = Some(self.position.column);
-        }
-
         let new_line = self.position.line.saturating_sub(n);
-        let line_len = line_lengths(new_line);
-        let preferred = self.preferred_column.unwrap_or(self.position.column);
-
-        self.position.line = new_line;
-        self.position.column = preferred.min(line_len);
+        self.move_to_line(new_line, display_column, column_at);
     }
 
-    /// Move cursor down by n lines
+    /// Move cursor down by n lines, keeping the preferred display column
+    ///
+    /// See [`Cursor::move_up`] for the column conversions.
     pub fn move_down(
         &mut self,
         n: usize,
         total_lines: usize,
-        line_lengths: impl Fn(usize) -> usize,
+        display_column: impl Fn(usize, usize) -> usize,
+        column_at: impl Fn(usize, usize) -> usize,
     ) {
         if self.position.line >= total_lines.saturating_sub(1) {
             return;
         }
+        let new_line = (self.position.line + n).min(total_lines.saturating_sub(1));
+        self.move_to_line(new_line, display_column, column_at);
+    }
 
+    /// Move to another line at the preferred display column
+    fn move_to_line(
+        &mut self,
+        line: usize,
+        display_column: impl Fn(usize, usize) -> usize,
+        column_at: impl Fn(usize, usize) -> usize,
+    ) {
         // Remember preferred column
-        if self.preferred_column.is_none() {
-            self.preferred_column = Some(self.position.column);
-        }
-
-        let new_line = (self.position.line + n).min(total_lines.saturating_sub(1));
-        let line_len = line_lengths(new_line);
-        let preferred = self.preferred_column.unwrap_or(self.position.column);
+        let preferred = *self
+            .preferred_column
+            .get_or_insert_with(|| display_column(self.position.line, self.position.column));
 
-        self.position.line = new_line;
-        self.position.column = preferred.min(line_len);
+        self.position.line = line;
+        self.position.column = column_at(line, preferred);
     }
 
     /// Move cursor left by n characters
@@ -196,6 +226,45 @@ impl Cursor {
     }
 }
 
+/// A rectangular (block/column) selection
+///
+/// Columns of the corners are display columns (terminal cells), so the
+/// rectangle stays straight across tabs and wide characters, and may extend
+/// past the end of short lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSelection {
+    /// Corner where the selection started (line, display column)
+    pub anchor: Position,
+    /// Corner moved when extending the selection (line, display column)
+    pub head: Position,
+}
+
+impl BlockSelection {
+    /// Create an empty block selection at a line and display column
+    pub fn at(line: usize, display_column: usize) -> Self {
+        let corner = Position::new(line, display_column);
+        Self {
+            anchor: corner,
+            head: corner,
+        }
+    }
+
+    /// Lines covered by the selection
+    pub fn lines(&self) -> RangeInclusive<usize> {
+        self.anchor.line.min(self.head.line)..=self.anchor.line.max(self.head.line)
+    }
+
+    /// Display columns covered by the selection
+    pub fn columns(&self) -> Range<usize> {
+        self.anchor.column.min(self.head.column)..self.anchor.column.max(self.head.column)
+    }
+
+    /// Check if a cell (line, display column) is inside the selection
+    pub fn contains(&self, line: usize, display_column: usize) -> bool {
+        self.lines().contains(&line) && self.columns().contains(&display_column)
+    }
+}
+
 impl Default for Cursor {
     fn default() -> Self {
         Self::new()
@@ -216,6 +285,15 @@ mod tests {
         }
     }
 
+    // Single-width characters: display columns are columns
+    fn display_column(_line: usize, column: usize) -> usize {
+        column
+    }
+
+    fn column_at(line: usize, display_column: usize) -> usize {
+        display_column.min(line_lengths(line))
+    }
+
     #[test]
     fn test_cursor_movement() {
         let mut cursor = Cursor::new();
@@ -225,7 +303,7 @@ mod tests {
         assert_eq!(cursor.position, Position::new(0, 3));
 
         // Move down
-        cursor.move_down(1, 3, line_lengths);
+        cursor.move_down(1, 3, display_column, column_at);
         assert_eq!(cursor.position, Position::new(1, 3));
 
         // Move left
@@ -233,7 +311,7 @@ mod tests {
         assert_eq!(cursor.position, Position::new(1, 1));
 
         // Move up
-        cursor.move_up(1, line_lengths);
+        cursor.move_up(1, display_column, column_at);
         assert_eq!(cursor.position, Position::new(0, 1));
     }
 
@@ -255,14 +333,65 @@ mod tests {
         let mut cursor = Cursor::at(0, 5);
 
         // Move down to shorter line - should clamp
-        cursor.move_down(2, 3, line_lengths);
+        cursor.move_down(2, 3, display_column, column_at);
         assert_eq!(cursor.position, Position::new(2, 1)); // "!" has length 1
 
         // Move up - should restore preferred column
-        cursor.move_up(2, line_lengths);
+        cursor.move_up(2, display_column, column_at);
         assert_eq!(cursor.position, Position::new(0, 5));
     }
 
+    #[test]
+    fn test_preferred_column_survives_short_lines() {
+        let mut cursor = Cursor::at(0, 4);
+
+        // Through the short line one step at a time (like page down)
+        cursor.move_down(1, 3, display_column, column_at);
+        cursor.move_down(1, 3, display_column, column_at);
+        assert_eq!(cursor.position, Position::new(2, 1));
+        assert_eq!(cursor.preferred_column(), Some(4));
+        cursor.move_up(1, display_column, column_at);
+        assert_eq!(cursor.position, Position::new(1, 4));
+
+        // Horizontal motion resets it
+        cursor.move_down(1, 3, display_column, column_at);
+        cursor.move_left(1, line_lengths);
+        assert_eq!(cursor.preferred_column(), None);
+        cursor.move_up(1, display_column, column_at);
+        assert_eq!(cursor.position, Position::new(1, 0));
+    }
+
+    #[test]
+    fn test_preferred_column_is_a_display_column() {
+        // Line 0: "中文x" (columns 0..3, display 0..5), line 1: "abcdef"
+        let display = |line: usize, column: usize| if line == 0 { column * 2 } else { column };
+        let column_at = |line: usize, display: usize| {
+            if line == 0 {
+                (display / 2).min(3)
+            } else {
+                display.min(6)
+            }
+        };
+
+        let mut cursor = Cursor::at(0, 2);
+        cursor.move_down(1, 2, display, column_at);
+        assert_eq!(cursor.position, Position::new(1, 4));
+        cursor.move_up(1, display, column_at);
+        assert_eq!(cursor.position, Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_block_selection_bounds() {
+        let mut block = BlockSelection::at(4, 6);
+        block.head = Position::new(2, 2);
+
+        assert_eq!(block.lines(), 2..=4);
+        assert_eq!(block.columns(), 2..6);
+        assert!(block.contains(3, 2));
+        assert!(!block.contains(3, 6));
+        assert!(!block.contains(5, 3));
+    }
+
     #[test]
     fn test_selection() {
         let mut cursor = Cursor::at(1, 2);
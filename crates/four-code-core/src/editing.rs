@@ -0,0 +1,240 @@
+//! Editing rules: auto-closing pairs and auto-indentation
+//!
+//! Typing an opening bracket or quote inserts the closing one too (not in
+//! strings and comments, and not for quotes next to words like `don't`), and
+//! typing over an auto-inserted closer steps past it. Enter keeps the
+//! indentation of the current line and indents further after `{`, `(`, `[`
+//! or `:`; typing a closer as the first character of a line dedents it.
+//!
+//! Rules have built-in defaults per file extension and can be configured per
+//! language (`[languages.php]` in the config).
+
+/// Auto-closing and auto-indentation rules of a language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditingRules {
+    /// Auto-closed pairs (opening, closing); quotes close themselves
+    pub pairs: Vec<(char, char)>,
+    /// Characters at the end of a line after which Enter indents further
+    pub indent_after: Vec<char>,
+    /// Keep indentation on Enter and dedent closers
+    pub auto_indent: bool,
+}
+
+impl Default for EditingRules {
+    fn default() -> Self {
+        Self {
+            pairs: parse_pairs("()[]{}\"\"''"),
+            indent_after: "{([:".chars().collect(),
+            auto_indent: true,
+        }
+    }
+}
+
+impl EditingRules {
+    /// Built-in rules for a file extension
+    pub fn for_extension(extension: &str) -> Self {
+        let defaults = Self::default();
+        match extension {
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "mts" | "cts" | "tsx" => Self {
+                pairs: parse_pairs("()[]{}\"\"''``"),
+                ..defaults
+            },
+            // `'` starts lifetimes
+            "rs" => Self {
+                pairs: parse_pairs("()[]{}\"\""),
+                ..defaults
+            },
+            "yaml" | "yml" => Self {
+                indent_after: vec![':'],
+                ..defaults
+            },
+            // Prose: apostrophes are no quotes, colons end sentences
+            "md" | "markdown" | "txt" | "" => Self {
+                pairs: parse_pairs("()[]{}"),
+                indent_after: Vec::new(),
+                ..defaults
+            },
+            _ => defaults,
+        }
+    }
+
+    /// Closing character of an auto-closed opening character
+    pub fn closer(&self, open: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|&&(o, _)| o == open)
+            .map(|&(_, close)| close)
+    }
+
+    /// Check if a character closes an auto-closed pair
+    pub fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|&(_, close)| close == c)
+    }
+
+    /// Closing character to insert after typing `open` between `prev` and
+    /// `next` (None: don't auto-close)
+    ///
+    /// Brackets are closed before whitespace, closers and `,`/`;` only, so
+    /// typing in front of existing code doesn't add stray closers. Quotes are
+    /// not closed next to word characters.
+    pub fn auto_close(
+        &self,
+        open: char,
+        prev: Option<char>,
+        next: Option<char>,
+        is_word_char: impl Fn(char) -> bool,
+    ) -> Option<char> {
+        let close = self.closer(open)?;
+        let next_ok =
+            next.is_none_or(|c| c.is_whitespace() || self.is_closer(c) || c == ',' || c == ';');
+        if open == close {
+            let next_ok = next_ok && next != Some(close);
+            (next_ok && !prev.is_some_and(&is_word_char)).then_some(close)
+        } else {
+            next_ok.then_some(close)
+        }
+    }
+
+    /// Check if a closing character dedents when typed first on a line
+    pub fn is_dedent_closer(&self, c: char) -> bool {
+        self.auto_indent
+            && self.indent_after.iter().any(|&open| {
+                self.closer(open)
+                    .is_some_and(|close| close == c && close != open)
+            })
+    }
+
+    /// Indentation of a new line split at the cursor
+    ///
+    /// `before` and `after` are the text of the line before and after the
+    /// cursor, `unit` is one level of indentation. Returns the indentation of
+    /// the new line, and of a further line for the closer right after the
+    /// cursor (Enter between `{}`).
+    pub fn newline_indent(
+        &self,
+        before: &str,
+        after: &str,
+        unit: &str,
+    ) -> (String, Option<String>) {
+        let indent = leading_whitespace(before);
+        if !self.auto_indent {
+            return (String::new(), None);
+        }
+        let Some(last) = before.trim_end().chars().last() else {
+            return (indent.to_string(), None);
+        };
+        if !self.indent_after.contains(&last) {
+            return (indent.to_string(), None);
+        }
+
+        let inner = format!("{indent}{unit}");
+        let splits_pair = self
+            .closer(last)
+            .is_some_and(|close| close != last && after.trim_start().starts_with(close));
+        (inner, splits_pair.then(|| indent.to_string()))
+    }
+
+    /// Indentation one level (`unit`) less than `indent`
+    pub fn dedent<'a>(&self, indent: &'a str, unit: &str) -> &'a str {
+        if let Some(rest) = indent.strip_suffix(unit) {
+            return rest;
+        }
+        if let Some(rest) = indent.strip_suffix('\t') {
+            return rest;
+        }
+        let spaces = indent.len() - indent.trim_end_matches(' ').len();
+        &indent[..indent.len() - spaces.min(unit.len().max(1))]
+    }
+}
+
+/// Parse pairs written as consecutive characters (`"()[]\"\""`)
+///
+/// A trailing unpaired character is ignored.
+pub fn parse_pairs(pairs: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = pairs.chars().collect();
+    chars
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+/// Leading whitespace of a line
+pub fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_word_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    #[test]
+    fn test_auto_close() {
+        let rules = EditingRules::for_extension("php");
+        assert_eq!(rules.auto_close('(', None, None, is_word_char), Some(')'));
+        assert_eq!(
+            rules.auto_close('{', Some(' '), Some(')'), is_word_char),
+            Some('}')
+        );
+        // Not in front of code
+        assert_eq!(rules.auto_close('(', None, Some('$'), is_word_char), None);
+        // Quotes: not after words (`don't`), not before a quote
+        assert_eq!(rules.auto_close('\'', Some('n'), None, is_word_char), None);
+        assert_eq!(
+            rules.auto_close('"', Some('('), Some(')'), is_word_char),
+            Some('"')
+        );
+        assert_eq!(rules.auto_close('"', None, Some('"'), is_word_char), None);
+        // Backticks in JS only, no `'` pairs in Rust
+        assert_eq!(rules.auto_close('`', None, None, is_word_char), None);
+        let js = EditingRules::for_extension("js");
+        assert_eq!(js.auto_close('`', None, None, is_word_char), Some('`'));
+        let rust = EditingRules::for_extension("rs");
+        assert_eq!(rust.auto_close('\'', Some('<'), None, is_word_char), None);
+    }
+
+    #[test]
+    fn test_newline_indent() {
+        let rules = EditingRules::default();
+        assert_eq!(
+            rules.newline_indent("    $a = 1;", "", "    "),
+            (String::from("    "), None)
+        );
+        assert_eq!(
+            rules.newline_indent("    if ($a) {", "", "    "),
+            (String::from("        "), None)
+        );
+        assert_eq!(
+            rules.newline_indent("\tfoo(", ")", "\t"),
+            (String::from("\t\t"), Some(String::from("\t")))
+        );
+        assert_eq!(
+            rules.newline_indent("  case 1: ", "  ", "  "),
+            (String::from("    "), None)
+        );
+
+        let off = EditingRules {
+            auto_indent: false,
+            ..EditingRules::default()
+        };
+        assert_eq!(
+            off.newline_indent("    {", "}", "    "),
+            (String::new(), None)
+        );
+    }
+
+    #[test]
+    fn test_dedent() {
+        let rules = EditingRules::default();
+        assert_eq!(rules.dedent("        ", "    "), "    ");
+        assert_eq!(rules.dedent("\t\t", "    "), "\t");
+        assert_eq!(rules.dedent("    ", "  "), "  ");
+        assert_eq!(rules.dedent("  ", "    "), "");
+        assert_eq!(rules.dedent("", "    "), "");
+        assert!(rules.is_dedent_closer('}'));
+        assert!(!rules.is_dedent_closer('"'));
+    }
+}
@@ -1,8 +1,16 @@
 //! Editor state combining buffer and cursor
 //!
-//! The Editor struct manages the text buffer, cursor, and viewport.
-
-use crate::{Buffer, Cursor};
+//! The Editor struct manages the text buffer, cursors, and viewport.
+//! Binary files are shown in a read-only hex view instead of the buffer.
+
+use crate::editing::leading_whitespace;
+use crate::search::line_text;
+use crate::word;
+use crate::{
+    normalize_line_endings, BlockSelection, Buffer, BufferError, Cursor, EditKind, EditingRules,
+    Encoding, HexView, IndentStyle, Indentation, LoadOptions, Position, Replacement, SearchMatch,
+    SearchOptions, SearchQuery, WordChars,
+};
 use std::path::PathBuf;
 
 /// Viewport for scrolling
@@ -45,11 +53,40 @@ pub struct Editor {
     /// Text buffer
     pub buffer: Buffer,
 
-    /// Cursor position
+    /// Cursor position (the primary cursor)
     pub cursor: Cursor,
 
+    /// Additional cursors (multi-cursor editing)
+    pub extra_cursors: Vec<Cursor>,
+
+    /// Block selection, while active (its lines are selected by the cursors)
+    block: Option<BlockSelection>,
+
     /// Viewport for scrolling
     pub viewport: Viewport,
+
+    /// Hex view (when a binary file is open; the buffer is unused then)
+    pub hex: Option<HexView>,
+
+    /// Characters that belong to words (for word motions and selecting words)
+    pub word_chars: WordChars,
+
+    /// Cursors before each expand-selection step (the last entry is the
+    /// current state, while it is unchanged)
+    selection_history: Vec<Vec<Cursor>>,
+
+    /// Auto-closing and auto-indentation rules
+    pub rules: EditingRules,
+
+    /// Positions of auto-inserted closers (typing them steps past them)
+    auto_closed: Vec<Position>,
+
+    /// Buffer revision and cursors after the last typed character (the
+    /// auto-inserted closers are forgotten once either changes)
+    auto_closed_at: Option<(u64, Vec<Cursor>)>,
+
+    /// Options the file was opened with (large-file threshold)
+    load_options: LoadOptions,
 }
 
 impl Editor {
@@ -58,7 +95,16 @@ impl Editor {
         Self {
             buffer: Buffer::new(),
             cursor: Cursor::new(),
+            extra_cursors: Vec::new(),
+            block: None,
             viewport: Viewport::default(),
+            hex: None,
+            word_chars: WordChars::default(),
+            selection_history: Vec::new(),
+            rules: EditingRules::default(),
+            auto_closed: Vec::new(),
+            auto_closed_at: None,
+            load_options: LoadOptions::default(),
         }
     }
 
@@ -66,18 +112,40 @@ impl Editor {
     pub fn with_content(text: &str) -> Self {
         Self {
             buffer: Buffer::with_content(text),
-            cursor: Cursor::new(),
-            viewport: Viewport::default(),
+            ..Self::new()
         }
     }
 
     /// Open a file
-    pub fn open(path: impl Into<PathBuf>) -> Result<Self, crate::BufferError> {
-        Ok(Self {
-            buffer: Buffer::from_file(path)?,
-            cursor: Cursor::new(),
-            viewport: Viewport::default(),
-        })
+    ///
+    /// Binary files open as a read-only hex view.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, BufferError> {
+        Self::open_with_options(path, &LoadOptions::default())
+    }
+
+    /// Open a file with load options (large-file threshold)
+    pub fn open_with_options(
+        path: impl Into<PathBuf>,
+        options: &LoadOptions,
+    ) -> Result<Self, BufferError> {
+        match Buffer::from_file_with_options(path, options) {
+            Ok(buffer) => Ok(Self {
+                buffer,
+                load_options: options.clone(),
+                ..Self::new()
+            }),
+            Err(BufferError::BinaryFile { path }) => Ok(Self {
+                hex: Some(HexView::open(path)?),
+                load_options: options.clone(),
+                ..Self::new()
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check if the editor is read-only (binary file)
+    pub fn is_read_only(&self) -> bool {
+        self.hex.is_some()
     }
 
     /// Set viewport size
@@ -98,59 +166,131 @@ impl Editor {
 
     // === Cursor Movement ===
 
+    /// Apply a motion to the hex view (returns `false` if there is none)
+    fn hex_motion(&mut self, motion: impl FnOnce(&mut HexView)) -> bool {
+        let Some(hex) = &mut self.hex else {
+            return false;
+        };
+        motion(hex);
+        self.viewport.ensure_visible(hex.cursor_row());
+        true
+    }
+
     /// Move cursor up
     pub fn move_up(&mut self) {
-        self.cursor
-            .move_up(1, |line| self.buffer.line_len(line).unwrap_or(0));
-        self.viewport.ensure_visible(self.cursor.position.line);
+        if self.hex_motion(|hex| hex.move_up(1)) {
+            return;
+        }
+        self.for_each_cursor(|editor| {
+            editor.cursor.move_up(
+                1,
+                |line, column| editor.buffer.display_column(line, column),
+                |line, display| editor.buffer.column_at_display(line, display),
+            );
+        });
     }
 
     /// Move cursor down
     pub fn move_down(&mut self) {
-        self.cursor.move_down(1, self.buffer.len_lines(), |line| {
-            self.buffer.line_len(line).unwrap_or(0)
+        if self.hex_motion(|hex| hex.move_down(1)) {
+            return;
+        }
+        self.for_each_cursor(|editor| {
+            editor.cursor.move_down(
+                1,
+                editor.buffer.len_lines(),
+                |line, column| editor.buffer.display_column(line, column),
+                |line, display| editor.buffer.column_at_display(line, display),
+            );
         });
-        self.viewport.ensure_visible(self.cursor.position.line);
     }
 
-    /// Move cursor left
+    /// Move cursor left (by one grapheme cluster)
     pub fn move_left(&mut self) {
-        self.cursor
-            .move_left(1, |line| self.buffer.line_len(line).unwrap_or(0));
-        self.viewport.ensure_visible(self.cursor.position.line);
+        if self.hex_motion(|hex| hex.move_left()) {
+            return;
+        }
+        self.for_each_cursor(|editor| {
+            let Position { line, column } = editor.cursor.position;
+            if column > 0 {
+                let column = editor.buffer.prev_grapheme_boundary(line, column);
+                editor.cursor.move_to(line, column);
+            } else {
+                editor
+                    .cursor
+                    .move_left(1, |line| editor.buffer.line_len(line).unwrap_or(0));
+            }
+        });
     }
 
-    /// Move cursor right
+    /// Move cursor right (by one grapheme cluster)
     pub fn move_right(&mut self) {
-        self.cursor.move_right(1, self.buffer.len_lines(), |line| {
-            self.buffer.line_len(line).unwrap_or(0)
+        if self.hex_motion(|hex| hex.move_right()) {
+            return;
+        }
+        self.for_each_cursor(|editor| {
+            let Position { line, column } = editor.cursor.position;
+            if column < editor.line_len(line) {
+                let column = editor.buffer.next_grapheme_boundary(line, column);
+                editor.cursor.move_to(line, column);
+            } else {
+                editor
+                    .cursor
+                    .move_right(1, editor.buffer.len_lines(), |line| {
+                        editor.buffer.line_len(line).unwrap_or(0)
+                    });
+            }
         });
-        self.viewport.ensure_visible(self.cursor.position.line);
     }
 
     /// Move to start of line
     pub fn move_to_line_start(&mut self) {
-        self.cursor.move_to_line_start();
+        if self.hex_motion(|hex| hex.move_to_row_start()) {
+            return;
+        }
+        self.for_each_cursor(|editor| editor.cursor.move_to_line_start());
     }
 
     /// Move to end of line
     pub fn move_to_line_end(&mut self) {
-        let line_len = self.current_line_len();
-        self.cursor.move_to_line_end(line_len);
+        if self.hex_motion(|hex| hex.move_to_row_end()) {
+            return;
+        }
+        self.for_each_cursor(|editor| {
+            let line_len = editor.current_line_len();
+            editor.cursor.move_to_line_end(line_len);
+        });
     }
 
     /// Move to start of document
     pub fn move_to_start(&mut self) {
-        self.cursor.move_to_start();
+        if self.hex_motion(|hex| hex.move_to_start()) {
+            return;
+        }
+        self.for_each_cursor(|editor| editor.cursor.move_to_start());
         self.viewport.top_line = 0;
     }
 
     /// Move to end of document
     pub fn move_to_end(&mut self) {
-        let total_lines = self.buffer.len_lines();
-        let last_line_len = self.line_len(total_lines.saturating_sub(1));
-        self.cursor.move_to_end(total_lines, last_line_len);
-        self.viewport.ensure_visible(self.cursor.position.line);
+        if self.hex_motion(|hex| hex.move_to_end()) {
+            return;
+        }
+        self.for_each_cursor(|editor| {
+            let total_lines = editor.buffer.len_lines();
+            let last_line_len = editor.line_len(total_lines.saturating_sub(1));
+            editor.cursor.move_to_end(total_lines, last_line_len);
+        });
+    }
+
+    /// Go to a position, back to a single cursor without selection
+    pub fn go_to(&mut self, position: Position) {
+        self.clear_extra_cursors();
+        self.clear_selection();
+        let line = position.line.min(self.buffer.len_lines().saturating_sub(1));
+        let column = position.column.min(self.line_len(line));
+        self.cursor.move_to(line, column);
+        self.viewport.ensure_visible(line);
     }
 
     /// Page up
@@ -169,298 +309,2394 @@ impl Editor {
         }
     }
 
-    // === Text Editing ===
-
-    /// Insert a character at cursor position
-    pub fn insert_char(&mut self, ch: char) {
-        if let Some(char_idx) = self
-            .buffer
-            .line_col_to_char(self.cursor.position.line, self.cursor.position.column)
-        {
-            self.buffer.insert_char(char_idx, ch);
+    // === Word and Paragraph Motions ===
 
-            if ch == '\n' {
-                // Move to start of new line
-                self.cursor.position.line += 1;
-                self.cursor.position.column = 0;
-            } else {
-                self.cursor.position.column += 1;
-            }
-            self.viewport.ensure_visible(self.cursor.position.line);
-        }
+    /// Move to the start of the previous word (Ctrl+Left)
+    pub fn move_word_left(&mut self) {
+        self.move_each(|editor, position| editor.word_target(position, word::prev_word_start));
     }
 
-    /// Insert a string at cursor position
-    pub fn insert_str(&mut self, text: &str) {
-        for ch in text.chars() {
-            self.insert_char(ch);
-        }
+    /// Move to the end of the next word (Ctrl+Right)
+    pub fn move_word_right(&mut self) {
+        self.move_each(|editor, position| editor.word_target(position, word::next_word_end));
     }
 
-    /// Delete character before cursor (backspace)
-    pub fn backspace(&mut self) {
-        if self.cursor.position.column > 0 {
-            // Delete character before cursor on same line
-            if let Some(char_idx) = self
-                .buffer
-                .line_col_to_char(self.cursor.position.line, self.cursor.position.column - 1)
-            {
-                self.buffer.remove(char_idx, char_idx + 1);
-                self.cursor.position.column -= 1;
-            }
-        } else if self.cursor.position.line > 0 {
-            // At start of line - join with previous line
-            let prev_line_len = self.line_len(self.cursor.position.line - 1);
-            if let Some(char_idx) = self.buffer.line_col_to_char(self.cursor.position.line, 0) {
-                // Remove the newline character at end of previous line
-                self.buffer.remove(char_idx - 1, char_idx);
-                self.cursor.position.line -= 1;
-                self.cursor.position.column = prev_line_len;
-            }
-            self.viewport.ensure_visible(self.cursor.position.line);
-        }
+    /// Move to the start of the previous subword (camelCase/snake_case part)
+    pub fn move_subword_left(&mut self) {
+        self.move_each(|editor, position| editor.word_target(position, word::prev_subword_start));
     }
 
-    /// Delete character at cursor (delete key)
-    pub fn delete(&mut self) {
-        let line_len = self.current_line_len();
+    /// Move to the end of the next subword (camelCase/snake_case part)
+    pub fn move_subword_right(&mut self) {
+        self.move_each(|editor, position| editor.word_target(position, word::next_subword_end));
+    }
 
-        if self.cursor.position.column < line_len {
-            // Delete character at cursor
-            if let Some(char_idx) = self
-                .buffer
-                .line_col_to_char(self.cursor.position.line, self.cursor.position.column)
-            {
-                self.buffer.remove(char_idx, char_idx + 1);
+    /// Move to the blank line before the paragraph (Ctrl+Up)
+    pub fn move_paragraph_up(&mut self) {
+        self.move_each(|editor, position| {
+            let mut line = position.line;
+            while line > 0 && editor.is_blank_line(line) {
+                line -= 1;
             }
-        } else if self.cursor.position.line < self.buffer.len_lines() - 1 {
-            // At end of line - join with next line (delete newline)
-            if let Some(char_idx) = self
-                .buffer
-                .line_col_to_char(self.cursor.position.line, self.cursor.position.column)
-            {
-                self.buffer.remove(char_idx, char_idx + 1);
+            while line > 0 && !editor.is_blank_line(line) {
+                line -= 1;
             }
-        }
-    }
-
-    /// Insert a new line (Enter key)
-    pub fn insert_newline(&mut self) {
-        self.insert_char('\n');
-    }
-
-    // === Selection ===
-
-    /// Start or extend selection
-    pub fn start_selection(&mut self) {
-        if !self.cursor.has_selection() {
-            self.cursor.start_selection();
-        }
+            Position::new(line, 0)
+        });
     }
 
-    /// Clear selection
-    pub fn clear_selection(&mut self) {
-        self.cursor.clear_selection();
+    /// Move to the blank line after the paragraph (Ctrl+Down)
+    pub fn move_paragraph_down(&mut self) {
+        self.move_each(|editor, position| {
+            let last_line = editor.buffer.len_lines().saturating_sub(1);
+            let mut line = position.line;
+            while line < last_line && editor.is_blank_line(line) {
+                line += 1;
+            }
+            while line < last_line && !editor.is_blank_line(line) {
+                line += 1;
+            }
+            if editor.is_blank_line(line) {
+                Position::new(line, 0)
+            } else {
+                Position::new(line, editor.line_len(line))
+            }
+        });
     }
 
-    /// Move with selection (Shift+Arrow)
-    pub fn move_up_select(&mut self) {
+    pub fn move_word_left_select(&mut self) {
         self.start_selection();
-        self.move_up();
+        self.move_word_left();
     }
 
-    pub fn move_down_select(&mut self) {
+    pub fn move_word_right_select(&mut self) {
         self.start_selection();
-        self.move_down();
+        self.move_word_right();
     }
 
-    pub fn move_left_select(&mut self) {
+    pub fn move_subword_left_select(&mut self) {
         self.start_selection();
-        self.move_left();
+        self.move_subword_left();
     }
 
-    pub fn move_right_select(&mut self) {
+    pub fn move_subword_right_select(&mut self) {
         self.start_selection();
-        self.move_right();
+        self.move_subword_right();
     }
 
-    pub fn move_to_line_start_select(&mut self) {
+    pub fn move_paragraph_up_select(&mut self) {
         self.start_selection();
-        self.move_to_line_start();
+        self.move_paragraph_up();
     }
 
-    pub fn move_to_line_end_select(&mut self) {
+    pub fn move_paragraph_down_select(&mut self) {
         self.start_selection();
-        self.move_to_line_end();
+        self.move_paragraph_down();
     }
 
-    pub fn move_to_start_select(&mut self) {
-        self.start_selection();
-        self.move_to_start();
+    /// Delete the selection, or to the start of the previous word
+    /// (Ctrl+Backspace)
+    pub fn delete_word_left(&mut self) {
+        self.edit_each(EditKind::Deleting, |editor| {
+            if !editor.delete_selection() {
+                let position = editor.cursor.position;
+                let target = editor.word_target(position, word::prev_word_start);
+                editor.remove_range(target, position);
+            }
+        });
     }
 
-    pub fn move_to_end_select(&mut self) {
-        self.start_selection();
-        self.move_to_end();
+    /// Delete the selection, or to the end of the next word (Ctrl+Delete)
+    pub fn delete_word_right(&mut self) {
+        self.edit_each(EditKind::Deleting, |editor| {
+            if !editor.delete_selection() {
+                let position = editor.cursor.position;
+                let target = editor.word_target(position, word::next_word_end);
+                editor.remove_range(position, target);
+            }
+        });
     }
 
-    /// Select all text
-    pub fn select_all(&mut self) {
-        self.move_to_start();
-        self.cursor.start_selection();
-        self.move_to_end();
+    /// Move every cursor to a target computed from its position
+    fn move_each(&mut self, target: impl Fn(&Self, Position) -> Position) {
+        if self.is_read_only() {
+            return;
+        }
+        self.for_each_cursor(|editor| {
+            let position = target(editor, editor.cursor.position);
+            editor.cursor.move_to(position.line, position.column);
+        });
     }
 
-    /// Get selected text
-    pub fn get_selected_text(&self) -> Option<String> {
-        let (start, end) = self.cursor.selection_range()?;
-
-        let start_idx = self.buffer.line_col_to_char(start.line, start.column)?;
-        let end_idx = self.buffer.line_col_to_char(end.line, end.column)?;
+    /// Target of a word motion within a line; at the line start/end, the
+    /// motion goes to the previous/next line
+    fn word_target(
+        &self,
+        position: Position,
+        motion: fn(&[char], usize, &WordChars) -> usize,
+    ) -> Position {
+        let Position { line, column } = position;
+        let chars: Vec<char> = self
+            .buffer
+            .line(line)
+            .map(|slice| slice.chars().take(self.line_len(line)).collect())
+            .unwrap_or_default();
+
+        let target = motion(&chars, column, &self.word_chars);
+        if target != column {
+            Position::new(line, target)
+        } else if target == chars.len() && line + 1 < self.buffer.len_lines() {
+            Position::new(line + 1, 0)
+        } else if target == 0 && line > 0 {
+            Position::new(line - 1, self.line_len(line - 1))
+        } else {
+            position
+        }
+    }
 
-        Some(self.buffer.rope().slice(start_idx..end_idx).to_string())
+    /// Check if a line is empty or whitespace only (separates paragraphs)
+    fn is_blank_line(&self, line: usize) -> bool {
+        self.buffer
+            .line(line)
+            .is_none_or(|slice| slice.chars().all(char::is_whitespace))
     }
 
-    /// Delete selected text
-    pub fn delete_selection(&mut self) -> bool {
-        if let Some((start, end)) = self.cursor.selection_range() {
-            if let (Some(start_idx), Some(end_idx)) = (
-                self.buffer.line_col_to_char(start.line, start.column),
-                self.buffer.line_col_to_char(end.line, end.column),
-            ) {
-                self.buffer.remove(start_idx, end_idx);
-                self.cursor.position = start;
-                self.cursor.clear_selection();
-                self.viewport.ensure_visible(self.cursor.position.line);
-                return true;
-            }
+    /// Remove the text between two positions, leaving the cursor at the start
+    fn remove_range(&mut self, start: Position, end: Position) {
+        let (start_idx, end_idx) = (self.char_index(start), self.char_index(end));
+        if start_idx < end_idx {
+            self.buffer.remove(start_idx, end_idx);
         }
-        false
+        self.cursor.move_to(start.line, start.column);
     }
 
-    /// Replace selection with text (or just insert if no selection)
-    pub fn replace_selection(&mut self, text: &str) {
-        self.delete_selection();
-        self.insert_str(text);
+    // === Multiple Cursors ===
+
+    /// Get all cursors, the primary cursor first
+    pub fn cursors(&self) -> impl Iterator<Item = &Cursor> {
+        std::iter::once(&self.cursor).chain(&self.extra_cursors)
     }
 
-    // === File Operations ===
+    /// Check if there is more than one cursor
+    pub fn has_multiple_cursors(&self) -> bool {
+        !self.extra_cursors.is_empty()
+    }
 
-    /// Save the file
-    pub fn save(&mut self) -> Result<(), crate::BufferError> {
-        self.buffer.save()
+    /// Remove all cursors except the primary one
+    pub fn clear_extra_cursors(&mut self) {
+        self.extra_cursors.clear();
+        self.block = None;
     }
 
-    /// Check if modified
-    pub fn is_modified(&self) -> bool {
-        self.buffer.is_modified()
+    /// Add a cursor on the line above the topmost cursor
+    pub fn add_cursor_above(&mut self) {
+        let top = self.cursors().min_by_key(|cursor| cursor.position).cloned();
+        if let Some(cursor) = top.filter(|cursor| cursor.position.line > 0) {
+            self.add_cursor_on_line(&cursor, cursor.position.line - 1);
+        }
     }
 
-    /// Get file path
-    pub fn path(&self) -> Option<&PathBuf> {
-        self.buffer.path()
+    /// Add a cursor on the line below the bottommost cursor
+    pub fn add_cursor_below(&mut self) {
+        let bottom = self.cursors().max_by_key(|cursor| cursor.position).cloned();
+        if let Some(cursor) =
+            bottom.filter(|cursor| cursor.position.line + 1 < self.buffer.len_lines())
+        {
+            self.add_cursor_on_line(&cursor, cursor.position.line + 1);
+        }
     }
 
-    /// Get filename for display
-    pub fn filename(&self) -> String {
-        self.buffer
-            .path()
-            .and_then(|p| p.file_name())
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "[untitled]".to_string())
+    /// Add a cursor on a line, at the (preferred) display column of `from`
+    fn add_cursor_on_line(&mut self, from: &Cursor, line: usize) {
+        if self.is_read_only() {
+            return;
+        }
+        let display = from.preferred_column().unwrap_or_else(|| {
+            self.buffer
+                .display_column(from.position.line, from.position.column)
+        });
+        let mut cursor = Cursor::at(line, self.buffer.column_at_display(line, display));
+        cursor.set_preferred_column(display);
+        self.add_cursor(cursor);
     }
-}
 
-impl Default for Editor {
-    fn default() -> Self {
-        Self::new()
+    /// Add a cursor and make it the primary one
+    fn add_cursor(&mut self, cursor: Cursor) {
+        self.block = None;
+        let previous = std::mem::replace(&mut self.cursor, cursor);
+        self.extra_cursors.push(previous);
+        self.merge_cursors();
+        self.viewport.ensure_visible(self.cursor.position.line);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::Position;
+    /// Select the next occurrence of the primary selection with an
+    /// additional cursor (Ctrl+D)
+    ///
+    /// Without a selection, the word at the cursor is selected first.
+    /// Returns `false` if there is nothing (more) to select.
+    pub fn add_next_occurrence(&mut self) -> bool {
+        if self.is_read_only() {
+            return false;
+        }
+        let Some((start, end)) = self.cursor.selection_range().filter(|(s, e)| s != e) else {
+            return self.select_word();
+        };
 
-    #[test]
-    fn test_insert_char() {
-        let mut editor = Editor::new();
-        editor.insert_char('H');
-        editor.insert_char('i');
+        let needle = self
+            .buffer
+            .rope()
+            .slice(self.char_index(start)..self.char_index(end))
+            .to_string();
+
+        // Search forward from the primary selection, wrapping around, for an
+        // occurrence that isn't selected yet
+        let (after, before): (Vec<_>, Vec<_>) = self
+            .occurrences(&needle)
+            .into_iter()
+            .partition(|(from, _)| *from >= end);
+        let selected: Vec<(Position, Position)> =
+            self.cursors().filter_map(Cursor::selection_range).collect();
+        let next = after
+            .into_iter()
+            .chain(before)
+            .find(|occurrence| !selected.contains(occurrence));
+
+        match next {
+            Some((start, end)) => {
+                let mut cursor = Cursor::at(end.line, end.column);
+                cursor.anchor = Some(start);
+                self.add_cursor(cursor);
+                true
+            }
+            None => false,
+        }
+    }
 
-        assert_eq!(editor.buffer.text(), "Hi");
-        assert_eq!(editor.cursor.position, Position::new(0, 2));
+    /// Occurrences of a text in the buffer, in document order
+    ///
+    /// The rope is searched line by line, like [`SearchQuery`] does: a text
+    /// spanning lines matches where the first line ends with its first line,
+    /// the lines in between are equal, and the last line starts with its
+    /// last line.
+    fn occurrences(&self, needle: &str) -> Vec<(Position, Position)> {
+        let rope = self.buffer.rope();
+        let lines: Vec<&str> = needle.split('\n').collect();
+        let [first, middle @ .., last] = lines.as_slice() else {
+            // A single line
+            let options = SearchOptions {
+                case_sensitive: true,
+                ..SearchOptions::default()
+            };
+            return SearchQuery::new(needle, options, &self.word_chars)
+                .map(|query| {
+                    query
+                        .find_all(rope)
+                        .iter()
+                        .map(|m| (m.start_position(), m.end_position()))
+                        .collect()
+                })
+                .unwrap_or_default();
+        };
+
+        let spanned = lines.len() - 1;
+        (0..rope.len_lines().saturating_sub(spanned))
+            .filter_map(|line| {
+                let text = line_text(rope, line);
+                let matches = text.ends_with(first)
+                    && middle
+                        .iter()
+                        .enumerate()
+                        .all(|(i, middle)| line_text(rope, line + 1 + i) == *middle)
+                    && line_text(rope, line + spanned).starts_with(last);
+                matches.then(|| {
+                    let column = text.chars().count() - first.chars().count();
+                    (
+                        Position::new(line, column),
+                        Position::new(line + spanned, last.chars().count()),
+                    )
+                })
+            })
+            .collect()
     }
 
-    #[test]
-    fn test_insert_newline() {
-        let mut editor = Editor::new();
-        editor.insert_str("Hello");
-        editor.insert_newline();
-        editor.insert_str("World");
+    /// Select the word at the primary cursor
+    ///
+    /// Returns `false` if the cursor is not at a word.
+    fn select_word(&mut self) -> bool {
+        let Position { line, column } = self.cursor.position;
+        let Some(slice) = self.buffer.line(line) else {
+            return false;
+        };
+        let chars: Vec<char> = slice.chars().take(self.line_len(line)).collect();
+        let is_word_char = |c: char| self.word_chars.is_word_char(c);
+        let mut start = column.min(chars.len());
+        while start > 0 && is_word_char(chars[start - 1]) {
+            start -= 1;
+        }
+        let mut end = column.min(chars.len());
+        while end < chars.len() && is_word_char(chars[end]) {
+            end += 1;
+        }
+        if start == end {
+            return false;
+        }
 
-        assert_eq!(editor.buffer.text(), "Hello\nWorld");
-        assert_eq!(editor.cursor.position, Position::new(1, 5));
+        self.cursor.move_to(line, end);
+        self.cursor.anchor = Some(Position::new(line, start));
+        true
     }
 
-    #[test]
-    fn test_backspace() {
-        let mut editor = Editor::with_content("Hello");
-        editor.cursor.move_to(0, 5);
+    /// Run a cursor operation once for every cursor
+    ///
+    /// Cursors are visited in document order, each temporarily installed as
+    /// `self.cursor` (with no extra cursors), so single-cursor code works
+    /// unchanged. Positions of cursors not visited yet are shifted by the
+    /// text inserted or removed before them. Overlapping cursors are merged
+    /// afterwards.
+    fn for_each_cursor(&mut self, mut op: impl FnMut(&mut Self)) {
+        // Any motion or edit turns a block selection into plain cursors
+        self.block = None;
+        if self.extra_cursors.is_empty() {
+            op(self);
+            self.viewport.ensure_visible(self.cursor.position.line);
+            return;
+        }
 
-        editor.backspace();
-        assert_eq!(editor.buffer.text(), "Hell");
-        assert_eq!(editor.cursor.position.column, 4);
+        let mut cursors: Vec<Cursor> = std::iter::once(self.cursor.clone())
+            .chain(std::mem::take(&mut self.extra_cursors))
+            .collect();
+        let offsets: Vec<(usize, Option<usize>)> = cursors
+            .iter()
+            .map(|cursor| {
+                let anchor = cursor.anchor.map(|anchor| self.char_index(anchor));
+                (self.char_index(cursor.position), anchor)
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..cursors.len()).collect();
+        order.sort_by_key(|&i| offsets[i].1.map_or(offsets[i].0, |a| a.min(offsets[i].0)));
+
+        let mut delta = 0isize;
+        for i in order {
+            let len_before = self.buffer.len_chars();
+            let shift = |offset: usize| offset.saturating_add_signed(delta);
+            let (position, anchor) = offsets[i];
+
+            self.cursor = cursors[i].clone();
+            self.cursor.position = self.position_at(shift(position));
+            self.cursor.anchor = anchor.map(|anchor| self.position_at(shift(anchor)));
+            op(self);
+            cursors[i] = self.cursor.clone();
+
+            delta += self.buffer.len_chars() as isize - len_before as isize;
+        }
+
+        self.cursor = cursors.remove(0);
+        self.extra_cursors = cursors;
+        self.merge_cursors();
+        self.viewport.ensure_visible(self.cursor.position.line);
     }
 
-    #[test]
-    fn test_backspace_join_lines() {
-        let mut editor = Editor::with_content("Hello\nWorld");
-        editor.cursor.move_to(1, 0); // Start of "World"
+    /// Merge cursors at the same position and overlapping selections
+    fn merge_cursors(&mut self) {
+        if self.extra_cursors.is_empty() {
+            return;
+        }
 
-        editor.backspace();
-        assert_eq!(editor.buffer.text(), "HelloWorld");
-        assert_eq!(editor.cursor.position, Position::new(0, 5));
-    }
+        // (cursor, is primary), in document order
+        let mut cursors: Vec<(Cursor, bool)> = std::iter::once((self.cursor.clone(), true))
+            .chain(self.extra_cursors.drain(..).map(|cursor| (cursor, false)))
+            .collect();
+        cursors.sort_by_key(|(cursor, _)| selection_bounds(cursor));
+
+        let mut merged: Vec<(Cursor, bool)> = Vec::with_capacity(cursors.len());
+        for (cursor, primary) in cursors {
+            let Some((last, last_primary)) = merged.last_mut() else {
+                merged.push((cursor, primary));
+                continue;
+            };
+            let (last_start, last_end) = selection_bounds(last);
+            let (start, end) = selection_bounds(&cursor);
+            if start != last_start && start >= last_end {
+                merged.push((cursor, primary));
+                continue;
+            }
 
-    #[test]
-    fn test_delete() {
-        let mut editor = Editor::with_content("Hello");
-        editor.cursor.move_to(0, 0);
+            // Union of both, keeping the direction of the earlier selection
+            let end = end.max(last_end);
+            if last_start == end {
+                *last = Cursor::at(end.line, end.column);
+            } else if last.position < last.anchor.unwrap_or(last.position) {
+                last.anchor = Some(end);
+            } else {
+                last.position = end;
+                last.anchor = Some(last_start);
+            }
+            *last_primary |= primary;
+        }
 
-        editor.delete();
-        assert_eq!(editor.buffer.text(), "ello");
+        let primary = merged.iter().position(|(_, primary)| *primary).unwrap_or(0);
+        self.cursor = merged.remove(primary).0;
+        self.extra_cursors = merged.into_iter().map(|(cursor, _)| cursor).collect();
     }
 
-    #[test]
-    fn test_delete_join_lines() {
-        let mut editor = Editor::with_content("Hello\nWorld");
-        editor.cursor.move_to(0, 5); // End of "Hello"
+    // === Block Selection ===
 
-        editor.delete();
-        assert_eq!(editor.buffer.text(), "HelloWorld");
+    /// Get the block selection, if active
+    pub fn block_selection(&self) -> Option<&BlockSelection> {
+        self.block.as_ref()
     }
 
-    #[test]
-    fn test_viewport_scrolling() {
-        let mut editor = Editor::with_content("Line1\nLine2\nLine3\nLine4\nLine5");
-        editor.set_viewport_size(3, 80);
+    /// Start or extend the block selection (Alt+Shift+Arrows)
+    ///
+    /// The moving corner goes `lines` down (up if negative) and `columns`
+    /// display columns right (left if negative); it may go past line ends.
+    /// Each line of the rectangle gets a cursor selecting its part of it, so
+    /// typing or deleting applies to every line.
+    pub fn extend_block_selection(&mut self, lines: isize, columns: isize) {
+        if self.is_read_only() {
+            return;
+        }
+        let mut block = self.block.unwrap_or_else(|| {
+            let Position { line, column } = self.cursor.position;
+            BlockSelection::at(line, self.buffer.display_column(line, column))
+        });
+        let last_line = self.buffer.len_lines().saturating_sub(1);
+        block.head.line = block.head.line.saturating_add_signed(lines).min(last_line);
+        block.head.column = block.head.column.saturating_add_signed(columns);
+
+        // One cursor per line, the primary one on the line of the moving corner
+        let range = block.columns();
+        let backward = block.head.column < block.anchor.column;
+        let mut cursors: Vec<Cursor> = block
+            .lines()
+            .map(|line| {
+                let start = self.buffer.column_at_display(line, range.start);
+                let end = self.buffer.column_at_display(line, range.end);
+                let (from, to) = if backward { (end, start) } else { (start, end) };
+                let mut cursor = Cursor::at(line, to);
+                cursor.anchor = (from != to).then(|| Position::new(line, from));
+                cursor
+            })
+            .collect();
+        let primary = block.head.line - block.lines().start();
+        self.cursor = cursors.remove(primary);
+        self.extra_cursors = cursors;
+        self.block = Some(block);
+        self.viewport.ensure_visible(block.head.line);
+    }
 
-        // Move down past viewport
-        editor.move_down();
-        editor.move_down();
-        editor.move_down();
+    /// Paste text copied from a block selection as a block
+    ///
+    /// Line `i` of the text is inserted `i` lines below the cursor, at the
+    /// cursor's display column. Short lines are padded with spaces, and lines
+    /// are added at the end of the buffer as needed.
+    pub fn paste_block(&mut self, text: &str) {
+        let text = normalize_line_endings(text);
+        self.transaction(EditKind::Other, |editor| {
+            editor.delete_selection();
+            let top = editor.cursors().map(|cursor| cursor.position).min();
+            let top = top.unwrap_or_default();
+            editor.clear_extra_cursors();
+            let display = editor.buffer.display_column(top.line, top.column);
+
+            let mut end = top;
+            for (i, piece) in text.split('\n').enumerate() {
+                let line = top.line + i;
+                if line >= editor.buffer.len_lines() {
+                    editor.buffer.insert(editor.buffer.len_chars(), "\n");
+                }
+                let line_len = editor.line_len(line);
+                let line_width = editor.buffer.display_column(line, line_len);
+                let (column, padding) = if line_width < display {
+                    (line_len, " ".repeat(display - line_width))
+                } else {
+                    (
+                        editor.buffer.column_at_display(line, display),
+                        String::new(),
+                    )
+                };
+                let at = editor.char_index(Position::new(line, column));
+                editor.buffer.insert(at, &format!("{padding}{piece}"));
+                end = Position::new(line, column + padding.len() + piece.chars().count());
+            }
+            editor.cursor.move_to(end.line, end.column);
+            editor.viewport.ensure_visible(end.line);
+        });
+    }
 
-        assert_eq!(editor.cursor.position.line, 3);
-        assert!(editor.viewport.top_line > 0);
+    /// Char index of a position (clamped to the buffer)
+    fn char_index(&self, position: Position) -> usize {
+        self.buffer
+            .line_col_to_char(position.line, position.column)
+            .unwrap_or_else(|| self.buffer.len_chars())
     }
 
-    #[test]
-    fn test_selection_get_text() {
-        let mut editor = Editor::with_content("Hello World");
-        editor.cursor.move_to(0, 0);
-        editor.cursor.start_selection();
-        editor.cursor.move_to(0, 5);
+    /// Position of a char index (clamped to the buffer)
+    fn position_at(&self, char_idx: usize) -> Position {
+        let (line, column) = self
+            .buffer
+            .char_to_line_col(char_idx.min(self.buffer.len_chars()));
+        Position::new(line, column)
+    }
+
+    // === Text Editing ===
+
+    /// Insert a character at cursor position
+    pub fn insert_char(&mut self, ch: char) {
+        self.edit_each(EditKind::Typing, |editor| {
+            if let Some(char_idx) = editor
+                .buffer
+                .line_col_to_char(editor.cursor.position.line, editor.cursor.position.column)
+            {
+                editor.buffer.insert_char(char_idx, ch);
+
+                if ch == '\n' {
+                    // Move to start of new line
+                    editor.cursor.position.line += 1;
+                    editor.cursor.position.column = 0;
+                } else {
+                    editor.cursor.position.column += 1;
+                }
+                editor.viewport.ensure_visible(editor.cursor.position.line);
+            }
+        });
+    }
+
+    /// Insert a string at cursor position
+    ///
+    /// Line endings are normalised to `\n` (e.g. CRLF from the clipboard).
+    pub fn insert_str(&mut self, text: &str) {
+        let text = normalize_line_endings(text);
+        if text.is_empty() {
+            return;
+        }
+        // Cursor movement: lines added, and the column on the last line
+        let lines = text.matches('\n').count();
+        let last_line = text.rsplit('\n').next().unwrap_or_default();
+        let last_len = last_line.chars().count();
+
+        self.edit_each(EditKind::Other, |editor| {
+            let position = editor.cursor.position;
+            let char_idx = editor.char_index(position);
+            editor.buffer.insert(char_idx, &text);
+
+            editor.cursor.position = if lines == 0 {
+                Position::new(position.line, position.column + last_len)
+            } else {
+                Position::new(position.line + lines, last_len)
+            };
+            editor.viewport.ensure_visible(editor.cursor.position.line);
+        });
+    }
+
+    /// Type a character (replaces the selection, if any)
+    ///
+    /// Applies the editing rules: see [`Editor::type_char_with`].
+    pub fn type_char(&mut self, ch: char) {
+        self.type_char_with(ch, |_| false);
+    }
+
+    /// Type a character (replaces the selection, if any), applying the
+    /// editing rules
+    ///
+    /// Opening brackets and quotes are auto-closed, except where
+    /// `in_literal(byte)` reports a string or comment at the cursor's byte
+    /// offset. Typing an auto-inserted closer steps past it, and a closer
+    /// typed first on a line dedents the line.
+    pub fn type_char_with(&mut self, ch: char, in_literal: impl Fn(usize) -> bool) {
+        let state = (self.buffer.revision(), self.cursors().cloned().collect());
+        if self.auto_closed_at.as_ref() != Some(&state) {
+            self.auto_closed.clear();
+        }
+
+        self.edit_each(EditKind::Typing, |editor| {
+            if editor.cursor.has_selection() {
+                editor.replace_selection(&ch.to_string());
+                return;
+            }
+            let position = editor.cursor.position;
+            let char_idx = editor.char_index(position);
+            let rope = editor.buffer.rope();
+            let prev = (position.column > 0).then(|| rope.char(char_idx - 1));
+            let next =
+                (position.column < editor.line_len(position.line)).then(|| rope.char(char_idx));
+
+            // Step past an auto-inserted closer
+            if next == Some(ch) && editor.auto_closed.contains(&position) {
+                editor.auto_closed.retain(|closer| *closer != position);
+                editor.cursor.move_to(position.line, position.column + 1);
+                return;
+            }
+
+            let close = editor
+                .rules
+                .auto_close(ch, prev, next, |c| editor.word_chars.is_word_char(c))
+                .filter(|_| !in_literal(rope.char_to_byte(char_idx)));
+            editor.insert_char(ch);
+            editor.shift_auto_closed(position, 1);
+            if let Some(close) = close {
+                editor.insert_char(close);
+                editor.cursor.move_to(position.line, position.column + 1);
+                editor.shift_auto_closed(editor.cursor.position, 1);
+                editor.auto_closed.push(editor.cursor.position);
+            } else if editor.rules.is_dedent_closer(ch) {
+                editor.dedent_closer();
+            }
+        });
+
+        self.auto_closed_at = Some((self.buffer.revision(), self.cursors().cloned().collect()));
+    }
+
+    /// Move auto-inserted closers after text inserted on their line
+    fn shift_auto_closed(&mut self, at: Position, len: usize) {
+        for closer in &mut self.auto_closed {
+            if closer.line == at.line && closer.column >= at.column {
+                closer.column += len;
+            }
+        }
+    }
+
+    /// Dedent the cursor line after a closer was typed as its first
+    /// non-whitespace character
+    fn dedent_closer(&mut self) {
+        let Position { line, column } = self.cursor.position;
+        let Some(slice) = self.buffer.line(line) else {
+            return;
+        };
+        let before: String = slice.chars().take(column - 1).collect();
+        if !before.chars().all(|c| c == ' ' || c == '\t') {
+            return;
+        }
+        let unit = self.buffer.indentation().unit();
+        let indent = self.rules.dedent(&before, &unit).to_string();
+        let removed = before.chars().count() - indent.chars().count();
+        if removed > 0 {
+            self.remove_range(
+                Position::new(line, indent.chars().count()),
+                Position::new(line, before.chars().count()),
+            );
+            self.cursor.move_to(line, column - removed);
+        }
+    }
+
+    /// Delete the selection, or the grapheme cluster before the cursor
+    /// (backspace)
+    pub fn backspace(&mut self) {
+        self.edit_each(EditKind::Deleting, |editor| {
+            if editor.delete_selection() {
+                return;
+            }
+            let Position { line, column } = editor.cursor.position;
+            if column > 0 {
+                // Delete grapheme cluster before cursor on same line
+                let start = editor.buffer.prev_grapheme_boundary(line, column);
+                if let (Some(from), Some(to)) = (
+                    editor.buffer.line_col_to_char(line, start),
+                    editor.buffer.line_col_to_char(line, column),
+                ) {
+                    editor.buffer.remove(from, to);
+                    editor.cursor.position.column = start;
+                }
+            } else if editor.cursor.position.line > 0 {
+                // At start of line - join with previous line
+                let prev_line_len = editor.line_len(editor.cursor.position.line - 1);
+                if let Some(char_idx) = editor
+                    .buffer
+                    .line_col_to_char(editor.cursor.position.line, 0)
+                {
+                    // Remove the newline character at end of previous line
+                    editor.buffer.remove(char_idx - 1, char_idx);
+                    editor.cursor.position.line -= 1;
+                    editor.cursor.position.column = prev_line_len;
+                }
+                editor.viewport.ensure_visible(editor.cursor.position.line);
+            }
+        });
+    }
+
+    /// Delete the selection, or the grapheme cluster at the cursor
+    /// (delete key)
+    pub fn delete(&mut self) {
+        self.edit_each(EditKind::Deleting, |editor| {
+            if editor.delete_selection() {
+                return;
+            }
+            let Position { line, column } = editor.cursor.position;
+            let Some(char_idx) = editor.buffer.line_col_to_char(line, column) else {
+                return;
+            };
+
+            if column < editor.current_line_len() {
+                // Delete grapheme cluster at cursor
+                let end = editor.buffer.next_grapheme_boundary(line, column);
+                editor.buffer.remove(char_idx, char_idx + (end - column));
+            } else if line < editor.buffer.len_lines() - 1 {
+                // Join with next line (delete newline) when at end of line
+                editor.buffer.remove(char_idx, char_idx + 1);
+            }
+        });
+    }
+
+    /// Insert a new line (Enter key), replacing the selection, if any
+    ///
+    /// With auto-indentation, the new line keeps the indentation of the
+    /// current one, indented further after an opening bracket or `:`. Enter
+    /// between a bracket pair puts the closer on a line of its own.
+    pub fn insert_newline(&mut self) {
+        self.edit_each(EditKind::Typing, |editor| {
+            editor.delete_selection();
+            let Position { line, column } = editor.cursor.position;
+            let Some(slice) = editor.buffer.line(line) else {
+                return;
+            };
+            let text: String = slice.chars().take(editor.line_len(line)).collect();
+            let split = text
+                .char_indices()
+                .nth(column)
+                .map_or(text.len(), |(offset, _)| offset);
+            let (before, after) = text.split_at(split);
+            let unit = editor.buffer.indentation().unit();
+            let (indent, closer_indent) = editor.rules.newline_indent(before, after, &unit);
+
+            // Whitespace after the cursor would end up after the indentation
+            if editor.rules.auto_indent {
+                let blanks = leading_whitespace(after).chars().count();
+                editor.remove_range(
+                    Position::new(line, column),
+                    Position::new(line, column + blanks),
+                );
+            }
+
+            editor.insert_char('\n');
+            for ch in indent.chars() {
+                editor.insert_char(ch);
+            }
+            if let Some(closer_indent) = closer_indent {
+                let inner = editor.cursor.position;
+                editor.insert_char('\n');
+                for ch in closer_indent.chars() {
+                    editor.insert_char(ch);
+                }
+                editor.cursor.move_to(inner.line, inner.column);
+            }
+        });
+    }
+
+    // === Indentation ===
+
+    /// Indent (Tab)
+    ///
+    /// With a selection spanning lines, every selected non-blank line is
+    /// indented by one level. Otherwise the selection is replaced by
+    /// indentation up to the next indentation stop.
+    pub fn indent(&mut self) {
+        let indentation = self.buffer.indentation();
+        if self.has_multi_line_selection() {
+            let unit = indentation.unit();
+            self.transaction(EditKind::Other, |editor| {
+                for line in editor.selected_lines() {
+                    if !editor.is_blank_line(line) {
+                        let indent = format!("{}{unit}", editor.line_indent(line));
+                        editor.set_line_indent(line, &indent);
+                    }
+                }
+            });
+            return;
+        }
+
+        self.edit_each(EditKind::Typing, |editor| {
+            editor.delete_selection();
+            let text = match indentation.style {
+                IndentStyle::Tabs => String::from("\t"),
+                IndentStyle::Spaces => {
+                    let Position { line, column } = editor.cursor.position;
+                    let display = editor.buffer.display_column(line, column);
+                    " ".repeat(indentation.size - display % indentation.size)
+                }
+            };
+            editor.insert_str(&text);
+        });
+    }
+
+    /// Outdent (Shift+Tab): remove one level of indentation from every
+    /// selected line, or the cursor lines
+    pub fn outdent(&mut self) {
+        let indentation = self.buffer.indentation();
+        self.transaction(EditKind::Other, |editor| {
+            for line in editor.selected_lines() {
+                let indent = editor.line_indent(line);
+                editor.set_line_indent(line, indentation.outdent(&indent));
+            }
+        });
+    }
+
+    /// Re-indent the selected lines (or the cursor lines) with the buffer's
+    /// indentation style, keeping their indentation levels
+    pub fn reindent_selection(&mut self) {
+        let lines = self.selected_lines();
+        let indentation = self.buffer.indentation();
+        self.reindent_lines(lines, indentation, indentation);
+    }
+
+    /// Convert the whole file to another indentation style, as one undo step
+    pub fn convert_indentation(&mut self, indentation: Indentation) {
+        if self.is_read_only() {
+            return;
+        }
+        let from = self.buffer.indentation();
+        self.buffer.set_indentation(indentation);
+        self.reindent_lines((0..self.buffer.len_lines()).collect(), from, indentation);
+    }
+
+    /// Re-indent lines written with indentation `from` to `to`
+    fn reindent_lines(&mut self, lines: Vec<usize>, from: Indentation, to: Indentation) {
+        let tab_width = self.buffer.tab_width();
+        self.transaction(EditKind::Other, |editor| {
+            for line in lines {
+                let indent = editor.line_indent(line);
+                editor.set_line_indent(line, &to.reindent(&indent, from, tab_width));
+            }
+        });
+    }
+
+    /// Check if any selection spans more than one line
+    fn has_multi_line_selection(&self) -> bool {
+        self.cursors().any(|cursor| {
+            cursor
+                .selection_range()
+                .is_some_and(|(start, end)| start.line != end.line)
+        })
+    }
+
+    /// Lines touched by the selections and cursors, in order (see
+    /// [`Editor::line_blocks`])
+    fn selected_lines(&self) -> Vec<usize> {
+        self.line_blocks()
+            .into_iter()
+            .flat_map(|(start, last)| start..=last)
+            .collect()
+    }
+
+    /// Leading whitespace of a line
+    fn line_indent(&self, line: usize) -> String {
+        self.buffer.line(line).map_or_else(String::new, |slice| {
+            slice
+                .chars()
+                .take_while(|&c| c == ' ' || c == '\t')
+                .collect()
+        })
+    }
+
+    /// Replace the leading whitespace of a line
+    ///
+    /// Cursors and selection anchors on the line stay on the same text;
+    /// those at the start of the line stay there (so selected lines remain
+    /// fully selected).
+    fn set_line_indent(&mut self, line: usize, indent: &str) {
+        let old = self.line_indent(line);
+        if old == indent {
+            return;
+        }
+        let Some(start) = self.buffer.line_col_to_char(line, 0) else {
+            return;
+        };
+        let (old_len, new_len) = (old.chars().count(), indent.chars().count());
+        self.buffer.remove(start, start + old_len);
+        self.buffer.insert(start, indent);
+
+        self.block = None;
+        let shift = |position: &mut Position| {
+            if position.line != line || position.column == 0 {
+                return;
+            }
+            position.column = if position.column >= old_len {
+                position.column - old_len + new_len
+            } else {
+                position.column.min(new_len)
+            };
+        };
+        for cursor in std::iter::once(&mut self.cursor).chain(&mut self.extra_cursors) {
+            shift(&mut cursor.position);
+            if let Some(anchor) = &mut cursor.anchor {
+                shift(anchor);
+            }
+        }
+    }
+
+    // === Line Operations ===
+
+    /// Duplicate the selected lines (or the cursor lines) below themselves,
+    /// moving the cursors and selections to the copies
+    pub fn duplicate_lines(&mut self) {
+        let blocks = self.line_blocks();
+        let shifts: Vec<(usize, usize, usize)> = blocks
+            .iter()
+            .scan(0, |before, &(start, last)| {
+                *before += last - start + 1;
+                Some((start, last, *before))
+            })
+            .collect();
+        self.transaction(EditKind::Other, |editor| {
+            for &(start, last) in blocks.iter().rev() {
+                let mut lines = editor.lines_text(start, last);
+                lines.extend_from_within(..);
+                editor.replace_lines(start, last, &lines);
+            }
+            editor.map_cursors(|position| {
+                let &(_, _, shift) = shifts
+                    .iter()
+                    .find(|&&(start, last, _)| (start..=last + 1).contains(&position.line))
+                    .expect("cursor in a block");
+                Position::new(position.line + shift, position.column)
+            });
+        });
+    }
+
+    /// Move the selected lines (or the cursor lines) up by one line
+    ///
+    /// Does nothing if a block of lines is already at the top.
+    pub fn move_lines_up(&mut self) {
+        let blocks = self.line_blocks();
+        if blocks.first().is_none_or(|&(start, _)| start == 0) {
+            return;
+        }
+        self.transaction(EditKind::Other, |editor| {
+            for &(start, last) in &blocks {
+                let mut lines = editor.lines_text(start - 1, last);
+                lines.rotate_left(1);
+                editor.replace_lines(start - 1, last, &lines);
+            }
+            editor.map_cursors(|position| Position::new(position.line - 1, position.column));
+        });
+    }
+
+    /// Move the selected lines (or the cursor lines) down by one line
+    ///
+    /// Does nothing if a block of lines is already at the bottom.
+    pub fn move_lines_down(&mut self) {
+        let blocks = self.line_blocks();
+        let last_line = self.buffer.len_lines().saturating_sub(1);
+        if blocks.last().is_none_or(|&(_, last)| last >= last_line) {
+            return;
+        }
+        self.transaction(EditKind::Other, |editor| {
+            for &(start, last) in blocks.iter().rev() {
+                let mut lines = editor.lines_text(start, last + 1);
+                lines.rotate_right(1);
+                editor.replace_lines(start, last + 1, &lines);
+            }
+            editor.map_cursors(|position| Position::new(position.line + 1, position.column));
+        });
+    }
+
+    /// Delete the selected lines (or the cursor lines)
+    pub fn delete_lines(&mut self) {
+        let blocks = self.line_blocks();
+        // New line of each block: its start minus the lines deleted above
+        let targets: Vec<(usize, usize, usize)> = blocks
+            .iter()
+            .scan(0, |deleted, &(start, last)| {
+                let target = start - *deleted;
+                *deleted += last - start + 1;
+                Some((start, last, target))
+            })
+            .collect();
+        self.transaction(EditKind::Other, |editor| {
+            for &(start, last) in blocks.iter().rev() {
+                let from = if last + 1 < editor.buffer.len_lines() || start == 0 {
+                    editor.char_index(Position::new(start, 0))
+                } else {
+                    // The last line: delete the line break before it instead
+                    editor.char_index(Position::new(start - 1, editor.line_len(start - 1)))
+                };
+                let to = editor.char_index(Position::new(last + 1, 0));
+                editor.buffer.remove(from, to);
+            }
+            for cursor in std::iter::once(&mut editor.cursor).chain(&mut editor.extra_cursors) {
+                cursor.clear_selection();
+            }
+            editor.map_cursors(|position| {
+                let &(_, _, target) = targets
+                    .iter()
+                    .find(|&&(start, last, _)| (start..=last + 1).contains(&position.line))
+                    .expect("cursor in a block");
+                Position::new(target, position.column)
+            });
+        });
+    }
+
+    /// Join the selected lines (or each cursor line with the next one)
+    ///
+    /// Lines are joined with a single space, without their leading and
+    /// trailing whitespace.
+    pub fn join_lines(&mut self) {
+        let last_line = self.buffer.len_lines().saturating_sub(1);
+        let blocks: Vec<(usize, usize)> = self
+            .line_blocks()
+            .into_iter()
+            .map(|(start, last)| (start, if start == last { last + 1 } else { last }))
+            .filter(|&(_, last)| last <= last_line)
+            .collect();
+        if blocks.is_empty() {
+            return;
+        }
+
+        // Per block: first line, new first line, and for each joined line
+        // its (new start column, removed leading whitespace, kept length)
+        let mut joins = Vec::new();
+        let mut removed = 0;
+        for &(start, last) in &blocks {
+            let mut joined = String::new();
+            let mut columns = Vec::new();
+            for (i, text) in self.lines_text(start, last).iter().enumerate() {
+                let content = if i == 0 {
+                    text.as_str()
+                } else {
+                    text.trim_start()
+                };
+                let content = if start + i < last {
+                    content.trim_end()
+                } else {
+                    content
+                };
+                if i > 0 && !content.is_empty() && !joined.is_empty() {
+                    joined.push(' ');
+                }
+                let leading = text.chars().count() - text.trim_start().chars().count();
+                columns.push((
+                    joined.chars().count(),
+                    if i == 0 { 0 } else { leading },
+                    content.chars().count(),
+                ));
+                joined.push_str(content);
+            }
+            joins.push((start, last, start - removed, joined, columns));
+            removed += last - start;
+        }
+
+        self.transaction(EditKind::Other, |editor| {
+            for (start, last, _, joined, _) in joins.iter().rev() {
+                editor.replace_lines(*start, *last, std::slice::from_ref(joined));
+            }
+            editor.map_cursors(|position| {
+                let Some((start, _, target, _, columns)) = joins
+                    .iter()
+                    .find(|(start, last, ..)| (*start..=*last + 1).contains(&position.line))
+                else {
+                    return position;
+                };
+                // A selection ending at the start of the line after the block
+                let Some(&(offset, leading, len)) = columns.get(position.line - start) else {
+                    return Position::new(target + 1, position.column);
+                };
+                let column = position.column.saturating_sub(leading).min(len);
+                Position::new(*target, offset + column)
+            });
+        });
+    }
+
+    /// Sort the selected lines (each selection separately)
+    pub fn sort_lines(&mut self) {
+        let blocks: Vec<(usize, usize)> = self
+            .line_blocks()
+            .into_iter()
+            .filter(|&(start, last)| start < last)
+            .collect();
+        if blocks.is_empty() {
+            return;
+        }
+        self.transaction(EditKind::Other, |editor| {
+            for &(start, last) in &blocks {
+                let mut lines = editor.lines_text(start, last);
+                lines.sort();
+                editor.replace_lines(start, last, &lines);
+            }
+            editor.map_cursors(|position| position);
+        });
+    }
+
+    /// Blocks of lines touched by the cursors and selections: (first, last)
+    /// line, in order, with overlapping and adjacent blocks merged
+    ///
+    /// A selection ending at the start of a line doesn't include that line
+    /// (but its end is in the block's range `first..=last + 1`).
+    fn line_blocks(&self) -> Vec<(usize, usize)> {
+        let mut blocks: Vec<(usize, usize)> = self
+            .cursors()
+            .map(|cursor| {
+                let (start, end) = selection_bounds(cursor);
+                let last = if end.line > start.line && end.column == 0 {
+                    end.line - 1
+                } else {
+                    end.line
+                };
+                (start.line, last)
+            })
+            .collect();
+        blocks.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(blocks.len());
+        for (start, last) in blocks {
+            match merged.last_mut() {
+                Some((_, previous)) if start <= *previous + 1 => *previous = last.max(*previous),
+                _ => merged.push((start, last)),
+            }
+        }
+        merged
+    }
+
+    /// Text of a range of lines (without line breaks)
+    fn lines_text(&self, start: usize, last: usize) -> Vec<String> {
+        (start..=last)
+            .map(|line| {
+                self.buffer.line(line).map_or_else(String::new, |slice| {
+                    slice.chars().take(self.line_len(line)).collect()
+                })
+            })
+            .collect()
+    }
+
+    /// Replace a range of lines (keeping the line break after the last one)
+    fn replace_lines(&mut self, start: usize, last: usize, lines: &[String]) {
+        let from = self.char_index(Position::new(start, 0));
+        let to = self.char_index(Position::new(last, self.line_len(last)));
+        self.buffer.remove(from, to);
+        self.buffer.insert(from, &lines.join("\n"));
+    }
+
+    /// Move every cursor and selection anchor, clamped to the buffer, and
+    /// merge cursors that end up together
+    fn map_cursors(&mut self, map: impl Fn(Position) -> Position) {
+        let mut cursors: Vec<Cursor> = self.cursors().cloned().collect();
+        for cursor in &mut cursors {
+            cursor.position = self.clamp_position(map(cursor.position));
+            cursor.anchor = cursor.anchor.map(|anchor| self.clamp_position(map(anchor)));
+        }
+        self.cursor = cursors.remove(0);
+        self.extra_cursors = cursors;
+        self.block = None;
+        self.merge_cursors();
+        self.viewport.ensure_visible(self.cursor.position.line);
+    }
+
+    // === Selection ===
+
+    /// Start or extend selection (of every cursor)
+    pub fn start_selection(&mut self) {
+        if self.is_read_only() {
+            return;
+        }
+        let cursors = std::iter::once(&mut self.cursor).chain(&mut self.extra_cursors);
+        for cursor in cursors.filter(|cursor| !cursor.has_selection()) {
+            cursor.start_selection();
+        }
+    }
+
+    /// Clear selection (of every cursor)
+    pub fn clear_selection(&mut self) {
+        self.block = None;
+        self.cursor.clear_selection();
+        for cursor in &mut self.extra_cursors {
+            cursor.clear_selection();
+        }
+    }
+
+    /// Move with selection (Shift+Arrow)
+    pub fn move_up_select(&mut self) {
+        self.start_selection();
+        self.move_up();
+    }
+
+    pub fn move_down_select(&mut self) {
+        self.start_selection();
+        self.move_down();
+    }
+
+    pub fn move_left_select(&mut self) {
+        self.start_selection();
+        self.move_left();
+    }
+
+    pub fn move_right_select(&mut self) {
+        self.start_selection();
+        self.move_right();
+    }
+
+    pub fn move_to_line_start_select(&mut self) {
+        self.start_selection();
+        self.move_to_line_start();
+    }
+
+    pub fn move_to_line_end_select(&mut self) {
+        self.start_selection();
+        self.move_to_line_end();
+    }
+
+    pub fn move_to_start_select(&mut self) {
+        self.start_selection();
+        self.move_to_start();
+    }
+
+    pub fn move_to_end_select(&mut self) {
+        self.start_selection();
+        self.move_to_end();
+    }
+
+    /// Select a range (with a single cursor, the cursor at `end`)
+    pub fn select_range(&mut self, start: Position, end: Position) {
+        self.clear_extra_cursors();
+        self.block = None;
+        let (start, end) = (self.clamp_position(start), self.clamp_position(end));
+        self.cursor.move_to(end.line, end.column);
+        self.cursor.anchor = (start != end).then_some(start);
+        self.viewport.ensure_visible(end.line);
+    }
+
+    /// Select all text
+    pub fn select_all(&mut self) {
+        self.clear_extra_cursors();
+        self.move_to_start();
+        self.cursor.start_selection();
+        self.move_to_end();
+    }
+
+    /// Expand every selection to the enclosing syntax node
+    ///
+    /// `enclosing(start, end)` returns the byte range of the smallest syntax
+    /// node around a byte range that is larger than it. Repeated expansion
+    /// goes identifier, expression, statement, block, method, class.
+    /// Returns `false` if no selection could grow.
+    pub fn expand_selection(
+        &mut self,
+        enclosing: impl Fn(usize, usize) -> Option<(usize, usize)>,
+    ) -> bool {
+        if self.is_read_only() {
+            return false;
+        }
+        let before: Vec<Cursor> = self.cursors().cloned().collect();
+        if self.selection_history.last() != Some(&before) {
+            self.selection_history = vec![before];
+        }
+
+        let mut expanded = false;
+        self.for_each_cursor(|editor| {
+            let (start, end) = selection_bounds(&editor.cursor);
+            let rope = editor.buffer.rope();
+            let start = rope.char_to_byte(editor.char_index(start));
+            let end = rope.char_to_byte(editor.char_index(end));
+            if let Some((start, end)) = enclosing(start, end) {
+                let start = editor.position_at(rope.byte_to_char(start));
+                let end = editor.position_at(rope.byte_to_char(end));
+                editor.cursor.move_to(end.line, end.column);
+                editor.cursor.anchor = Some(start);
+                expanded = true;
+            }
+        });
+        if expanded {
+            self.selection_history
+                .push(self.cursors().cloned().collect());
+        }
+        expanded
+    }
+
+    /// Shrink the selections back to before the last expand step
+    ///
+    /// Returns `false` if the selections weren't expanded, or were changed
+    /// since.
+    pub fn shrink_selection(&mut self) -> bool {
+        let current: Vec<Cursor> = self.cursors().cloned().collect();
+        if self.selection_history.len() < 2 || self.selection_history.last() != Some(&current) {
+            self.selection_history.clear();
+            return false;
+        }
+        self.selection_history.pop();
+        if let Some(previous) = self.selection_history.last() {
+            self.restore_cursors(previous.clone());
+        }
+        true
+    }
+
+    /// Get selected text
+    ///
+    /// With multiple cursors, the selections are joined by newlines (in
+    /// document order). A block selection gives one line per line of the
+    /// rectangle, empty where it lies past the line end.
+    pub fn get_selected_text(&self) -> Option<String> {
+        let mut ranges: Vec<(Position, Position)> = match self.block {
+            Some(_) => self.cursors().map(selection_bounds).collect(),
+            None => self.cursors().filter_map(Cursor::selection_range).collect(),
+        };
+        ranges.sort();
+
+        let texts: Vec<String> = ranges
+            .into_iter()
+            .map(|(start, end)| {
+                let range = self.char_index(start)..self.char_index(end);
+                self.buffer.rope().slice(range).to_string()
+            })
+            .collect();
+        (!texts.is_empty()).then(|| texts.join("\n"))
+    }
+
+    /// Delete selected text
+    ///
+    /// Returns `false` if no cursor has a selection.
+    pub fn delete_selection(&mut self) -> bool {
+        let mut deleted = false;
+        self.edit_each(EditKind::Other, |editor| {
+            let Some((start, end)) = editor.cursor.selection_range() else {
+                return;
+            };
+            let (Some(start_idx), Some(end_idx)) = (
+                editor.buffer.line_col_to_char(start.line, start.column),
+                editor.buffer.line_col_to_char(end.line, end.column),
+            ) else {
+                return;
+            };
+
+            editor.buffer.remove(start_idx, end_idx);
+            editor.cursor.position = start;
+            editor.cursor.clear_selection();
+            editor.viewport.ensure_visible(editor.cursor.position.line);
+            deleted = true;
+        });
+        deleted
+    }
+
+    /// Replace selection with text (or just insert if no selection)
+    pub fn replace_selection(&mut self, text: &str) {
+        self.edit_each(EditKind::Other, |editor| {
+            editor.delete_selection();
+            editor.insert_str(text);
+        });
+    }
+
+    /// Replace search matches (of the current text, in document order) as
+    /// one undo step; returns the number of replaced matches
+    ///
+    /// The replacement texts are expanded from the unchanged text first, as in
+    /// [`SearchQuery::preview`]. Each match is then selected and replaced
+    /// with [`Editor::replace_selection`], from the last one backwards so
+    /// earlier positions stay valid. The cursor ends up after the first
+    /// replacement.
+    pub fn replace_matches(
+        &mut self,
+        query: &SearchQuery,
+        matches: &[SearchMatch],
+        replacement: &Replacement,
+    ) -> usize {
+        if self.is_read_only() || matches.is_empty() {
+            return 0;
+        }
+        // Later replacements on a line would change what `\b` or `$` see
+        let texts: Vec<String> = matches
+            .iter()
+            .map(|m| query.replacement_for(self.buffer.rope(), m, replacement))
+            .collect();
+        self.transaction(EditKind::Other, |editor| {
+            for (m, text) in matches.iter().zip(&texts).rev() {
+                editor.select_range(m.start_position(), m.end_position());
+                editor.replace_selection(text);
+            }
+        });
+        matches.len()
+    }
+
+    /// Paste text, replacing the selections
+    ///
+    /// With multiple cursors and one line per cursor (e.g. copied from as
+    /// many cursors), each cursor gets its own line.
+    pub fn paste(&mut self, text: &str) {
+        let text = normalize_line_endings(text);
+        let lines: Vec<&str> = text.lines().collect();
+        if !self.has_multiple_cursors() || lines.len() != self.extra_cursors.len() + 1 {
+            self.replace_selection(&text);
+            return;
+        }
+
+        let mut lines = lines.into_iter();
+        self.edit_each(EditKind::Other, |editor| {
+            editor.replace_selection(lines.next().unwrap_or_default());
+        });
+    }
+
+    /// Replace the whole text as one undoable change (e.g. recovered content)
+    pub fn replace_all(&mut self, text: &str) {
+        let text = normalize_line_endings(text);
+        self.transaction(EditKind::Other, |editor| {
+            editor.buffer.remove(0, editor.buffer.len_chars());
+            editor.buffer.insert(0, &text);
+            editor.clamp_cursor();
+        });
+    }
+
+    // === Undo/Redo ===
+
+    /// Undo the last change, restoring cursors and selections
+    pub fn undo(&mut self) -> bool {
+        match self.buffer.undo() {
+            Some(cursors) => {
+                self.restore_cursors(cursors);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redo the last undone change, restoring cursors and selections
+    pub fn redo(&mut self) -> bool {
+        match self.buffer.redo() {
+            Some(cursors) => {
+                self.restore_cursors(cursors);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Install cursors returned by undo/redo (primary first)
+    fn restore_cursors(&mut self, mut cursors: Vec<Cursor>) {
+        if cursors.is_empty() {
+            return;
+        }
+        self.cursor = cursors.remove(0);
+        self.extra_cursors = cursors;
+        self.block = None;
+        self.viewport.ensure_visible(self.cursor.position.line);
+    }
+
+    /// Run an edit operation as a single undo transaction
+    ///
+    /// Does nothing in a read-only editor.
+    fn transaction(&mut self, kind: EditKind, edit: impl FnOnce(&mut Self)) {
+        if self.is_read_only() {
+            return;
+        }
+        let cursors: Vec<Cursor> = self.cursors().cloned().collect();
+        self.buffer.begin_transaction(kind, &cursors);
+        edit(self);
+        self.selection_history.clear();
+
+        // Edits forget the sticky column
+        self.cursor.reset_preferred_column();
+        for cursor in &mut self.extra_cursors {
+            cursor.reset_preferred_column();
+        }
+        let cursors: Vec<Cursor> = self.cursors().cloned().collect();
+        self.buffer.commit_transaction(&cursors);
+    }
+
+    /// Run an edit operation at every cursor, as a single undo transaction
+    fn edit_each(&mut self, kind: EditKind, edit: impl FnMut(&mut Self)) {
+        self.transaction(kind, |editor| editor.for_each_cursor(edit));
+    }
+
+    // === File Operations ===
+
+    /// Save the file
+    ///
+    /// Refused for binary files and for files changed on disk since they
+    /// were loaded (see [`Editor::force_save`]). The `.editorconfig`
+    /// whitespace rules are applied first (once the save isn't refused).
+    pub fn save(&mut self) -> Result<(), BufferError> {
+        self.check_saveable()?;
+        if let (true, Some(path)) = (self.buffer.is_changed_on_disk(), self.buffer.path()) {
+            return Err(BufferError::ChangedOnDisk { path: path.clone() });
+        }
+        self.normalize_whitespace();
+        self.buffer.force_save()
+    }
+
+    /// Save the file even if it was changed on disk since it was loaded
+    pub fn force_save(&mut self) -> Result<(), BufferError> {
+        self.check_saveable()?;
+        self.normalize_whitespace();
+        self.buffer.force_save()
+    }
+
+    /// Refuse to save binary files (opened in the hex view)
+    fn check_saveable(&self) -> Result<(), BufferError> {
+        match &self.hex {
+            Some(hex) => Err(BufferError::BinaryFile {
+                path: hex.path().clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Trim trailing whitespace and add or remove the final newline as the
+    /// `.editorconfig` says, as one undo step (nothing if already clean)
+    ///
+    /// Skipped in large-file mode, where a pass over the whole buffer would
+    /// stall.
+    fn normalize_whitespace(&mut self) {
+        if self.buffer.is_large_file() {
+            return;
+        }
+        let editorconfig = self.buffer.editorconfig();
+        let trim = editorconfig.trim_trailing_whitespace == Some(true);
+        let final_newline = editorconfig.insert_final_newline;
+
+        // Trailing whitespace: (line, start column, end column)
+        let mut trailing = Vec::new();
+        if trim {
+            for line in 0..self.buffer.len_lines() {
+                let len = self.line_len(line);
+                let Some(slice) = self.buffer.line(line) else {
+                    continue;
+                };
+                let blanks = slice
+                    .chars()
+                    .take(len)
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .rev()
+                    .take_while(|c| c.is_whitespace())
+                    .count();
+                if blanks > 0 {
+                    trailing.push((line, len - blanks, len));
+                }
+            }
+        }
+
+        let rope = self.buffer.rope();
+        let newlines = rope
+            .chars_at(rope.len_chars())
+            .reversed()
+            .take_while(|&c| c == '\n')
+            .count();
+        let add_newline = final_newline == Some(true) && newlines == 0 && rope.len_chars() > 0;
+        let remove_newlines = if final_newline == Some(false) {
+            newlines
+        } else {
+            0
+        };
+        if trailing.is_empty() && !add_newline && remove_newlines == 0 {
+            return;
+        }
+
+        self.transaction(EditKind::Other, |editor| {
+            for &(line, start, end) in trailing.iter().rev() {
+                let from = editor.char_index(Position::new(line, start));
+                let to = editor.char_index(Position::new(line, end));
+                editor.buffer.remove(from, to);
+            }
+            let len = editor.buffer.len_chars();
+            if add_newline {
+                editor.buffer.insert(len, "\n");
+            } else if remove_newlines > 0 {
+                editor.buffer.remove(len - remove_newlines, len);
+            }
+
+            // Keep the cursors inside the shortened lines
+            editor.map_cursors(|position| position);
+        });
+    }
+
+    /// Clamp a position to the buffer
+    fn clamp_position(&self, position: Position) -> Position {
+        let line = position.line.min(self.buffer.len_lines().saturating_sub(1));
+        Position::new(line, position.column.min(self.line_len(line)))
+    }
+
+    /// Reload the file from disk, keeping the cursor position
+    pub fn reload(&mut self) -> Result<(), BufferError> {
+        if let Some(hex) = &mut self.hex {
+            let cursor = hex.cursor();
+            *hex = HexView::open(hex.path().clone())?;
+            hex.move_to(cursor);
+            self.viewport.ensure_visible(hex.cursor_row());
+            return Ok(());
+        }
+        self.buffer.reload()?;
+        self.clamp_cursor();
+        Ok(())
+    }
+
+    /// Reload the file with a different encoding
+    ///
+    /// A binary file is opened as text with that encoding (in large-file mode
+    /// above the threshold it was opened with).
+    pub fn reopen_with_encoding(&mut self, encoding: Encoding) -> Result<(), BufferError> {
+        if let Some(hex) = &self.hex {
+            let path = hex.path().clone();
+            let len = std::fs::metadata(&path)?.len();
+            self.buffer = if len >= self.load_options.large_file_threshold {
+                Buffer::from_file_streaming(path, Some(encoding))?
+            } else {
+                Buffer::from_file_with_encoding(path, encoding)?
+            };
+            self.hex = None;
+            self.viewport.top_line = 0;
+        } else {
+            self.buffer.reopen_with_encoding(encoding)?;
+        }
+        self.clamp_cursor();
+        Ok(())
+    }
+
+    /// Keep the cursor inside the buffer after its content was replaced
+    fn clamp_cursor(&mut self) {
+        let line = self
+            .cursor
+            .position
+            .line
+            .min(self.buffer.len_lines().saturating_sub(1));
+        let column = self.cursor.position.column.min(self.line_len(line));
+        self.clear_extra_cursors();
+        self.cursor.clear_selection();
+        self.cursor.move_to(line, column);
+        self.viewport.ensure_visible(line);
+    }
+
+    /// Check if modified
+    pub fn is_modified(&self) -> bool {
+        self.buffer.is_modified()
+    }
+
+    /// Get file path
+    pub fn path(&self) -> Option<&PathBuf> {
+        match &self.hex {
+            Some(hex) => Some(hex.path()),
+            None => self.buffer.path(),
+        }
+    }
+
+    /// Get filename for display
+    pub fn filename(&self) -> String {
+        self.path()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "[untitled]".to_string())
+    }
+}
+
+/// Start and end of a cursor's selection (the cursor position if none)
+fn selection_bounds(cursor: &Cursor) -> (Position, Position) {
+    cursor
+        .selection_range()
+        .unwrap_or((cursor.position, cursor.position))
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Position;
+
+    #[test]
+    fn test_insert_char() {
+        let mut editor = Editor::new();
+        editor.insert_char('H');
+        editor.insert_char('i');
+
+        assert_eq!(editor.buffer.text(), "Hi");
+        assert_eq!(editor.cursor.position, Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_insert_newline() {
+        let mut editor = Editor::new();
+        editor.insert_str("Hello");
+        editor.insert_newline();
+        editor.insert_str("World");
+
+        assert_eq!(editor.buffer.text(), "Hello\nWorld");
+        assert_eq!(editor.cursor.position, Position::new(1, 5));
+    }
+
+    #[test]
+    fn test_insert_newline_replaces_selection_as_one_step() {
+        let mut editor = Editor::with_content("hello world");
+        editor.select_range(Position::new(0, 6), Position::new(0, 11));
+
+        editor.insert_newline();
+        assert_eq!(editor.buffer.text(), "hello \n");
+
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "hello world");
+    }
+
+    #[test]
+    fn test_backspace() {
+        let mut editor = Editor::with_content("Hello");
+        editor.cursor.move_to(0, 5);
+
+        editor.backspace();
+        assert_eq!(editor.buffer.text(), "Hell");
+        assert_eq!(editor.cursor.position.column, 4);
+    }
+
+    #[test]
+    fn test_backspace_join_lines() {
+        let mut editor = Editor::with_content("Hello\nWorld");
+        editor.cursor.move_to(1, 0); // Start of "World"
+
+        editor.backspace();
+        assert_eq!(editor.buffer.text(), "HelloWorld");
+        assert_eq!(editor.cursor.position, Position::new(0, 5));
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut editor = Editor::with_content("Hello");
+        editor.cursor.move_to(0, 0);
+
+        editor.delete();
+        assert_eq!(editor.buffer.text(), "ello");
+    }
+
+    #[test]
+    fn test_delete_join_lines() {
+        let mut editor = Editor::with_content("Hello\nWorld");
+        editor.cursor.move_to(0, 5); // End of "Hello"
+
+        editor.delete();
+        assert_eq!(editor.buffer.text(), "HelloWorld");
+    }
+
+    #[test]
+    fn test_multi_cursor_editing() {
+        let mut editor = Editor::with_content("$a = 1;\n$bb = 2;\n$c = 3;");
+        editor.cursor.move_to(0, 1);
+        editor.add_cursor_below();
+        editor.add_cursor_below();
+        assert_eq!(editor.cursors().count(), 3);
+        assert_eq!(editor.cursor.position, Position::new(2, 1));
+
+        editor.type_char('x');
+        assert_eq!(editor.buffer.text(), "$xa = 1;\n$xbb = 2;\n$xc = 3;");
+        editor.move_to_line_end();
+        editor.backspace();
+        assert_eq!(editor.buffer.text(), "$xa = 1\n$xbb = 2\n$xc = 3");
+
+        // One undo step for all cursors, which are restored
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "$xa = 1;\n$xbb = 2;\n$xc = 3;");
+        assert_eq!(editor.cursors().count(), 3);
+
+        // Cursors moved onto the same position merge
+        editor.move_to_start();
+        assert!(!editor.has_multiple_cursors());
+    }
+
+    #[test]
+    fn test_auto_indent_on_enter() {
+        let mut editor = Editor::with_content("    if ($a) {}");
+        editor.cursor.move_to(0, 13);
+        editor.insert_newline();
+        assert_eq!(editor.buffer.text(), "    if ($a) {\n        \n    }");
+        assert_eq!(editor.cursor.position, Position::new(1, 8));
+
+        editor.insert_str("return;");
+        editor.insert_newline();
+        assert_eq!(editor.cursor.position, Position::new(2, 8));
+
+        // A closer typed first on the line dedents it
+        let mut editor = Editor::with_content("    foo {\n        ");
+        editor.cursor.move_to(1, 8);
+        editor.type_char('}');
+        assert_eq!(editor.buffer.text(), "    foo {\n    }");
+        assert_eq!(editor.cursor.position, Position::new(1, 5));
+    }
+
+    #[test]
+    fn test_auto_close_and_step_over() {
+        let mut editor = Editor::new();
+        for ch in "f(\"x".chars() {
+            editor.type_char(ch);
+        }
+        assert_eq!(editor.buffer.text(), "f(\"x\")");
+
+        // Typing the closers steps past them
+        editor.type_char('"');
+        editor.type_char(')');
+        editor.type_char(';');
+        assert_eq!(editor.buffer.text(), "f(\"x\");");
+        assert_eq!(editor.cursor.position, Position::new(0, 7));
+
+        // A closer that wasn't auto-inserted is typed normally
+        let mut editor = Editor::with_content("f()");
+        editor.cursor.move_to(0, 2);
+        editor.type_char(')');
+        assert_eq!(editor.buffer.text(), "f())");
+
+        // No auto-closing in strings and comments
+        let mut editor = Editor::new();
+        editor.type_char_with('(', |_| true);
+        assert_eq!(editor.buffer.text(), "(");
+    }
+
+    #[test]
+    fn test_indent_and_outdent() {
+        // Tab inserts up to the next stop, in the buffer's style
+        let mut editor = Editor::with_content("ab");
+        editor.cursor.move_to(0, 1);
+        editor.indent();
+        assert_eq!(editor.buffer.text(), "a   b");
+        editor.buffer.set_indentation(Indentation::tabs());
+        editor.indent();
+        assert_eq!(editor.buffer.text(), "a   \tb");
+
+        // A multi-line selection indents every non-blank line, as one step
+        let mut editor = Editor::with_content("a\n  b\n\nc\nd");
+        editor.buffer.set_indentation(Indentation::spaces(2));
+        editor.cursor.move_to(0, 0);
+        editor.start_selection();
+        editor.cursor.move_to(4, 0);
+        editor.indent();
+        assert_eq!(editor.buffer.text(), "  a\n    b\n\n  c\nd");
+        assert_eq!(editor.cursor.anchor, Some(Position::new(0, 0)));
+        assert_eq!(editor.cursor.position, Position::new(4, 0));
+
+        editor.outdent();
+        editor.outdent();
+        assert_eq!(editor.buffer.text(), "a\nb\n\nc\nd");
+        editor.undo();
+        editor.undo();
+        editor.undo();
+        assert_eq!(editor.buffer.text(), "a\n  b\n\nc\nd");
+
+        // Shift+Tab without a selection outdents the cursor line
+        let mut editor = Editor::with_content("\t\tx");
+        editor.cursor.move_to(0, 3);
+        editor.outdent();
+        assert_eq!(editor.buffer.text(), "\tx");
+        assert_eq!(editor.cursor.position, Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_convert_indentation() {
+        let mut editor = Editor::with_content("a {\n    b {\n        c\n      d\n    }\n}");
+        assert_eq!(editor.buffer.indentation(), Indentation::spaces(4));
+        editor.convert_indentation(Indentation::tabs());
+        assert_eq!(editor.buffer.text(), "a {\n\tb {\n\t\tc\n\t  d\n\t}\n}");
+        assert_eq!(editor.buffer.indentation(), Indentation::tabs());
+
+        editor.convert_indentation(Indentation::spaces(2));
+        assert_eq!(editor.buffer.text(), "a {\n  b {\n    c\n    d\n  }\n}");
+        editor.undo();
+        assert_eq!(editor.buffer.text(), "a {\n\tb {\n\t\tc\n\t  d\n\t}\n}");
+    }
+
+    #[test]
+    fn test_save_applies_editorconfig_whitespace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "a  \nb\t").unwrap();
+
+        let mut editor = Editor::open(&path).unwrap();
+        editor
+            .buffer
+            .set_editorconfig(crate::EditorconfigProperties {
+                trim_trailing_whitespace: Some(true),
+                insert_final_newline: Some(true),
+                ..Default::default()
+            });
+        editor.cursor.move_to(0, 3);
+        editor.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb\n");
+        assert_eq!(editor.cursor.position, Position::new(0, 1));
+        assert!(!editor.is_modified());
+
+        // One undo step
+        editor.undo();
+        assert_eq!(editor.buffer.text(), "a  \nb\t");
+
+        editor
+            .buffer
+            .set_editorconfig(crate::EditorconfigProperties {
+                insert_final_newline: Some(false),
+                ..Default::default()
+            });
+        editor.move_to_end();
+        editor.insert_str("\n\n");
+        editor.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a  \nb\t");
+
+        // A refused save leaves the buffer alone
+        std::fs::write(&path, "changed").unwrap();
+        editor
+            .buffer
+            .set_editorconfig(crate::EditorconfigProperties {
+                trim_trailing_whitespace: Some(true),
+                ..Default::default()
+            });
+        assert!(matches!(
+            editor.save(),
+            Err(BufferError::ChangedOnDisk { .. })
+        ));
+        assert_eq!(editor.buffer.text(), "a  \nb\t");
+    }
+
+    fn select(editor: &mut Editor, from: (usize, usize), to: (usize, usize)) {
+        editor.clear_selection();
+        editor.cursor.move_to(from.0, from.1);
+        editor.start_selection();
+        editor.cursor.move_to(to.0, to.1);
+    }
+
+    #[test]
+    fn test_duplicate_and_delete_lines() {
+        let mut editor = Editor::with_content("a\nb\nc");
+        editor.cursor.move_to(1, 1);
+        editor.duplicate_lines();
+        assert_eq!(editor.buffer.text(), "a\nb\nb\nc");
+        assert_eq!(editor.cursor.position, Position::new(2, 1));
+
+        // A selection ending at a line start doesn't include that line
+        select(&mut editor, (0, 0), (2, 0));
+        editor.duplicate_lines();
+        assert_eq!(editor.buffer.text(), "a\nb\na\nb\nb\nc");
+        assert_eq!(
+            editor.cursor.selection_range(),
+            Some((Position::new(2, 0), Position::new(4, 0)))
+        );
+        editor.undo();
+        assert_eq!(editor.buffer.text(), "a\nb\nb\nc");
+
+        select(&mut editor, (1, 0), (2, 1));
+        editor.delete_lines();
+        assert_eq!(editor.buffer.text(), "a\nc");
+        assert_eq!(editor.cursor.position, Position::new(1, 1));
+        assert!(!editor.cursor.has_selection());
+
+        // The last line takes the line break before it
+        editor.delete_lines();
+        assert_eq!(editor.buffer.text(), "a");
+        assert_eq!(editor.cursor.position, Position::new(0, 1));
+    }
+
+    #[test]
+    fn test_move_lines() {
+        let mut editor = Editor::with_content("a\nb\nc\nd");
+        select(&mut editor, (1, 0), (2, 1));
+        editor.move_lines_up();
+        assert_eq!(editor.buffer.text(), "b\nc\na\nd");
+        assert_eq!(
+            editor.cursor.selection_range(),
+            Some((Position::new(0, 0), Position::new(1, 1)))
+        );
+
+        // Nothing moves past the top
+        editor.move_lines_up();
+        assert_eq!(editor.buffer.text(), "b\nc\na\nd");
+
+        editor.move_lines_down();
+        editor.move_lines_down();
+        assert_eq!(editor.buffer.text(), "a\nd\nb\nc");
+        editor.move_lines_down();
+        assert_eq!(editor.buffer.text(), "a\nd\nb\nc");
+
+        // Several cursors move their lines together
+        let mut editor = Editor::with_content("1\n2\n3\n4");
+        editor.cursor.move_to(1, 0);
+        editor.add_cursor_below();
+        editor.add_cursor_below();
+        editor.move_lines_up();
+        assert_eq!(editor.buffer.text(), "2\n3\n4\n1");
+        assert_eq!(editor.cursors().count(), 3);
+        editor.undo();
+        assert_eq!(editor.buffer.text(), "1\n2\n3\n4");
+    }
+
+    #[test]
+    fn test_join_and_sort_lines() {
+        let mut editor = Editor::with_content("foo(  \n    a,\n    b\n)");
+        editor.cursor.move_to(0, 3);
+        editor.join_lines();
+        assert_eq!(editor.buffer.text(), "foo( a,\n    b\n)");
+        assert_eq!(editor.cursor.position, Position::new(0, 3));
+
+        select(&mut editor, (0, 0), (2, 1));
+        editor.join_lines();
+        assert_eq!(editor.buffer.text(), "foo( a, b )");
+        assert_eq!(
+            editor.cursor.selection_range(),
+            Some((Position::new(0, 0), Position::new(0, 11)))
+        );
+
+        let mut editor = Editor::with_content("use C;\nuse A;\nuse B;\n\nx");
+        select(&mut editor, (0, 0), (3, 0));
+        editor.sort_lines();
+        assert_eq!(editor.buffer.text(), "use A;\nuse B;\nuse C;\n\nx");
+        editor.undo();
+        assert_eq!(editor.buffer.text(), "use C;\nuse A;\nuse B;\n\nx");
+    }
+
+    #[test]
+    fn test_replace_matches() {
+        let mut editor = Editor::with_content("$a = foo(1);\n$b = foo(22) + foo(3);");
+        let options = crate::SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new(r"foo\((\d+)\)", options, &editor.word_chars).unwrap();
+        let matches = query.find_all(editor.buffer.rope());
+        let replacement = Replacement {
+            template: String::from("bar($1, 0)"),
+            preserve_case: false,
+        };
+
+        // Only the matches in a selection
+        let (start, end) = (Position::new(1, 0), Position::new(1, 21));
+        let selected: Vec<_> = matches
+            .iter()
+            .copied()
+            .filter(|m| m.is_within(start, end))
+            .collect();
+        assert_eq!(editor.replace_matches(&query, &selected, &replacement), 2);
+        assert_eq!(
+            editor.buffer.text(),
+            "$a = foo(1);\n$b = bar(22, 0) + bar(3, 0);"
+        );
+        assert_eq!(editor.cursor.position, Position::new(1, 15));
+
+        // Replace all is one undo step
+        let matches = query.find_all(editor.buffer.rope());
+        assert_eq!(editor.replace_matches(&query, &matches, &replacement), 1);
+        assert_eq!(
+            editor.buffer.text(),
+            "$a = bar(1, 0);\n$b = bar(22, 0) + bar(3, 0);"
+        );
+        editor.undo();
+        editor.undo();
+        assert_eq!(editor.buffer.text(), "$a = foo(1);\n$b = foo(22) + foo(3);");
+    }
+
+    #[test]
+    fn test_replace_matches_expands_before_replacing() {
+        // `\B` after the first `a` only matches while the second one is there
+        let mut editor = Editor::with_content("aab");
+        let options = crate::SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new(r"(a)\B", options, &editor.word_chars).unwrap();
+        let matches = query.find_all(editor.buffer.rope());
+        let replacement = Replacement {
+            template: String::from("<$1>"),
+            preserve_case: false,
+        };
+
+        let preview = query.preview(editor.buffer.rope(), &matches, &replacement);
+        assert_eq!(preview[0].after, "<a><a>b");
+        assert_eq!(editor.replace_matches(&query, &matches, &replacement), 2);
+        assert_eq!(editor.buffer.text(), "<a><a>b");
+    }
+
+    #[test]
+    fn test_insert_newline_with_multiple_cursors() {
+        let mut editor = Editor::with_content("ab\ncd");
+        editor.cursor.move_to(0, 1);
+        editor.add_cursor_below();
+
+        editor.insert_newline();
+        assert_eq!(editor.buffer.text(), "a\nb\nc\nd");
+        let positions: Vec<Position> = editor.cursors().map(|c| c.position).collect();
+        assert_eq!(positions, vec![Position::new(3, 0), Position::new(1, 0)]);
+    }
+
+    #[test]
+    fn test_add_next_occurrence() {
+        let mut editor = Editor::with_content("$user = $repo->user;\nreturn $user;");
+        editor.cursor.move_to(0, 2);
+
+        // Selects the word first, then the following occurrences
+        assert!(editor.add_next_occurrence());
+        assert_eq!(editor.get_selected_text().unwrap(), "user");
+        assert!(editor.add_next_occurrence());
+        assert!(editor.add_next_occurrence());
+        assert_eq!(editor.cursors().count(), 3);
+        assert!(!editor.add_next_occurrence());
+
+        editor.paste("member");
+        assert_eq!(
+            editor.buffer.text(),
+            "$member = $repo->member;\nreturn $member;"
+        );
+    }
+
+    #[test]
+    fn test_add_next_occurrence_across_lines() {
+        let mut editor = Editor::with_content("a b\nc d\nb\nc\nxb\nc e");
+        editor.cursor.move_to(0, 2);
+        editor.start_selection();
+        editor.cursor.move_to(1, 1);
+        assert_eq!(editor.get_selected_text().unwrap(), "b\nc");
+
+        // Each line of the text must match
+        assert!(editor.add_next_occurrence());
+        assert!(editor.add_next_occurrence());
+        assert!(!editor.add_next_occurrence());
+        let mut selections: Vec<_> = editor
+            .cursors()
+            .filter_map(Cursor::selection_range)
+            .collect();
+        selections.sort();
+        assert_eq!(
+            selections,
+            [
+                (Position::new(0, 2), Position::new(1, 1)),
+                (Position::new(2, 0), Position::new(3, 1)),
+                (Position::new(4, 1), Position::new(5, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_selections_merge() {
+        let mut editor = Editor::with_content("abcdef");
+        editor.cursor.move_to(0, 1);
+        editor.add_cursor(Cursor::at(0, 3));
+        editor.move_right_select();
+        editor.move_right_select();
+        assert_eq!(editor.cursors().count(), 2);
+
+        editor.move_right_select();
+        assert!(!editor.has_multiple_cursors());
+        assert_eq!(
+            editor.cursor.selection_range(),
+            Some((Position::new(0, 1), Position::new(0, 6)))
+        );
+    }
+
+    #[test]
+    fn test_paste_distributes_lines() {
+        let mut editor = Editor::with_content("a\nb");
+        editor.add_cursor_below();
+
+        editor.paste("1\n2");
+        assert_eq!(editor.buffer.text(), "1a\n2b");
+    }
+
+    #[test]
+    fn test_paste_inserts_text_at_once() {
+        let mut editor = Editor::with_content("ab");
+        editor.cursor.move_to(0, 1);
+
+        editor.paste("x\nyz");
+        assert_eq!(editor.buffer.text(), "ax\nyzb");
+        assert_eq!(editor.cursor.position, Position::new(1, 2));
+
+        // One edit, not one per character
+        let mut history = editor.buffer.history().clone();
+        let transaction = history.undo().unwrap();
+        assert_eq!(transaction.edits, vec![crate::Edit::insert(1, "x\nyz")]);
+    }
+
+    #[test]
+    fn test_block_selection_typing_and_copy() {
+        let mut editor = Editor::with_content("id  name\n1   a\n\n22  bb");
+        editor.cursor.move_to(0, 4);
+        editor.extend_block_selection(3, 0);
+        editor.extend_block_selection(0, 4);
+
+        // The empty line has nothing selected; copying gives one line each
+        let block = *editor.block_selection().unwrap();
+        assert_eq!(block.lines(), 0..=3);
+        assert_eq!(block.columns(), 4..8);
+        assert_eq!(editor.get_selected_text().unwrap(), "name\na\n\nbb");
+
+        // Typing replaces the block on every line
+        editor.type_char('x');
+        assert!(editor.block_selection().is_none());
+        assert_eq!(editor.buffer.text(), "id  x\n1   x\nx\n22  x");
+    }
+
+    #[test]
+    fn test_paste_block_pads_short_lines() {
+        let mut editor = Editor::with_content("abc\na\nabc");
+        editor.cursor.move_to(0, 2);
+
+        editor.paste_block("1\n2\n3\n4");
+        assert_eq!(editor.buffer.text(), "ab1c\na 2\nab3c\n  4");
+        assert_eq!(editor.cursor.position, Position::new(3, 3));
+
+        // One undo step
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "abc\na\nabc");
+    }
+
+    #[test]
+    fn test_word_motions_cross_lines() {
+        let mut editor = Editor::with_content("foo bar\n  baz");
+        editor.cursor.move_to(0, 3);
+
+        editor.move_word_right();
+        assert_eq!(editor.cursor.position, Position::new(0, 7));
+        editor.move_word_right();
+        assert_eq!(editor.cursor.position, Position::new(1, 0));
+        editor.move_word_right();
+        assert_eq!(editor.cursor.position, Position::new(1, 5));
+
+        editor.move_word_left_select();
+        assert_eq!(editor.cursor.position, Position::new(1, 2));
+        editor.move_word_left_select();
+        editor.move_word_left_select();
+        assert_eq!(editor.cursor.position, Position::new(0, 7));
+        assert_eq!(editor.get_selected_text().unwrap(), "\n  baz");
+    }
+
+    #[test]
+    fn test_delete_word_uses_word_chars() {
+        let mut editor = Editor::with_content("return $userRepository;");
+        editor.word_chars = WordChars::for_extension("php");
+        editor.cursor.move_to(0, 22);
+
+        editor.move_subword_left();
+        assert_eq!(editor.cursor.position, Position::new(0, 12));
+        editor.move_to_line_end();
+        editor.move_left();
+
+        editor.delete_word_left();
+        assert_eq!(editor.buffer.text(), "return ;");
+        editor.cursor.move_to(0, 0);
+        editor.delete_word_right();
+        assert_eq!(editor.buffer.text(), " ;");
+    }
+
+    #[test]
+    fn test_paragraph_motions() {
+        let mut editor = Editor::with_content("a\nb\n\nc\n\n\nd");
+        editor.cursor.move_to(0, 0);
+
+        editor.move_paragraph_down();
+        assert_eq!(editor.cursor.position, Position::new(2, 0));
+        editor.move_paragraph_down();
+        assert_eq!(editor.cursor.position, Position::new(4, 0));
+        editor.move_paragraph_down();
+        assert_eq!(editor.cursor.position, Position::new(6, 1));
+
+        editor.move_paragraph_up();
+        assert_eq!(editor.cursor.position, Position::new(5, 0));
+        editor.move_paragraph_up();
+        assert_eq!(editor.cursor.position, Position::new(2, 0));
+        editor.move_paragraph_up();
+        assert_eq!(editor.cursor.position, Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_grapheme_motions_and_deletions() {
+        // "cafe" + combining acute, then a ZWJ family emoji
+        let mut editor = Editor::with_content("cafe\u{301}👨\u{200d}👩\u{200d}👧!\nx");
+        editor.cursor.move_to(0, 3);
+
+        editor.move_right();
+        assert_eq!(editor.cursor.position, Position::new(0, 5));
+        editor.move_right();
+        assert_eq!(editor.cursor.position, Position::new(0, 10));
+        editor.move_left();
+        assert_eq!(editor.cursor.position, Position::new(0, 5));
+
+        editor.delete();
+        assert_eq!(editor.buffer.text(), "cafe\u{301}!\nx");
+        editor.backspace();
+        assert_eq!(editor.buffer.text(), "caf!\nx");
+        assert_eq!(editor.cursor.position, Position::new(0, 3));
+
+        // Vertical moves keep the display column and don't land inside a cluster
+        let mut editor = Editor::with_content("e\u{301}e\u{301}\nabcd");
+        editor.cursor.move_to(1, 1);
+        editor.move_up();
+        assert_eq!(editor.cursor.position, Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_sticky_column() {
+        let mut editor = Editor::with_content("中文中文\nab\nabcdefgh\nx\nabcdefgh");
+        editor.set_viewport_size(4, 80);
+
+        // Display column 6 survives the short line and a page down
+        editor.cursor.move_to(0, 3);
+        editor.move_down();
+        assert_eq!(editor.cursor.position, Position::new(1, 2));
+        editor.move_down();
+        assert_eq!(editor.cursor.position, Position::new(2, 6));
+        editor.page_down();
+        assert_eq!(editor.cursor.position, Position::new(4, 6));
+        editor.page_up();
+        assert_eq!(editor.cursor.position, Position::new(2, 6));
+
+        // ... and adding cursors through short lines
+        editor.cursor.move_to(0, 3);
+        editor.add_cursor_below();
+        editor.add_cursor_below();
+        let positions: Vec<Position> = editor.cursors().map(|c| c.position).collect();
+        assert!(positions.contains(&Position::new(1, 2)));
+        assert!(positions.contains(&Position::new(2, 6)));
+        editor.move_down();
+        editor.move_down();
+        let positions: Vec<Position> = editor.cursors().map(|c| c.position).collect();
+        assert!(positions.contains(&Position::new(3, 1)));
+        assert!(positions.contains(&Position::new(4, 6)));
+        editor.clear_extra_cursors();
+
+        // Edits reset it
+        editor.cursor.move_to(2, 6);
+        editor.move_down();
+        editor.backspace();
+        editor.move_down();
+        assert_eq!(editor.cursor.position, Position::new(4, 0));
+    }
+
+    #[test]
+    fn test_expand_and_shrink_selection() {
+        // Syntax nodes of `f(a, [bc])`, as a parser would report them
+        let nodes = [(6, 8), (5, 9), (2, 9), (1, 10), (0, 10)];
+        let enclosing = |start: usize, end: usize| {
+            nodes
+                .iter()
+                .copied()
+                .find(|&(s, e)| s <= start && e >= end && (s, e) != (start, end))
+        };
+
+        let mut editor = Editor::with_content("f(a, [bc])");
+        editor.cursor.move_to(0, 6);
+        assert!(editor.expand_selection(enclosing));
+        assert_eq!(editor.get_selected_text().as_deref(), Some("bc"));
+        assert!(editor.expand_selection(enclosing));
+        assert!(editor.expand_selection(enclosing));
+        assert_eq!(editor.get_selected_text().as_deref(), Some("a, [bc]"));
+
+        assert!(editor.shrink_selection());
+        assert_eq!(editor.get_selected_text().as_deref(), Some("[bc]"));
+        assert!(editor.shrink_selection());
+        assert!(editor.shrink_selection());
+        assert!(!editor.cursor.has_selection());
+        assert_eq!(editor.cursor.position, Position::new(0, 6));
+        assert!(!editor.shrink_selection());
+
+        // Moving the cursor forgets the history
+        editor.expand_selection(enclosing);
+        editor.move_left();
+        assert!(!editor.shrink_selection());
+    }
+
+    #[test]
+    fn test_viewport_scrolling() {
+        let mut editor = Editor::with_content("Line1\nLine2\nLine3\nLine4\nLine5");
+        editor.set_viewport_size(3, 80);
+
+        // Move down past viewport
+        editor.move_down();
+        editor.move_down();
+        editor.move_down();
+
+        assert_eq!(editor.cursor.position.line, 3);
+        assert!(editor.viewport.top_line > 0);
+    }
+
+    #[test]
+    fn test_selection_get_text() {
+        let mut editor = Editor::with_content("Hello World");
+        editor.cursor.move_to(0, 0);
+        editor.cursor.start_selection();
+        editor.cursor.move_to(0, 5);
 
         let selected = editor.get_selected_text();
         assert_eq!(selected, Some("Hello".to_string()));
@@ -509,4 +2745,129 @@ mod tests {
         let selected = editor.get_selected_text();
         assert_eq!(selected, Some("Hello\nWorld".to_string()));
     }
+
+    #[test]
+    fn test_undo_typed_word() {
+        let mut editor = Editor::new();
+        for ch in "Hello World".chars() {
+            editor.type_char(ch);
+        }
+
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "Hello");
+        assert_eq!(editor.cursor.position, Position::new(0, 5));
+
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "");
+        assert!(!editor.undo());
+
+        assert!(editor.redo());
+        assert!(editor.redo());
+        assert_eq!(editor.buffer.text(), "Hello World");
+        assert_eq!(editor.cursor.position, Position::new(0, 11));
+    }
+
+    #[test]
+    fn test_undo_replace_selection_restores_selection() {
+        let mut editor = Editor::with_content("Hello World");
+        editor.cursor.move_to(0, 6);
+        editor.cursor.start_selection();
+        editor.cursor.move_to(0, 11);
+
+        editor.replace_selection("Rust\nand more");
+        assert_eq!(editor.buffer.text(), "Hello Rust\nand more");
+
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "Hello World");
+        assert_eq!(
+            editor.cursor.selection_range(),
+            Some((Position::new(0, 6), Position::new(0, 11)))
+        );
+        assert!(!editor.is_modified());
+    }
+
+    #[test]
+    fn test_undo_multi_cursor_typing_per_word() {
+        let mut editor = Editor::with_content("a\nb\nc");
+        editor.add_cursor_below();
+        editor.add_cursor_below();
+
+        for ch in "xy z".chars() {
+            editor.type_char(ch);
+        }
+        assert_eq!(editor.buffer.text(), "xy za\nxy zb\nxy zc");
+
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "xya\nxyb\nxyc");
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "a\nb\nc");
+        assert!(!editor.undo());
+        assert_eq!(editor.cursors().count(), 3);
+
+        // Redo replays the coalesced edits in order
+        assert!(editor.redo());
+        assert_eq!(editor.buffer.text(), "xya\nxyb\nxyc");
+
+        // Backspacing with all cursors is one step as well
+        editor.backspace();
+        editor.backspace();
+        assert_eq!(editor.buffer.text(), "a\nb\nc");
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "xya\nxyb\nxyc");
+    }
+
+    #[test]
+    fn test_undo_backspaces_as_one_step() {
+        let mut editor = Editor::with_content("Hello");
+        editor.cursor.move_to(0, 5);
+        editor.backspace();
+        editor.backspace();
+        editor.backspace();
+        assert_eq!(editor.buffer.text(), "He");
+
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "Hello");
+        assert_eq!(editor.cursor.position, Position::new(0, 5));
+    }
+
+    #[test]
+    fn test_open_binary_file_as_hex_view() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.phar");
+        let bytes: Vec<u8> = (0..=255).collect();
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut editor = Editor::open(&path).unwrap();
+        assert!(editor.is_read_only());
+        assert_eq!(editor.filename(), "archive.phar");
+
+        editor.move_down();
+        editor.move_right();
+        assert_eq!(editor.hex.as_ref().unwrap().cursor(), 17);
+
+        // Edits and saving are refused
+        editor.type_char('x');
+        editor.backspace();
+        assert!(!editor.is_modified());
+        assert!(matches!(editor.save(), Err(BufferError::BinaryFile { .. })));
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_reopen_large_binary_file_as_text_streams() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.bin");
+        std::fs::write(&path, b"a\0b\nc").unwrap();
+
+        let options = LoadOptions {
+            large_file_threshold: 4,
+        };
+        let mut editor = Editor::open_with_options(&path, &options).unwrap();
+        assert!(editor.is_read_only());
+
+        editor.reopen_with_encoding(Encoding::Iso8859_1).unwrap();
+        assert!(!editor.is_read_only());
+        assert!(editor.buffer.is_large_file());
+        assert_eq!(editor.buffer.text(), "a\0b\nc");
+    }
 }
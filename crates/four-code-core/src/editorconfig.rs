@@ -0,0 +1,500 @@
+//! `.editorconfig` support
+//!
+//! The `.editorconfig` files from the directory of a file up to the file
+//! system root (or the first one with `root = true`) are read, and the
+//! properties of all sections whose glob matches the file are applied, with
+//! nearer files and later sections winning. See <https://editorconfig.org>.
+//!
+//! Unknown keys and invalid values are ignored, `unset` removes a value set
+//! by a more distant file.
+
+use crate::{Encoding, IndentStyle, Indentation, LineEnding};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File name of editorconfig files
+pub const EDITORCONFIG_FILE: &str = ".editorconfig";
+
+/// Properties resolved for a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditorconfigProperties {
+    /// `indent_style`
+    pub indent_style: Option<IndentStyle>,
+    /// `indent_size` (`tab` resolves to the tab width)
+    pub indent_size: Option<usize>,
+    /// `tab_width` (defaults to `indent_size`)
+    pub tab_width: Option<usize>,
+    /// `end_of_line`
+    pub end_of_line: Option<LineEnding>,
+    /// `charset`: encoding and BOM
+    pub charset: Option<(Encoding, bool)>,
+    /// `trim_trailing_whitespace`
+    pub trim_trailing_whitespace: Option<bool>,
+    /// `insert_final_newline`
+    pub insert_final_newline: Option<bool>,
+    /// `max_line_length` (`off` is None)
+    pub max_line_length: Option<usize>,
+    /// The `.editorconfig` files that were read, nearest first
+    pub files: Vec<PathBuf>,
+}
+
+/// A parsed `.editorconfig` file
+#[derive(Debug, Clone, Default)]
+struct EditorconfigFile {
+    /// `root = true` in the preamble: don't look further up
+    root: bool,
+    /// Sections in file order
+    sections: Vec<Section>,
+}
+
+/// A `[glob]` section
+#[derive(Debug, Clone)]
+struct Section {
+    glob: String,
+    /// Properties with lowercase keys, in file order
+    properties: Vec<(String, String)>,
+}
+
+impl EditorconfigProperties {
+    /// Resolve the properties of a file from the `.editorconfig` chain
+    ///
+    /// Unreadable `.editorconfig` files are skipped.
+    pub fn resolve(path: &Path) -> Self {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let file = dir.join(EDITORCONFIG_FILE);
+            let Ok(text) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            let parsed = EditorconfigFile::parse(&text);
+            let root = parsed.root;
+            files.push((dir.to_path_buf(), file, parsed));
+            if root {
+                break;
+            }
+        }
+
+        // From the most distant file to the nearest one
+        let mut values = HashMap::new();
+        for (dir, _, parsed) in files.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            parsed.apply(&relative, &mut values);
+        }
+
+        let mut properties = Self::from_values(&values);
+        properties.files = files.into_iter().map(|(_, file, _)| file).collect();
+        properties
+    }
+
+    /// Interpret raw values (lowercase keys)
+    fn from_values(values: &HashMap<String, String>) -> Self {
+        let get = |key: &str| values.get(key).map(|value| value.to_lowercase());
+        let number = |key: &str| {
+            get(key)
+                .and_then(|value| value.parse().ok())
+                .filter(|&n| n > 0)
+        };
+        let boolean = |key: &str| match get(key).as_deref() {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        };
+
+        let indent_style = match get("indent_style").as_deref() {
+            Some("tab") => Some(IndentStyle::Tabs),
+            Some("space") => Some(IndentStyle::Spaces),
+            _ => None,
+        };
+        let indent_size_is_tab = get("indent_size").as_deref() == Some("tab")
+            || (indent_style == Some(IndentStyle::Tabs) && !values.contains_key("indent_size"));
+        let mut indent_size = number("indent_size");
+        let tab_width = number("tab_width").or(indent_size);
+        if indent_size_is_tab {
+            indent_size = tab_width;
+        }
+
+        Self {
+            indent_style,
+            indent_size,
+            tab_width,
+            end_of_line: match get("end_of_line").as_deref() {
+                Some("lf") => Some(LineEnding::Lf),
+                Some("crlf") => Some(LineEnding::Crlf),
+                Some("cr") => Some(LineEnding::Cr),
+                _ => None,
+            },
+            charset: match get("charset").as_deref() {
+                Some("utf-8") => Some((Encoding::Utf8, false)),
+                Some("utf-8-bom") => Some((Encoding::Utf8, true)),
+                Some("utf-16le") => Some((Encoding::Utf16Le, true)),
+                Some("utf-16be") => Some((Encoding::Utf16Be, true)),
+                Some("latin1") => Some((Encoding::Iso8859_1, false)),
+                _ => None,
+            },
+            trim_trailing_whitespace: boolean("trim_trailing_whitespace"),
+            insert_final_newline: boolean("insert_final_newline"),
+            max_line_length: number("max_line_length"),
+            files: Vec::new(),
+        }
+    }
+
+    /// Indentation of the file: the configured style and size, the detected
+    /// indentation for anything not configured
+    pub fn indentation(&self, detected: Indentation) -> Indentation {
+        match (self.indent_style, self.indent_size) {
+            (Some(IndentStyle::Tabs), _) => Indentation::tabs(),
+            (Some(IndentStyle::Spaces), size) => {
+                Indentation::spaces(size.unwrap_or(match detected.style {
+                    IndentStyle::Spaces => detected.size,
+                    IndentStyle::Tabs => Indentation::default().size,
+                }))
+            }
+            (None, Some(size)) if detected.style == IndentStyle::Spaces => {
+                Indentation::spaces(size)
+            }
+            (None, _) => detected,
+        }
+    }
+
+    /// Resolved properties as (key, value) pairs, for display
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = Vec::new();
+        let mut push = |key, value: Option<String>| {
+            if let Some(value) = value {
+                entries.push((key, value));
+            }
+        };
+        push(
+            "indent_style",
+            self.indent_style.map(|style| match style {
+                IndentStyle::Tabs => String::from("tab"),
+                IndentStyle::Spaces => String::from("space"),
+            }),
+        );
+        push("indent_size", self.indent_size.map(|n| n.to_string()));
+        push("tab_width", self.tab_width.map(|n| n.to_string()));
+        push(
+            "end_of_line",
+            self.end_of_line.map(|ending| ending.name().to_lowercase()),
+        );
+        push(
+            "charset",
+            self.charset.map(|(encoding, bom)| {
+                format!("{}{}", encoding.name(), if bom { " BOM" } else { "" })
+            }),
+        );
+        push(
+            "trim_trailing_whitespace",
+            self.trim_trailing_whitespace.map(|b| b.to_string()),
+        );
+        push(
+            "insert_final_newline",
+            self.insert_final_newline.map(|b| b.to_string()),
+        );
+        push(
+            "max_line_length",
+            self.max_line_length.map(|n| n.to_string()),
+        );
+        entries
+    }
+}
+
+impl EditorconfigFile {
+    /// Parse an `.editorconfig` file (invalid lines are skipped)
+    fn parse(text: &str) -> Self {
+        let mut file = Self::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                file.sections.push(Section {
+                    glob: glob.to_string(),
+                    properties: Vec::new(),
+                });
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim().to_string());
+            match file.sections.last_mut() {
+                Some(section) => section.properties.push((key, value)),
+                None if key == "root" => file.root = value.eq_ignore_ascii_case("true"),
+                None => {}
+            }
+        }
+        file
+    }
+
+    /// Apply the sections matching a path (relative to the file's directory,
+    /// `/`-separated)
+    fn apply(&self, relative: &str, values: &mut HashMap<String, String>) {
+        for section in self.sections.iter().filter(|s| s.matches(relative)) {
+            for (key, value) in &section.properties {
+                if value.eq_ignore_ascii_case("unset") {
+                    values.remove(key);
+                } else {
+                    values.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Section {
+    /// Check if the section's glob matches a relative path
+    ///
+    /// Globs without `/` match the file name in any directory, others are
+    /// relative to the `.editorconfig` directory.
+    fn matches(&self, relative: &str) -> bool {
+        let glob: Vec<char> = self.glob.chars().collect();
+        if glob.contains(&'/') {
+            let glob = glob.strip_prefix(&['/']).unwrap_or(&glob);
+            glob_match(glob, &relative.chars().collect::<Vec<_>>())
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            glob_match(&glob, &name.chars().collect::<Vec<_>>())
+        }
+    }
+}
+
+/// Match an editorconfig glob: `*` (not across `/`), `**`, `?`, `[abc]`,
+/// `[!a-z]`, `{a,b}`, `{1..10}` and `\` escapes
+fn glob_match(glob: &[char], text: &[char]) -> bool {
+    let Some((&first, rest)) = glob.split_first() else {
+        return text.is_empty();
+    };
+    match first {
+        '*' if rest.first() == Some(&'*') => {
+            let rest = &rest[1..];
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        '*' => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match(rest, &text[i..])),
+        '?' => text.first().is_some_and(|&c| c != '/') && glob_match(rest, &text[1..]),
+        '[' => match rest.iter().position(|&c| c == ']') {
+            Some(end) if end > 0 => {
+                let class = &rest[..end];
+                text.first()
+                    .is_some_and(|&c| c != '/' && class_matches(class, c))
+                    && glob_match(&rest[end + 1..], &text[1..])
+            }
+            _ => literal_match('[', rest, text),
+        },
+        '{' => match closing_brace(rest) {
+            Some(end) => braces_match(&rest[..end], &rest[end + 1..], text),
+            None => literal_match('{', rest, text),
+        },
+        '\\' if !rest.is_empty() => literal_match(rest[0], &rest[1..], text),
+        c => literal_match(c, rest, text),
+    }
+}
+
+/// Match a literal character, then the rest of the glob
+fn literal_match(c: char, rest: &[char], text: &[char]) -> bool {
+    text.first() == Some(&c) && glob_match(rest, &text[1..])
+}
+
+/// Check if a character is in a `[...]` class (without the brackets)
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some(('!', rest)) => (true, rest),
+        _ => (false, class),
+    };
+    let mut i = 0;
+    let mut found = false;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+/// Index of the `}` closing a `{` (nesting allowed)
+fn closing_brace(glob: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, &c) in glob.iter().enumerate() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Match `{alternatives}` or `{n1..n2}`, then the rest of the glob
+fn braces_match(inner: &[char], rest: &[char], text: &[char]) -> bool {
+    let inner_text: String = inner.iter().collect();
+    if let Some((from, to)) = inner_text.split_once("..") {
+        if let (Ok(from), Ok(to)) = (from.parse::<i64>(), to.parse::<i64>()) {
+            let digits = text
+                .iter()
+                .enumerate()
+                .take_while(|&(i, &c)| c.is_ascii_digit() || (i == 0 && c == '-'))
+                .count();
+            return (1..=digits).any(|len| {
+                let number: String = text[..len].iter().collect();
+                number
+                    .parse::<i64>()
+                    .is_ok_and(|n| (from.min(to)..=from.max(to)).contains(&n))
+                    && glob_match(rest, &text[len..])
+            });
+        }
+    }
+
+    // Split at top-level commas
+    let mut alternatives = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, &c) in inner.iter().enumerate() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&inner[start..]);
+    if alternatives.len() == 1 {
+        // `{single}` is literal
+        let mut glob = vec!['\\', '{'];
+        glob.extend_from_slice(inner);
+        glob.extend(['\\', '}']);
+        glob.extend_from_slice(rest);
+        return glob_match(&glob, text);
+    }
+    alternatives.into_iter().any(|alternative| {
+        let glob: Vec<char> = alternative.iter().chain(rest).copied().collect();
+        glob_match(&glob, text)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        Section {
+            glob: glob.to_string(),
+            properties: Vec::new(),
+        }
+        .matches(path)
+    }
+
+    #[test]
+    fn test_globs() {
+        assert!(matches("*", "src/main.rs"));
+        assert!(matches("*.php", "src/Controller/Home.php"));
+        assert!(!matches("*.php", "src/Home.phpx"));
+        assert!(matches("*.{js,ts}", "app.ts"));
+        assert!(!matches("*.{js,ts}", "app.rs"));
+        assert!(matches("Makefile", "sub/Makefile"));
+        assert!(matches("[Mm]akefile", "makefile"));
+        assert!(!matches("[!M]akefile", "Makefile"));
+        assert!(matches("file{1..12}.txt", "file10.txt"));
+        assert!(!matches("file{1..12}.txt", "file13.txt"));
+
+        // Globs with a slash are relative to the .editorconfig directory
+        assert!(matches("lib/*.js", "lib/a.js"));
+        assert!(!matches("lib/*.js", "lib/sub/a.js"));
+        assert!(matches("/lib/**.js", "lib/sub/a.js"));
+        assert!(matches("vendor/**", "vendor/a/b.php"));
+    }
+
+    #[test]
+    fn test_values() {
+        let file = EditorconfigFile::parse(
+            "root = true\n\
+             # comment\n\
+             [*]\n\
+             indent_style = space\n\
+             indent_size = 4\n\
+             end_of_line = LF\n\
+             charset = utf-8-bom\n\
+             trim_trailing_whitespace = true\n\
+             max_line_length = 120\n\
+             [Makefile]\n\
+             indent_style = tab\n\
+             indent_size = unset\n\
+             max_line_length = off\n",
+        );
+        assert!(file.root);
+
+        let mut values = HashMap::new();
+        file.apply("src/a.php", &mut values);
+        let php = EditorconfigProperties::from_values(&values);
+        assert_eq!(php.indentation(Indentation::tabs()), Indentation::spaces(4));
+        assert_eq!(php.tab_width, Some(4));
+        assert_eq!(php.end_of_line, Some(LineEnding::Lf));
+        assert_eq!(php.charset, Some((Encoding::Utf8, true)));
+        assert_eq!(php.trim_trailing_whitespace, Some(true));
+        assert_eq!(php.insert_final_newline, None);
+        assert_eq!(php.max_line_length, Some(120));
+
+        let mut values = HashMap::new();
+        file.apply("Makefile", &mut values);
+        let makefile = EditorconfigProperties::from_values(&values);
+        assert_eq!(makefile.indent_style, Some(IndentStyle::Tabs));
+        assert_eq!(makefile.indent_size, None);
+        assert_eq!(makefile.max_line_length, None);
+    }
+
+    #[test]
+    fn test_resolve_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("project/web");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(
+            dir.path().join(EDITORCONFIG_FILE),
+            "[*]\nmax_line_length = 80\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("project").join(EDITORCONFIG_FILE),
+            "[*]\nindent_style = space\nindent_size = 4\n[web/*.js]\nindent_size = 2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            sub.join(EDITORCONFIG_FILE),
+            "[*.js]\ninsert_final_newline = true\n",
+        )
+        .unwrap();
+
+        let js = EditorconfigProperties::resolve(&sub.join("app.js"));
+        assert_eq!(js.indent_size, Some(2));
+        assert_eq!(js.insert_final_newline, Some(true));
+        assert_eq!(js.max_line_length, Some(80));
+        assert_eq!(js.files[0], sub.join(EDITORCONFIG_FILE));
+
+        // `root = true` stops the search
+        std::fs::write(
+            dir.path().join("project").join(EDITORCONFIG_FILE),
+            "root = true\n[*]\nindent_style = tab\n",
+        )
+        .unwrap();
+        let css = EditorconfigProperties::resolve(&sub.join("app.css"));
+        assert_eq!(css.indentation(Indentation::default()), Indentation::tabs());
+        assert_eq!(css.max_line_length, None);
+        assert_eq!(css.files.len(), 2);
+    }
+}
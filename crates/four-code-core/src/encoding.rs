@@ -0,0 +1,328 @@
+//! Character encoding detection and conversion
+//!
+//! Files are decoded into the rope as UTF-8 and re-encoded on save. Besides
+//! UTF-8 and UTF-16 (with or without BOM), the legacy 8-bit encodings common
+//! in older PHP projects are supported: ISO-8859-1, ISO-8859-15 and
+//! Windows-1252.
+
+/// Windows-1252 characters for bytes 0x80..=0x9F
+///
+/// The five undefined bytes map to the C1 control characters of the same
+/// value (as in ISO-8859-1), so that any file round-trips.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Bytes where ISO-8859-15 differs from ISO-8859-1
+const ISO_8859_15_DIFF: [(u8, char); 8] = [
+    (0xA4, '€'),
+    (0xA6, 'Š'),
+    (0xA8, 'š'),
+    (0xB4, 'Ž'),
+    (0xB8, 'ž'),
+    (0xBC, 'Œ'),
+    (0xBD, 'œ'),
+    (0xBE, 'Ÿ'),
+];
+
+/// Supported file encodings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Latin-1
+    Iso8859_1,
+    /// Latin-9 (Latin-1 with €)
+    Iso8859_15,
+    Windows1252,
+}
+
+/// Result of decoding file content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    /// Decoded text
+    pub text: String,
+    /// Encoding used
+    pub encoding: Encoding,
+    /// Whether the content started with a byte order mark
+    pub bom: bool,
+}
+
+/// All supported encodings
+pub const ENCODINGS: &[Encoding] = &[
+    Encoding::Utf8,
+    Encoding::Utf16Le,
+    Encoding::Utf16Be,
+    Encoding::Iso8859_1,
+    Encoding::Iso8859_15,
+    Encoding::Windows1252,
+];
+
+impl Encoding {
+    /// Display name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16Be => "UTF-16 BE",
+            Encoding::Iso8859_1 => "ISO-8859-1",
+            Encoding::Iso8859_15 => "ISO-8859-15",
+            Encoding::Windows1252 => "Windows-1252",
+        }
+    }
+
+    /// Byte order mark of this encoding (empty for 8-bit encodings)
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            _ => &[],
+        }
+    }
+
+    /// Detect the encoding of file content
+    ///
+    /// Checks for a BOM first, then for BOM-less UTF-16 (ASCII text with
+    /// every other byte NUL), then for valid UTF-8 (a truncated character at
+    /// the end is allowed). Anything else is
+    /// treated as a legacy 8-bit encoding: Windows-1252 if it uses the
+    /// 0x80-0x9F range (typographic quotes, €), ISO-8859-1 otherwise.
+    pub fn detect(bytes: &[u8]) -> (Encoding, bool) {
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+            if bytes.starts_with(encoding.bom()) {
+                return (encoding, true);
+            }
+        }
+
+        if let Some(encoding) = detect_utf16(bytes) {
+            return (encoding, false);
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(_) => return (Encoding::Utf8, false),
+            // Only the last character is cut off (e.g. a sample of a file)
+            Err(e) if e.error_len().is_none() => return (Encoding::Utf8, false),
+            Err(_) => {}
+        }
+
+        if bytes.iter().any(|b| (0x80..=0x9F).contains(b)) {
+            (Encoding::Windows1252, false)
+        } else {
+            (Encoding::Iso8859_1, false)
+        }
+    }
+
+    /// Decode bytes (without BOM); invalid sequences become U+FFFD
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Encoding::Iso8859_1 => bytes.iter().map(|&b| char::from(b)).collect(),
+            Encoding::Iso8859_15 => bytes
+                .iter()
+                .map(|&b| {
+                    ISO_8859_15_DIFF
+                        .iter()
+                        .find(|(byte, _)| *byte == b)
+                        .map_or(char::from(b), |(_, ch)| *ch)
+                })
+                .collect(),
+            Encoding::Windows1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(b - 0x80)],
+                    _ => char::from(b),
+                })
+                .collect(),
+        }
+    }
+
+    /// Encode text (without BOM)
+    ///
+    /// Returns the first character that can't be represented on failure.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Encoding::Iso8859_1 => text
+                .chars()
+                .map(|ch| u8::try_from(ch).map_err(|_| ch))
+                .collect(),
+            Encoding::Iso8859_15 => text
+                .chars()
+                .map(|ch| {
+                    if let Some((byte, _)) = ISO_8859_15_DIFF.iter().find(|(_, c)| *c == ch) {
+                        return Ok(*byte);
+                    }
+                    match u8::try_from(ch) {
+                        // Replaced by other characters in Latin-9
+                        Ok(b) if ISO_8859_15_DIFF.iter().any(|(byte, _)| *byte == b) => Err(ch),
+                        Ok(b) => Ok(b),
+                        Err(_) => Err(ch),
+                    }
+                })
+                .collect(),
+            Encoding::Windows1252 => text
+                .chars()
+                .map(|ch| {
+                    if let Some(i) = WINDOWS_1252_HIGH.iter().position(|&c| c == ch) {
+                        return Ok(0x80 + i as u8);
+                    }
+                    match u8::try_from(ch) {
+                        Ok(b) if !(0x80..=0x9F).contains(&b) => Ok(b),
+                        _ => Err(ch),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Detect and decode file content
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let (encoding, _) = Encoding::detect(bytes);
+    decode_with(bytes, encoding)
+}
+
+/// Decode file content with a given encoding (a matching BOM is stripped)
+pub fn decode_with(bytes: &[u8], encoding: Encoding) -> Decoded {
+    let bom = !encoding.bom().is_empty() && bytes.starts_with(encoding.bom());
+    let content = if bom {
+        &bytes[encoding.bom().len()..]
+    } else {
+        bytes
+    };
+    Decoded {
+        text: encoding.decode(content),
+        encoding,
+        bom,
+    }
+}
+
+/// Detect BOM-less UTF-16 from the distribution of NUL bytes
+fn detect_utf16(bytes: &[u8]) -> Option<Encoding> {
+    if bytes.len() < 4 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let sample = &bytes[..bytes.len().min(4096)];
+    let pairs = sample.len() / 2;
+    let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nuls = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+
+    // Mostly-ASCII text: the high byte of nearly every code unit is NUL
+    if odd_nuls * 10 >= pairs * 7 && even_nuls * 20 < pairs {
+        Some(Encoding::Utf16Le)
+    } else if even_nuls * 10 >= pairs * 7 && odd_nuls * 20 < pairs {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Decode UTF-16 code units
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [a, b] => to_unit([*a, *b]),
+        // Odd trailing byte
+        _ => 0xFFFD,
+    });
+    char::decode_utf16(units)
+        .map(|r| r.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_bom() {
+        assert_eq!(
+            Encoding::detect(b"\xEF\xBB\xBFhello"),
+            (Encoding::Utf8, true)
+        );
+        assert_eq!(
+            Encoding::detect(b"\xFF\xFEh\0i\0"),
+            (Encoding::Utf16Le, true)
+        );
+        assert_eq!(
+            Encoding::detect(b"\xFE\xFF\0h\0i"),
+            (Encoding::Utf16Be, true)
+        );
+    }
+
+    #[test]
+    fn test_detect_without_bom() {
+        assert_eq!(
+            Encoding::detect("Grüße".as_bytes()),
+            (Encoding::Utf8, false)
+        );
+        assert_eq!(
+            Encoding::detect(b"<\0?\0p\0h\0p\0"),
+            (Encoding::Utf16Le, false)
+        );
+        // "Grüße" in Latin-1
+        assert_eq!(
+            Encoding::detect(b"Gr\xFC\xDFe"),
+            (Encoding::Iso8859_1, false)
+        );
+        // Typographic quotes only exist in Windows-1252
+        assert_eq!(
+            Encoding::detect(b"\x84Hallo\x93"),
+            (Encoding::Windows1252, false)
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let text = "Grüße € 'quoted'";
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Utf16Le,
+            Encoding::Utf16Be,
+            Encoding::Iso8859_15,
+            Encoding::Windows1252,
+        ] {
+            let bytes = encoding.encode(text).unwrap();
+            assert_eq!(encoding.decode(&bytes), text, "{}", encoding.name());
+        }
+    }
+
+    #[test]
+    fn test_decode_legacy() {
+        assert_eq!(Encoding::Iso8859_1.decode(b"Gr\xFC\xDFe"), "Grüße");
+        assert_eq!(Encoding::Iso8859_15.decode(b"\xA4"), "€");
+        assert_eq!(Encoding::Windows1252.decode(b"\x80\x84\x93"), "€„“");
+
+        // Undefined bytes round-trip as C1 control characters
+        let undefined = b"\x81\x8D\x8F\x90\x9D";
+        let text = Encoding::Windows1252.decode(undefined);
+        assert_eq!(text, "\u{81}\u{8D}\u{8F}\u{90}\u{9D}");
+        assert_eq!(Encoding::Windows1252.encode(&text).unwrap(), undefined);
+        assert_eq!(Encoding::Windows1252.encode("\u{80}"), Err('\u{80}'));
+    }
+
+    #[test]
+    fn test_unmappable_character() {
+        assert_eq!(Encoding::Iso8859_1.encode("10 €"), Err('€'));
+        assert_eq!(Encoding::Windows1252.encode("中"), Err('中'));
+    }
+
+    #[test]
+    fn test_decode_strips_bom() {
+        let decoded = decode(b"\xEF\xBB\xBF<?php");
+        assert_eq!(decoded.text, "<?php");
+        assert!(decoded.bom);
+    }
+}
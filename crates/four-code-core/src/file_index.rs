@@ -0,0 +1,318 @@
+//! Index of the project files (Ctrl+P)
+//!
+//! The project tree is walked once on a background thread with the `ignore`
+//! crate (skipping `.gitignore`d and hidden files, like the project search)
+//! and then kept fresh from file system events: created, removed and
+//! renamed files are added and removed as they come in, without walking the
+//! tree again. Only events in directories the walk went into count, so
+//! builds writing to ignored directories (`var/cache/`, `node_modules/`)
+//! cost next to nothing.
+//!
+//! The file list is shared as a snapshot (`Arc`): readers never wait for
+//! the walk, and an update only copies the list while a snapshot is held.
+
+use ignore::WalkBuilder;
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Events for a path within this time are handled once
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How often the index thread checks if the index was dropped
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Files found by the walk are published in batches of this size
+const BATCH_SIZE: usize = 2000;
+
+/// The files of a project, kept up to date in the background
+///
+/// Dropping the index stops the walk and the watching.
+#[derive(Debug)]
+pub struct FileIndex {
+    root: PathBuf,
+    shared: Arc<Shared>,
+}
+
+/// State shared with the index thread
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<IndexState>,
+    /// The initial walk is done
+    ready: AtomicBool,
+    /// The index was dropped
+    stopped: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct IndexState {
+    /// Paths relative to the root with `/` separators (sorted once the
+    /// walk is done)
+    files: Arc<Vec<String>>,
+    /// Directories the walk went into (events elsewhere are ignored)
+    dirs: HashSet<PathBuf>,
+    /// Incremented on every change of `files`
+    revision: u64,
+}
+
+impl FileIndex {
+    /// Start indexing the files under `root`
+    pub fn new(root: &Path) -> Self {
+        let index = Self {
+            root: root.to_path_buf(),
+            shared: Arc::default(),
+        };
+        let root = index.root.clone();
+        let shared = Arc::clone(&index.shared);
+        std::thread::spawn(move || run(&root, &shared));
+        index
+    }
+
+    /// The indexed directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The indexed files (relative paths with `/` separators) and the
+    /// revision of the list, which changes whenever files are added or
+    /// removed
+    pub fn snapshot(&self) -> (u64, Arc<Vec<String>>) {
+        let state = lock(&self.shared.state);
+        (state.revision, Arc::clone(&state.files))
+    }
+
+    /// Check if the initial walk is done
+    pub fn is_ready(&self) -> bool {
+        self.shared.ready.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for FileIndex {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Index thread: watch the tree, walk it, then apply the events
+fn run(root: &Path, shared: &Shared) {
+    // Watch first, so nothing changed during the walk is missed; without a
+    // watcher (e.g. out of inotify watches) the index just isn't updated
+    let (sender, events) = mpsc::channel::<DebounceEventResult>();
+    let _debouncer = new_debouncer(DEBOUNCE_TIMEOUT, sender)
+        .ok()
+        .and_then(|mut debouncer| {
+            let watcher = debouncer.watcher();
+            watcher.watch(root, RecursiveMode::Recursive).ok()?;
+            Some(debouncer)
+        });
+
+    let mut batch = Vec::new();
+    walk(root, None, &shared.stopped, |path, is_dir| {
+        if is_dir {
+            lock(&shared.state).dirs.insert(path);
+            return;
+        }
+        batch.extend(relative(root, &path));
+        if batch.len() >= BATCH_SIZE {
+            publish(shared, |files| files.append(&mut batch));
+        }
+    });
+    publish(shared, |files| {
+        files.append(&mut batch);
+        files.sort_unstable();
+    });
+    shared.ready.store(true, Ordering::Release);
+
+    while !shared.stopped.load(Ordering::Relaxed) {
+        match events.recv_timeout(STOP_CHECK_INTERVAL) {
+            Ok(Ok(events)) => {
+                let paths: HashSet<PathBuf> = events.into_iter().map(|event| event.path).collect();
+                apply_changes(root, shared, paths);
+            }
+            // Watcher errors (e.g. a removed directory) are not fatal
+            Ok(Err(_)) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// Apply file system events: forget the changed paths, then add back the
+/// ones that exist and aren't ignored
+fn apply_changes(root: &Path, shared: &Shared, paths: HashSet<PathBuf>) {
+    // Only paths in directories the walk went into (so not ignored)
+    let mut by_dir: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    {
+        let state = lock(&shared.state);
+        for path in paths {
+            if let Some(dir) = path.parent().filter(|dir| state.dirs.contains(*dir)) {
+                by_dir.entry(dir.to_path_buf()).or_default().push(path);
+            }
+        }
+    }
+    if by_dir.is_empty() {
+        return;
+    }
+
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut added_dirs = Vec::new();
+    for (dir, paths) in by_dir {
+        // The directory's entries that aren't ignored (the ignore files of
+        // the parent directories apply too)
+        let mut listed = HashMap::new();
+        walk(&dir, Some(1), &shared.stopped, |path, is_dir| {
+            listed.insert(path, is_dir);
+        });
+        for path in paths {
+            match listed.get(&path) {
+                Some(false) => added.extend(relative(root, &path)),
+                Some(true) => walk(&path, None, &shared.stopped, |path, is_dir| {
+                    if is_dir {
+                        added_dirs.push(path);
+                    } else {
+                        added.extend(relative(root, &path));
+                    }
+                }),
+                // Removed, renamed away or ignored now
+                None => removed.push(path),
+            }
+        }
+    }
+
+    let mut state = lock(&shared.state);
+    for path in &removed {
+        state.dirs.retain(|dir| !dir.starts_with(path));
+    }
+    state.dirs.extend(added_dirs);
+
+    // A file is removed with its path or one of its directories
+    let removed: HashSet<String> = removed
+        .iter()
+        .filter_map(|path| relative(root, path))
+        .collect();
+    let is_removed = |file: &str| {
+        removed.contains(file)
+            || file
+                .match_indices('/')
+                .any(|(i, _)| removed.contains(&file[..i]))
+    };
+    // Most events are for files that were changed, not added or removed
+    let files = &state.files;
+    let changed = (!removed.is_empty() && files.iter().any(|file| is_removed(file)))
+        || added.iter().any(|file| files.binary_search(file).is_err());
+    if !changed {
+        return;
+    }
+    let files = Arc::make_mut(&mut state.files);
+    files.retain(|file| !is_removed(file));
+    for file in added {
+        if let Err(index) = files.binary_search(&file) {
+            files.insert(index, file);
+        }
+    }
+    state.revision += 1;
+}
+
+/// Walk a directory (respecting ignore files, also those of its parent
+/// directories), calling `found` with every file and directory below it
+/// and whether it is a directory
+fn walk(
+    dir: &Path,
+    max_depth: Option<usize>,
+    stopped: &AtomicBool,
+    mut found: impl FnMut(PathBuf, bool),
+) {
+    let walker = WalkBuilder::new(dir)
+        .require_git(false)
+        .max_depth(max_depth)
+        .build();
+    for entry in walker.flatten() {
+        if stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(file_type) = entry.file_type() else {
+            continue;
+        };
+        let is_dir = file_type.is_dir();
+        if is_dir && entry.depth() == 0 && max_depth.is_some() {
+            continue;
+        }
+        if is_dir || file_type.is_file() {
+            found(entry.into_path(), is_dir);
+        }
+    }
+}
+
+/// Change the file list (as a new revision)
+fn publish(shared: &Shared, change: impl FnOnce(&mut Vec<String>)) {
+    let mut state = lock(&shared.state);
+    change(Arc::make_mut(&mut state.files));
+    state.revision += 1;
+}
+
+/// Path relative to the root with `/` separators (None for paths outside
+/// the root or that aren't valid UTF-8)
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?.to_str()?;
+    Some(relative.replace('\\', "/"))
+}
+
+/// Lock the state (a panic on the index thread doesn't poison it for the
+/// editor)
+fn lock(state: &Mutex<IndexState>) -> std::sync::MutexGuard<'_, IndexState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Instant;
+
+    /// Wait until the index has the expected files (or time out)
+    fn wait_for(index: &FileIndex, expected: &[&str]) -> Vec<String> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let files = index.snapshot().1.to_vec();
+            if (index.is_ready() && files == expected) || Instant::now() > deadline {
+                return files;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_index_and_updates() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("src")).unwrap();
+        fs::create_dir_all(root.path().join("var/cache")).unwrap();
+        fs::write(root.path().join(".gitignore"), "var/\n").unwrap();
+        fs::write(root.path().join("src/User.php"), "").unwrap();
+        fs::write(root.path().join("var/cache/x.php"), "").unwrap();
+        fs::write(root.path().join("index.php"), "").unwrap();
+
+        let index = FileIndex::new(root.path());
+        assert_eq!(
+            wait_for(&index, &["index.php", "src/User.php"]),
+            ["index.php", "src/User.php"]
+        );
+
+        // Created and removed files and directories; ignored ones stay out
+        fs::write(root.path().join("src/Post.php"), "").unwrap();
+        fs::write(root.path().join("var/cache/y.php"), "").unwrap();
+        fs::write(root.path().join(".gitignore"), "var/\n*.log\n").unwrap();
+        fs::write(root.path().join("debug.log"), "").unwrap();
+        fs::remove_file(root.path().join("index.php")).unwrap();
+        fs::create_dir_all(root.path().join("tests/Unit")).unwrap();
+        fs::write(root.path().join("tests/Unit/UserTest.php"), "").unwrap();
+        let expected = ["src/Post.php", "src/User.php", "tests/Unit/UserTest.php"];
+        assert_eq!(wait_for(&index, &expected), expected);
+
+        drop(index);
+    }
+}
@@ -0,0 +1,261 @@
+//! Fuzzy matching of file paths (Ctrl+P)
+//!
+//! The query characters have to appear in the path in order, ignoring case.
+//! Of all the ways they can be matched, the best scoring one is picked:
+//! matches at the start of a path segment, a word (`_`, `-`, `.`) or a
+//! camelCase hump score high, runs of consecutive matches score higher,
+//! gaps cost a little. So `uc` ranks `src/UserController.php` well above
+//! `lib/ducks.php`.
+
+/// Match at the start of the path or of a path segment (after `/`)
+const BONUS_SEGMENT: i32 = 10;
+
+/// Match at the start of a word (after `_`, `-`, `.` or a space)
+const BONUS_WORD: i32 = 8;
+
+/// Match at a camelCase hump (`Controller` in `UserController`)
+const BONUS_CAMEL: i32 = 8;
+
+/// Match right after the previous match
+const BONUS_CONSECUTIVE: i32 = 6;
+
+/// Match in the file name (the last path segment)
+const BONUS_FILE_NAME: i32 = 2;
+
+/// Match with the exact case of the query
+const BONUS_CASE: i32 = 1;
+
+/// Score of a matched char
+const SCORE_MATCH: i32 = 16;
+
+/// Cost per skipped char between two matches
+const PENALTY_GAP: i32 = 1;
+
+/// Score of an impossible match
+const NONE: i32 = i32::MIN;
+
+/// A compiled fuzzy query
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuzzyQuery {
+    /// Query chars (whitespace removed)
+    chars: Vec<char>,
+    /// Lowercase query chars
+    lower: Vec<char>,
+}
+
+/// A path matching a fuzzy query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Higher is better
+    pub score: i32,
+    /// Char indexes of the matched chars in the path
+    pub positions: Vec<usize>,
+}
+
+impl FuzzyQuery {
+    /// Compile a query (whitespace is ignored)
+    pub fn new(query: &str) -> Self {
+        let chars: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+        let lower = chars.iter().map(|&c| lower(c)).collect();
+        Self { chars, lower }
+    }
+
+    /// Check if the query is empty (every path matches)
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Check if the query chars appear in the path in order (fast check
+    /// before scoring)
+    pub fn is_match(&self, path: &str) -> bool {
+        let mut query = self.lower.iter().peekable();
+        for c in path.chars() {
+            match query.peek() {
+                Some(&&q) if lower(c) == q => {
+                    query.next();
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        query.peek().is_none()
+    }
+
+    /// Score a path; None if it doesn't match
+    pub fn score(&self, path: &str) -> Option<FuzzyMatch> {
+        if self.chars.is_empty() {
+            return Some(FuzzyMatch {
+                score: 0,
+                positions: Vec::new(),
+            });
+        }
+        let text: Vec<char> = path.chars().collect();
+        let (n, m) = (text.len(), self.chars.len());
+
+        // Earliest and latest path char each query char can be matched at
+        // (matching greedily from the start and from the end); only the
+        // chars in between have to be scored
+        let mut first = Vec::with_capacity(m);
+        let mut i = 0;
+        for &q in &self.lower {
+            i += text[i..].iter().position(|&c| lower(c) == q)?;
+            first.push(i);
+            i += 1;
+        }
+        let mut last = vec![0; m];
+        let mut end = n;
+        for q in (0..m).rev() {
+            end = text[..end]
+                .iter()
+                .rposition(|&c| lower(c) == self.lower[q])?;
+            last[q] = end;
+        }
+
+        let file_name = text.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+        let own = |q: usize, i: usize| {
+            SCORE_MATCH
+                + boundary_bonus(&text, i)
+                + i32::from(i >= file_name) * BONUS_FILE_NAME
+                + i32::from(text[i] == self.chars[q]) * BONUS_CASE
+        };
+
+        // scores[q * n + i]: best score with query char q matched at path
+        // char i (NONE: impossible); from[q * n + i]: where query char q - 1
+        // was matched
+        let mut scores = vec![NONE; m * n];
+        let mut from = vec![0; m * n];
+        for i in first[0]..=last[0] {
+            if lower(text[i]) == self.lower[0] {
+                scores[i] = own(0, i);
+            }
+        }
+        for q in 1..m {
+            let (previous, row) = scores[(q - 1) * n..(q + 1) * n].split_at_mut(n);
+            // Best previous score plus the gap cost to here, as
+            // `score + PENALTY_GAP * index` (so it can be kept as a running
+            // maximum) with its index
+            let mut best: Option<(i32, usize)> = None;
+            for i in first[q - 1] + 1..=last[q] {
+                if previous[i - 1] != NONE {
+                    let value = previous[i - 1] + PENALTY_GAP * (i as i32 - 1);
+                    if best.is_none_or(|(best, _)| value > best) {
+                        best = Some((value, i - 1));
+                    }
+                }
+                if i < first[q] || lower(text[i]) != self.lower[q] {
+                    continue;
+                }
+                let Some((value, index)) = best else {
+                    continue;
+                };
+                let own = own(q, i);
+                // Skipping `i - index - 1` chars
+                let mut score = value - PENALTY_GAP * (i as i32 - 1) + own;
+                let mut before = index;
+                if previous[i - 1] != NONE {
+                    let consecutive = previous[i - 1] + own + BONUS_CONSECUTIVE;
+                    if consecutive >= score {
+                        score = consecutive;
+                        before = i - 1;
+                    }
+                }
+                row[i] = score;
+                from[q * n + i] = before;
+            }
+        }
+
+        let row = &scores[(m - 1) * n..];
+        let (mut index, score) = (first[m - 1]..=last[m - 1])
+            .filter(|&i| row[i] != NONE)
+            .map(|i| (i, row[i]))
+            .max_by_key(|&(i, score)| (score, std::cmp::Reverse(i)))?;
+        let mut positions = vec![0; m];
+        for q in (0..m).rev() {
+            positions[q] = index;
+            index = from[q * n + index];
+        }
+        Some(FuzzyMatch { score, positions })
+    }
+}
+
+/// Bonus for a match at a path char, by what comes before it
+fn boundary_bonus(text: &[char], i: usize) -> i32 {
+    let Some(&before) = i.checked_sub(1).and_then(|j| text.get(j)) else {
+        return BONUS_SEGMENT;
+    };
+    let c = text[i];
+    match before {
+        '/' | '\\' => BONUS_SEGMENT,
+        '_' | '-' | '.' | ' ' => BONUS_WORD,
+        _ if before.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+        _ if !before.is_numeric() && c.is_numeric() => BONUS_WORD,
+        _ => 0,
+    }
+}
+
+/// Lowercase of a char (the first char for chars lowercasing to several)
+fn lower(c: char) -> char {
+    if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(query: &str, path: &str) -> Option<Vec<usize>> {
+        FuzzyQuery::new(query).score(path).map(|m| m.positions)
+    }
+
+    /// Paths matching the query, best first
+    fn rank<'a>(query: &str, paths: &[&'a str]) -> Vec<&'a str> {
+        let query = FuzzyQuery::new(query);
+        let mut scored: Vec<_> = paths
+            .iter()
+            .filter_map(|&path| query.score(path).map(|m| (m.score, path)))
+            .collect();
+        scored.sort_by_key(|&(score, path)| (std::cmp::Reverse(score), path.len()));
+        scored.into_iter().map(|(_, path)| path).collect()
+    }
+
+    #[test]
+    fn test_match_positions() {
+        assert_eq!(positions("abc", "a/b/c"), Some(vec![0, 2, 4]));
+        assert_eq!(positions("ABC", "xabc"), Some(vec![1, 2, 3]));
+        assert_eq!(positions("acb", "abc"), None);
+        assert_eq!(positions("", "abc"), Some(vec![]));
+        // Whitespace in the query is ignored
+        assert_eq!(positions("u c", "UserController"), Some(vec![0, 4]));
+
+        // The segment start wins over the first occurrence
+        assert_eq!(positions("c", "src/cache.php"), Some(vec![4]));
+        // The camelCase hump wins over the first occurrence
+        assert_eq!(positions("uc", "UserController"), Some(vec![0, 4]));
+        // A consecutive run wins over scattered boundary matches
+        assert_eq!(
+            positions("user", "src/UserService.php"),
+            Some(vec![4, 5, 6, 7])
+        );
+    }
+
+    #[test]
+    fn test_ranking() {
+        let paths = [
+            "lib/ducks.php",
+            "src/Controller/UserController.php",
+            "src/Entity/User.php",
+            "tests/Unit/ControllerTest.php",
+        ];
+        assert_eq!(rank("uc", &paths)[0], "src/Controller/UserController.php");
+        assert_eq!(rank("uc", &paths).last(), Some(&"lib/ducks.php"));
+        assert_eq!(
+            rank("usercont", &paths),
+            ["src/Controller/UserController.php"]
+        );
+        // Path segments: `e/u` finds the file by its directory
+        assert_eq!(rank("e/u", &paths)[0], "src/Entity/User.php");
+    }
+}
@@ -0,0 +1,146 @@
+//! Grapheme clusters and display width
+//!
+//! Cursor columns count `char`s, but what the user sees as one character can
+//! be several `char`s (`e` + combining accent, emoji ZWJ sequences, flags),
+//! and some characters take two terminal cells (CJK, most emoji). Motions and
+//! deletions step over whole grapheme clusters, and rendering and the
+//! on-screen cursor use display width.
+
+use ropey::RopeSlice;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Default number of cells between tab stops
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Number of chars looked at around a column to find cluster boundaries
+///
+/// Grapheme clusters longer than this (e.g. absurd stacks of combining marks)
+/// are split, which keeps boundary lookups O(1) on lines of several MB.
+const BOUNDARY_WINDOW: usize = 32;
+
+/// Display width of a grapheme cluster at display column `x`
+///
+/// Tabs extend to the next tab stop; other control characters take one cell.
+pub fn grapheme_width(grapheme: &str, x: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        let tab_width = tab_width.max(1);
+        return tab_width - x % tab_width;
+    }
+    grapheme.width().max(1)
+}
+
+/// Line content without the trailing line break
+fn content(line: RopeSlice<'_>) -> RopeSlice<'_> {
+    let mut len = line.len_chars();
+    while len > 0 && matches!(line.char(len - 1), '\n' | '\r') {
+        len -= 1;
+    }
+    line.slice(..len)
+}
+
+/// Column of the grapheme cluster boundary before `column`
+pub fn prev_boundary(line: RopeSlice<'_>, column: usize) -> usize {
+    let line = content(line);
+    let column = column.min(line.len_chars());
+    let start = column.saturating_sub(BOUNDARY_WINDOW);
+    let window = line.slice(start..column).to_string();
+    match window.grapheme_indices(true).next_back() {
+        Some((_, grapheme)) => column - grapheme.chars().count(),
+        None => column,
+    }
+}
+
+/// Column of the grapheme cluster boundary after `column`
+pub fn next_boundary(line: RopeSlice<'_>, column: usize) -> usize {
+    let line = content(line);
+    let column = column.min(line.len_chars());
+    let end = (column + BOUNDARY_WINDOW).min(line.len_chars());
+    let window = line.slice(column..end).to_string();
+    match window.graphemes(true).next() {
+        Some(grapheme) => column + grapheme.chars().count(),
+        None => column,
+    }
+}
+
+/// Move a column back to the start of the grapheme cluster containing it
+pub fn floor_boundary(line: RopeSlice<'_>, column: usize) -> usize {
+    let column = column.min(content(line).len_chars());
+    let prev = prev_boundary(line, column);
+    if next_boundary(line, prev) > column {
+        prev
+    } else {
+        column
+    }
+}
+
+/// Display column (in cells) of a char column
+pub fn display_column(line: RopeSlice<'_>, column: usize, tab_width: usize) -> usize {
+    let line = content(line);
+    let prefix = line.slice(..column.min(line.len_chars())).to_string();
+    prefix
+        .graphemes(true)
+        .fold(0, |x, grapheme| x + grapheme_width(grapheme, x, tab_width))
+}
+
+/// Char column of the grapheme cluster covering a display column
+///
+/// Display columns past the end of the line map to the line length.
+pub fn column_at_display(line: RopeSlice<'_>, display: usize, tab_width: usize) -> usize {
+    let text = content(line).to_string();
+    let mut x = 0;
+    let mut column = 0;
+    for grapheme in text.graphemes(true) {
+        let width = grapheme_width(grapheme, x, tab_width);
+        if x + width > display {
+            break;
+        }
+        x += width;
+        column += grapheme.chars().count();
+    }
+    column
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    #[test]
+    fn test_boundaries_skip_clusters() {
+        // "e" + combining acute, family emoji (ZWJ sequence), CJK
+        let rope = Rope::from_str("e\u{301}👨\u{200d}👩\u{200d}👧中\n");
+        let line = rope.line(0);
+
+        assert_eq!(next_boundary(line, 0), 2);
+        assert_eq!(next_boundary(line, 2), 7);
+        assert_eq!(next_boundary(line, 7), 8);
+        assert_eq!(next_boundary(line, 8), 8);
+
+        assert_eq!(prev_boundary(line, 8), 7);
+        assert_eq!(prev_boundary(line, 7), 2);
+        assert_eq!(prev_boundary(line, 2), 0);
+        assert_eq!(prev_boundary(line, 0), 0);
+
+        assert_eq!(floor_boundary(line, 1), 0);
+        assert_eq!(floor_boundary(line, 4), 2);
+        assert_eq!(floor_boundary(line, 7), 7);
+    }
+
+    #[test]
+    fn test_display_width() {
+        let rope = Rope::from_str("a中e\u{301}\tb\n");
+        let line = rope.line(0);
+
+        assert_eq!(display_column(line, 1, 4), 1);
+        assert_eq!(display_column(line, 2, 4), 3);
+        assert_eq!(display_column(line, 4, 4), 4);
+        // Tab to the next stop
+        assert_eq!(display_column(line, 5, 4), 8);
+
+        assert_eq!(column_at_display(line, 2, 4), 1);
+        assert_eq!(column_at_display(line, 3, 4), 2);
+        assert_eq!(column_at_display(line, 6, 4), 4);
+        assert_eq!(column_at_display(line, 100, 4), 6);
+    }
+}
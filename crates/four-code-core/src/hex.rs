@@ -0,0 +1,206 @@
+//! Binary file detection and read-only hex view
+//!
+//! Binary files (images, `.phar` archives, ...) are not decoded into a rope.
+//! They open as a hex view with offset, hex and ASCII columns, navigated by
+//! byte. Rows scroll with the editor's [`crate::Viewport`].
+
+use std::path::PathBuf;
+
+/// Bytes shown per row
+pub const BYTES_PER_ROW: usize = 16;
+
+/// Number of bytes inspected for binary detection
+const SAMPLE_SIZE: usize = 8192;
+
+/// Check if file content looks binary
+///
+/// Text with a BOM or in BOM-less UTF-16 is never binary. Otherwise a NUL
+/// byte, or - for content that is not valid UTF-8 - a high density of
+/// control characters in the first few KB marks the file as binary. Legacy
+/// 8-bit text (ISO-8859-1, ...) has invalid UTF-8 but few control characters.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let (encoding, bom) = crate::Encoding::detect(bytes);
+    if bom
+        || matches!(
+            encoding,
+            crate::Encoding::Utf16Le | crate::Encoding::Utf16Be
+        )
+    {
+        return false;
+    }
+
+    let sample = &bytes[..bytes.len().min(SAMPLE_SIZE)];
+    if sample.contains(&0) {
+        return true;
+    }
+    if encoding == crate::Encoding::Utf8 {
+        return false;
+    }
+
+    let control = sample
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B)) || b == 0x7F)
+        .count();
+    control * 10 > sample.len()
+}
+
+/// Read-only hex view of a binary file
+#[derive(Debug, Clone)]
+pub struct HexView {
+    /// File content
+    bytes: Vec<u8>,
+    /// Path of the file
+    path: PathBuf,
+    /// Cursor (byte offset)
+    cursor: usize,
+}
+
+impl HexView {
+    /// Create a hex view of some bytes
+    pub fn new(path: impl Into<PathBuf>, bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            path: path.into(),
+            cursor: 0,
+        }
+    }
+
+    /// Open a file as hex view
+    pub fn open(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let bytes = std::fs::read(&path)?;
+        Ok(Self::new(path, bytes))
+    }
+
+    /// Get the file path
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Get the file content
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Number of rows
+    pub fn len_rows(&self) -> usize {
+        self.bytes.len().div_ceil(BYTES_PER_ROW).max(1)
+    }
+
+    /// Bytes of a row (empty past the end)
+    pub fn row(&self, row: usize) -> &[u8] {
+        let start = (row * BYTES_PER_ROW).min(self.bytes.len());
+        let end = (start + BYTES_PER_ROW).min(self.bytes.len());
+        &self.bytes[start..end]
+    }
+
+    /// Cursor byte offset
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Row of the cursor
+    pub fn cursor_row(&self) -> usize {
+        self.cursor / BYTES_PER_ROW
+    }
+
+    /// Column (byte within row) of the cursor
+    pub fn cursor_column(&self) -> usize {
+        self.cursor % BYTES_PER_ROW
+    }
+
+    /// Last valid cursor offset
+    fn last_offset(&self) -> usize {
+        self.bytes.len().saturating_sub(1)
+    }
+
+    /// Move to a byte offset (clamped)
+    pub fn move_to(&mut self, offset: usize) {
+        self.cursor = offset.min(self.last_offset());
+    }
+
+    /// Move one byte left
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    /// Move one byte right
+    pub fn move_right(&mut self) {
+        self.move_to(self.cursor + 1);
+    }
+
+    /// Move up by n rows
+    pub fn move_up(&mut self, n: usize) {
+        if self.cursor_row() > 0 {
+            self.cursor = self.cursor.saturating_sub(n * BYTES_PER_ROW);
+        }
+    }
+
+    /// Move down by n rows (stays in the last row)
+    pub fn move_down(&mut self, n: usize) {
+        if self.cursor_row() + 1 < self.len_rows() {
+            self.move_to(self.cursor + n * BYTES_PER_ROW);
+        }
+    }
+
+    /// Move to the first byte of the row
+    pub fn move_to_row_start(&mut self) {
+        self.cursor -= self.cursor_column();
+    }
+
+    /// Move to the last byte of the row
+    pub fn move_to_row_end(&mut self) {
+        self.move_to(self.cursor - self.cursor_column() + BYTES_PER_ROW - 1);
+    }
+
+    /// Move to the first byte
+    pub fn move_to_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// Move to the last byte
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.last_offset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(is_binary(&[0x01, 0x02, 0x03, 0x04, 0xFF, 0xFE, 0x05, 0x06]));
+        assert!(!is_binary(b"<?php echo 'hello';\n"));
+        // Latin-1 text
+        assert!(!is_binary(b"// Gr\xFC\xDFe\n"));
+        // UTF-16 with and without BOM
+        assert!(!is_binary(b"\xFF\xFEh\0i\0"));
+        assert!(!is_binary(b"<\0?\0p\0h\0p\0"));
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut view = HexView::new("data.bin", (0..40).collect());
+        assert_eq!(view.len_rows(), 3);
+
+        view.move_down(1);
+        assert_eq!(view.cursor(), 16);
+        view.move_to_row_end();
+        assert_eq!(view.cursor(), 31);
+        view.move_right();
+        assert_eq!((view.cursor_row(), view.cursor_column()), (2, 0));
+
+        // Last row is short: clamp to the last byte
+        view.move_to_row_end();
+        assert_eq!(view.cursor(), 39);
+        view.move_down(1);
+        assert_eq!(view.cursor(), 39);
+
+        view.move_up(5);
+        assert_eq!(view.cursor(), 0);
+        view.move_left();
+        assert_eq!(view.cursor(), 0);
+    }
+}
@@ -0,0 +1,516 @@
+//! Undo/redo history
+//!
+//! Every buffer modification is recorded as an invertible [`Edit`]. Edits are
+//! grouped into [`Transaction`]s so that one logical change (a typed word, a
+//! paste, deleting a selection) is undone and redone as a single step.
+
+use crate::Cursor;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of transactions kept on the undo stack
+const MAX_TRANSACTIONS: usize = 1000;
+
+/// A single invertible text modification (character indices)
+///
+/// `len` is the length of `text` in chars, kept so that merging and applying
+/// edits doesn't have to count them again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Edit {
+    /// `text` was inserted at char index `at`
+    Insert { at: usize, text: String, len: usize },
+    /// `text` was removed starting at char index `at`
+    Remove { at: usize, text: String, len: usize },
+}
+
+impl Edit {
+    /// Insertion of `text` at char index `at`
+    pub fn insert(at: usize, text: impl Into<String>) -> Edit {
+        let text = text.into();
+        let len = text.chars().count();
+        Edit::Insert { at, text, len }
+    }
+
+    /// Removal of `text` starting at char index `at`
+    pub fn remove(at: usize, text: impl Into<String>) -> Edit {
+        let text = text.into();
+        let len = text.chars().count();
+        Edit::Remove { at, text, len }
+    }
+
+    /// Get the edit that reverts this one
+    pub fn inverted(&self) -> Edit {
+        match self {
+            Edit::Insert { at, text, len } => Edit::Remove {
+                at: *at,
+                text: text.clone(),
+                len: *len,
+            },
+            Edit::Remove { at, text, len } => Edit::Insert {
+                at: *at,
+                text: text.clone(),
+                len: *len,
+            },
+        }
+    }
+
+    /// Try to merge a following edit into this one
+    ///
+    /// Consecutive typing and consecutive Delete presses produce adjacent
+    /// edits that are stored as one to keep the history compact. Backspace
+    /// runs stay separate edits: merging them would mean prepending to an
+    /// ever longer text.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (
+                Edit::Insert { at, text, len },
+                Edit::Insert {
+                    at: next_at,
+                    text: next_text,
+                    len: next_len,
+                },
+            ) if *next_at == *at + *len => {
+                text.push_str(next_text);
+                *len += next_len;
+                true
+            }
+            // Delete key: same index, text removed further right
+            (
+                Edit::Remove { at, text, len },
+                Edit::Remove {
+                    at: next_at,
+                    text: next_text,
+                    len: next_len,
+                },
+            ) if *next_at == *at => {
+                text.push_str(next_text);
+                *len += next_len;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What kind of change a transaction represents
+///
+/// Consecutive transactions of the same kind may be coalesced into one undo
+/// step (e.g. typing a word character by character).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EditKind {
+    /// Typing characters
+    Typing,
+    /// Backspace/Delete presses
+    Deleting,
+    /// Anything else (paste, cut, delete selection, ...). Never coalesced.
+    Other,
+}
+
+/// A group of edits undone/redone as one step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    /// Unique id (used to track the save point)
+    id: u64,
+    /// Edits in the order they were applied
+    pub edits: Vec<Edit>,
+    /// Kind of change
+    pub kind: EditKind,
+    /// Cursor (with selection) before the transaction
+    pub cursor_before: Option<Cursor>,
+    /// Cursor (with selection) after the transaction
+    pub cursor_after: Option<Cursor>,
+    /// Additional cursors (multi-cursor editing) before the transaction
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_cursors_before: Vec<Cursor>,
+    /// Additional cursors (multi-cursor editing) after the transaction
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_cursors_after: Vec<Cursor>,
+}
+
+impl Transaction {
+    /// Check if the text of this transaction ends in whitespace
+    fn ends_with_whitespace(&self) -> bool {
+        match self.edits.last() {
+            Some(Edit::Insert { text, .. }) => text.chars().last().is_some_and(char::is_whitespace),
+            _ => false,
+        }
+    }
+
+    /// Check if the text of this transaction starts with whitespace
+    fn starts_with_whitespace(&self) -> bool {
+        match self.edits.first() {
+            Some(Edit::Insert { text, .. }) => text.chars().next().is_some_and(char::is_whitespace),
+            _ => false,
+        }
+    }
+}
+
+/// Undo/redo stacks with transaction grouping
+///
+/// Serializable so it can be persisted across sessions (see [`crate::UndoStore`]).
+/// A transaction still being recorded is not part of the serialized state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    /// Transactions that can be undone (most recent last)
+    undo_stack: Vec<Transaction>,
+    /// Transactions that can be redone (most recent last)
+    redo_stack: Vec<Transaction>,
+    /// Transaction currently being recorded
+    #[serde(skip)]
+    pending: Option<Transaction>,
+    /// Nesting depth of begin/commit calls
+    #[serde(skip)]
+    depth: usize,
+    /// Next transaction id
+    next_id: u64,
+    /// Id of the top undo transaction when the buffer was saved (0 = empty)
+    saved_id: u64,
+}
+
+impl History {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending: None,
+            depth: 0,
+            next_id: 1,
+            saved_id: 0,
+        }
+    }
+
+    /// Begin a transaction
+    ///
+    /// Transactions nest: only the outermost begin/commit pair creates an
+    /// undo step, so composite operations (e.g. replace selection) built from
+    /// smaller ones are still undone at once. `cursors` starts with the
+    /// primary cursor.
+    pub fn begin(&mut self, kind: EditKind, cursors: &[Cursor]) {
+        if self.depth == 0 {
+            let (primary, extra) = split_cursors(cursors);
+            self.pending = Some(Transaction {
+                id: 0,
+                edits: Vec::new(),
+                kind,
+                cursor_before: primary,
+                cursor_after: None,
+                extra_cursors_before: extra,
+                extra_cursors_after: Vec::new(),
+            });
+        }
+        self.depth += 1;
+    }
+
+    /// Commit the current transaction
+    pub fn commit(&mut self, cursors: &[Cursor]) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+
+        let Some(mut transaction) = self.pending.take() else {
+            return;
+        };
+        if transaction.edits.is_empty() {
+            return;
+        }
+        (transaction.cursor_after, transaction.extra_cursors_after) = split_cursors(cursors);
+
+        if self.try_coalesce(&transaction) {
+            return;
+        }
+        self.push(transaction);
+    }
+
+    /// Record an edit that has been applied to the buffer
+    pub fn record(&mut self, edit: Edit) {
+        self.redo_stack.clear();
+
+        if let Some(pending) = &mut self.pending {
+            if let Some(last) = pending.edits.last_mut() {
+                if last.merge(&edit) {
+                    return;
+                }
+            }
+            pending.edits.push(edit);
+        } else {
+            // Edit outside of any transaction: it becomes its own undo step
+            self.push(Transaction {
+                id: 0,
+                edits: vec![edit],
+                kind: EditKind::Other,
+                cursor_before: None,
+                cursor_after: None,
+                extra_cursors_before: Vec::new(),
+                extra_cursors_after: Vec::new(),
+            });
+        }
+    }
+
+    /// Pop the most recent transaction for undoing
+    ///
+    /// The caller applies the inverted edits in reverse order.
+    pub fn undo(&mut self) -> Option<&Transaction> {
+        let transaction = self.undo_stack.pop()?;
+        self.redo_stack.push(transaction);
+        self.redo_stack.last()
+    }
+
+    /// Pop the most recently undone transaction for redoing
+    ///
+    /// The caller re-applies the edits in order.
+    pub fn redo(&mut self) -> Option<&Transaction> {
+        let transaction = self.redo_stack.pop()?;
+        self.undo_stack.push(transaction);
+        self.undo_stack.last()
+    }
+
+    /// Check if there is anything to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Check if there is anything to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Mark the current state as saved
+    pub fn mark_saved(&mut self) {
+        self.saved_id = self.current_id();
+    }
+
+    /// Check if the buffer content matches the last saved state
+    pub fn is_at_save_point(&self) -> bool {
+        let pending_edits = self.pending.as_ref().is_some_and(|t| !t.edits.is_empty());
+        !pending_edits && self.current_id() == self.saved_id
+    }
+
+    /// Clear all history
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.pending = None;
+        self.depth = 0;
+        self.saved_id = 0;
+    }
+
+    /// Number of transactions on the undo stack
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    /// Drop all redoable transactions
+    pub fn discard_redo(&mut self) {
+        self.redo_stack.clear();
+    }
+
+    /// Drop the `count` oldest undoable transactions
+    pub fn discard_oldest(&mut self, count: usize) {
+        let count = count.min(self.undo_stack.len());
+        self.undo_stack.drain(..count);
+    }
+
+    /// Id of the transaction on top of the undo stack (0 if empty)
+    fn current_id(&self) -> u64 {
+        self.undo_stack.last().map(|t| t.id).unwrap_or(0)
+    }
+
+    /// Push a finished transaction onto the undo stack
+    fn push(&mut self, mut transaction: Transaction) {
+        transaction.id = self.next_id;
+        self.next_id += 1;
+        self.undo_stack.push(transaction);
+
+        if self.undo_stack.len() > MAX_TRANSACTIONS {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// Merge a typing/deleting transaction into the previous one if it
+    /// continues it directly
+    ///
+    /// With multiple cursors, each of them must continue where it stopped.
+    /// The edits are appended to the previous ones (merged if adjacent), so
+    /// undoing them in reverse order stays correct.
+    fn try_coalesce(&mut self, transaction: &Transaction) -> bool {
+        // One edit per cursor
+        if transaction.kind == EditKind::Other
+            || transaction.edits.len() != transaction.extra_cursors_before.len() + 1
+        {
+            return false;
+        }
+        let saved_id = self.saved_id;
+        let Some(last) = self.undo_stack.last_mut() else {
+            return false;
+        };
+        if last.kind != transaction.kind || last.id == saved_id {
+            return false;
+        }
+
+        // No cursor must have moved in between
+        let continues = match (&last.cursor_after, &transaction.cursor_before) {
+            (Some(after), Some(before)) => after.position == before.position,
+            _ => false,
+        };
+        let extra_continue = last
+            .extra_cursors_after
+            .iter()
+            .map(|cursor| cursor.position)
+            .eq(transaction
+                .extra_cursors_before
+                .iter()
+                .map(|cursor| cursor.position));
+        if !continues || !extra_continue {
+            return false;
+        }
+
+        // Typing whitespace after a word starts a new undo step
+        if transaction.kind == EditKind::Typing
+            && transaction.starts_with_whitespace()
+            && !last.ends_with_whitespace()
+        {
+            return false;
+        }
+
+        for edit in &transaction.edits {
+            if !last
+                .edits
+                .last_mut()
+                .is_some_and(|previous| previous.merge(edit))
+            {
+                last.edits.push(edit.clone());
+            }
+        }
+        last.cursor_after = transaction.cursor_after.clone();
+        last.extra_cursors_after = transaction.extra_cursors_after.clone();
+        true
+    }
+}
+
+/// Split cursors into the primary cursor and the additional ones
+fn split_cursors(cursors: &[Cursor]) -> (Option<Cursor>, Vec<Cursor>) {
+    match cursors.split_first() {
+        Some((primary, extra)) => (Some(primary.clone()), extra.to_vec()),
+        None => (None, Vec::new()),
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(at: usize, text: &str) -> Edit {
+        Edit::insert(at, text)
+    }
+
+    #[test]
+    fn test_record_and_undo() {
+        let mut history = History::new();
+        history.record(insert(0, "Hello"));
+
+        assert!(history.can_undo());
+        let transaction = history.undo().unwrap();
+        assert_eq!(transaction.edits, vec![insert(0, "Hello")]);
+        assert!(!history.can_undo());
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn test_transaction_groups_edits() {
+        let mut history = History::new();
+        let cursor = Cursor::new();
+
+        history.begin(EditKind::Other, std::slice::from_ref(&cursor));
+        history.record(Edit::remove(0, "old"));
+        history.record(insert(0, "new"));
+        history.commit(&[cursor]);
+
+        let transaction = history.undo().unwrap();
+        assert_eq!(transaction.edits.len(), 2);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_typing_coalesces_per_word() {
+        let mut history = History::new();
+        let mut cursor = Cursor::new();
+
+        for (i, ch) in "ab cd".chars().enumerate() {
+            history.begin(EditKind::Typing, std::slice::from_ref(&cursor));
+            history.record(insert(i, &ch.to_string()));
+            cursor.move_to(0, i + 1);
+            history.commit(std::slice::from_ref(&cursor));
+        }
+
+        assert_eq!(history.undo().unwrap().edits, vec![insert(2, " cd")]);
+        assert_eq!(history.undo().unwrap().edits, vec![insert(0, "ab")]);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_deleting_coalesces_runs() {
+        let mut history = History::new();
+        let mut cursor = Cursor::new();
+        cursor.move_to(0, 4);
+
+        // Backspace from the end of "abcd", then Delete twice at the start
+        for at in (2..4).rev() {
+            history.begin(EditKind::Deleting, std::slice::from_ref(&cursor));
+            history.record(Edit::remove(at, "x"));
+            cursor.move_to(0, at);
+            history.commit(std::slice::from_ref(&cursor));
+        }
+        let cursor = Cursor::new();
+        for _ in 0..2 {
+            history.begin(EditKind::Deleting, std::slice::from_ref(&cursor));
+            history.record(Edit::remove(0, "y"));
+            history.commit(std::slice::from_ref(&cursor));
+        }
+
+        assert_eq!(history.undo().unwrap().edits, vec![Edit::remove(0, "yy")]);
+        assert_eq!(
+            history.undo().unwrap().edits,
+            vec![Edit::remove(3, "x"), Edit::remove(2, "x")]
+        );
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_new_edit_clears_redo() {
+        let mut history = History::new();
+        history.record(insert(0, "a"));
+        history.undo();
+        assert!(history.can_redo());
+
+        history.record(insert(0, "b"));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_save_point() {
+        let mut history = History::new();
+        assert!(history.is_at_save_point());
+
+        history.record(insert(0, "a"));
+        assert!(!history.is_at_save_point());
+
+        history.mark_saved();
+        assert!(history.is_at_save_point());
+
+        history.undo();
+        assert!(!history.is_at_save_point());
+
+        history.redo();
+        assert!(history.is_at_save_point());
+    }
+}
@@ -0,0 +1,204 @@
+//! Indentation style detection
+//!
+//! Buffers detect on load whether a file is indented with tabs or spaces,
+//! and by how many spaces per level (2, 4 or 8), from the first lines. Files
+//! without indented lines get the default (4 spaces).
+
+/// Number of lines looked at to detect the indentation
+pub const DETECT_LINES: usize = 1000;
+
+/// Indentation widths that can be detected
+const DETECTABLE_WIDTHS: [usize; 3] = [2, 4, 8];
+
+/// Tabs or spaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Spaces,
+    Tabs,
+}
+
+/// Indentation of a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indentation {
+    /// Tabs or spaces
+    pub style: IndentStyle,
+    /// Spaces per level (with tabs, levels are one tab wide)
+    pub size: usize,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self::spaces(4)
+    }
+}
+
+impl Indentation {
+    /// Indentation with a number of spaces per level
+    pub fn spaces(size: usize) -> Self {
+        Self {
+            style: IndentStyle::Spaces,
+            size: size.max(1),
+        }
+    }
+
+    /// Indentation with tabs
+    pub fn tabs() -> Self {
+        Self {
+            style: IndentStyle::Tabs,
+            size: 1,
+        }
+    }
+
+    /// Text of one indentation level
+    pub fn unit(&self) -> String {
+        match self.style {
+            IndentStyle::Spaces => " ".repeat(self.size),
+            IndentStyle::Tabs => String::from("\t"),
+        }
+    }
+
+    /// Display width of one level
+    pub fn level_width(&self, tab_width: usize) -> usize {
+        match self.style {
+            IndentStyle::Spaces => self.size,
+            IndentStyle::Tabs => tab_width.max(1),
+        }
+    }
+
+    /// Short description for the status bar ("Spaces: 4", "Tabs")
+    pub fn name(&self) -> String {
+        match self.style {
+            IndentStyle::Spaces => format!("Spaces: {}", self.size),
+            IndentStyle::Tabs => String::from("Tabs"),
+        }
+    }
+
+    /// Detect the indentation from the leading whitespace of the non-blank
+    /// lines of a file (in order)
+    ///
+    /// Returns None if no line is indented. Lines indented by a single space
+    /// (e.g. ` * ` in doc comments) don't count.
+    pub fn detect<'a>(indents: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut widths = [0usize; DETECTABLE_WIDTHS.len()];
+        let mut previous = Some(0);
+
+        for indent in indents {
+            if indent.starts_with('\t') {
+                tab_lines += 1;
+                previous = None;
+                continue;
+            }
+            let spaces = indent.len();
+            if spaces >= 2 {
+                space_lines += 1;
+            }
+            // Width of one level: the change in indentation between lines
+            if let Some(previous) = previous {
+                let delta = spaces.abs_diff(previous);
+                if let Some(i) = DETECTABLE_WIDTHS.iter().position(|&w| w == delta) {
+                    widths[i] += 1;
+                }
+            }
+            previous = Some(spaces);
+        }
+
+        if tab_lines == 0 && space_lines == 0 {
+            return None;
+        }
+        if tab_lines > space_lines {
+            return Some(Self::tabs());
+        }
+        // The most frequent width, the smaller one on ties
+        let (i, count) = widths
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, count)| *count)
+            .expect("non-empty");
+        Some(Self::spaces(if *count > 0 {
+            DETECTABLE_WIDTHS[i]
+        } else {
+            4
+        }))
+    }
+
+    /// Leading whitespace written with indentation `from`, re-indented with
+    /// this style (keeping the number of levels)
+    ///
+    /// Whitespace narrower than a level of `from` is kept as spaces.
+    pub fn reindent(&self, indent: &str, from: Indentation, tab_width: usize) -> String {
+        let width = indent_width(indent, tab_width);
+        let from_width = from.level_width(tab_width);
+        let mut text = self.unit().repeat(width / from_width);
+        text.push_str(&" ".repeat(width % from_width));
+        text
+    }
+
+    /// Leading whitespace with one level removed
+    pub fn outdent<'a>(&self, indent: &'a str) -> &'a str {
+        if let Some(rest) = indent.strip_prefix('\t') {
+            return rest;
+        }
+        let spaces = indent.len() - indent.trim_start_matches(' ').len();
+        &indent[spaces.min(self.size)..]
+    }
+}
+
+/// Display width of leading whitespace
+pub fn indent_width(indent: &str, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    indent.chars().fold(0, |width, c| match c {
+        '\t' => width + tab_width - width % tab_width,
+        _ => width + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let php = [
+            "<?php",
+            "class A",
+            "{",
+            "    function f()",
+            "    {",
+            "        return 1;",
+            "    }",
+            "}",
+        ];
+        let indents = php.map(|line| &line[..line.len() - line.trim_start().len()]);
+        assert_eq!(Indentation::detect(indents), Some(Indentation::spaces(4)));
+
+        let yaml = ["a:", "  b:", "    c: 1", "  d:", "    - 1", "e: 2"];
+        let indents = yaml.map(|line| &line[..line.len() - line.trim_start().len()]);
+        assert_eq!(Indentation::detect(indents), Some(Indentation::spaces(2)));
+
+        let makefile = ["all:", "\tcc -o a a.c", "\trm -f a.o"];
+        let indents = makefile.map(|line| &line[..line.len() - line.trim_start().len()]);
+        assert_eq!(Indentation::detect(indents), Some(Indentation::tabs()));
+
+        // Doc comment continuation lines don't count
+        assert_eq!(Indentation::detect(["", " ", " ", ""]), None);
+    }
+
+    #[test]
+    fn test_reindent_and_outdent() {
+        let spaces = Indentation::spaces(4);
+        assert_eq!(Indentation::tabs().reindent("        ", spaces, 4), "\t\t");
+        assert_eq!(Indentation::tabs().reindent("     ", spaces, 4), "\t ");
+        assert_eq!(
+            Indentation::spaces(2).reindent("\t\t", Indentation::tabs(), 4),
+            "    "
+        );
+        assert_eq!(spaces.reindent(" \t", spaces, 4), "    ");
+
+        assert_eq!(Indentation::spaces(4).outdent("      "), "  ");
+        assert_eq!(Indentation::spaces(4).outdent("  "), "");
+        assert_eq!(Indentation::spaces(4).outdent("\t\t"), "\t");
+    }
+}
@@ -2,17 +2,77 @@
 //!
 //! This crate provides the fundamental building blocks for the editor:
 //! - Buffer: Text storage using rope data structure
+//! - LineEnding: Line ending detection (LF/CRLF/CR)
+//! - LoadOptions: Large-file mode (streamed loading)
+//! - Encoding: Character encoding detection (UTF-8/16, legacy 8-bit)
+//! - HexView: Read-only view of binary files
 //! - Cursor: Position and movement
-//! - Selection: Range selections
+//! - WordChars: Word and subword boundaries
+//! - EditingRules: Auto-closing pairs and auto-indentation
+//! - Indentation: Tabs/spaces detection and indent width
+//! - EditorconfigProperties: `.editorconfig` settings of a file
+//! - Graphemes: Cluster boundaries and display width
+//! - Selection: Range and block (rectangular) selections
+//! - SearchQuery: In-file search and replace (text or regex) over the rope
+//! - ProjectSearch: Parallel search and replace in the project tree (find in files)
+//! - FileIndex: Project file list kept up to date (Ctrl+P)
+//! - FuzzyQuery: Fuzzy path matching
+//! - History: Undo/redo transactions
+//! - UndoStore: Persistent undo history across sessions
+//! - SwapStore: Crash recovery snapshots of unsaved buffers
+//! - FileWatcher: External modification detection
+//! - Config: User configuration (config.toml)
 //! - Editor: Combined state with viewport
 
 mod buffer;
+mod config;
 mod cursor;
+mod editing;
 mod editor;
+mod editorconfig;
+mod encoding;
+mod file_index;
+mod fuzzy;
+mod grapheme;
+mod hash;
+mod hex;
+mod history;
+mod indent;
+mod line_ending;
+mod load;
+mod project_search;
+mod save;
+mod search;
+mod swap;
+mod undo_store;
+mod watcher;
+mod word;
 
-pub use buffer::{Buffer, BufferError};
-pub use cursor::{Cursor, Position};
+pub use buffer::{Buffer, BufferError, FileFormat, TextEdit};
+pub use config::{Config, ConfigError, EditorConfig, FilesConfig, LanguageConfig, SearchConfig};
+pub use cursor::{BlockSelection, Cursor, Position};
+pub use editing::EditingRules;
 pub use editor::{Editor, Viewport};
+pub use editorconfig::{EditorconfigProperties, EDITORCONFIG_FILE};
+pub use encoding::{Encoding, ENCODINGS};
+pub use file_index::FileIndex;
+pub use fuzzy::{FuzzyMatch, FuzzyQuery};
+pub use grapheme::{grapheme_width, DEFAULT_TAB_WIDTH};
+pub use hex::{is_binary, HexView, BYTES_PER_ROW};
+pub use history::{Edit, EditKind, History, Transaction};
+pub use indent::{indent_width, IndentStyle, Indentation};
+pub use line_ending::{normalize_line_endings, LineEnding};
+pub use load::{LoadOptions, DEFAULT_LARGE_FILE_THRESHOLD};
+pub use project_search::{
+    replace_in_file, FileFilter, FileMatches, ProjectMatch, ProjectSearch, ProjectSearchError,
+    MAX_PROJECT_MATCHES,
+};
+pub use save::{BackupMode, SaveOptions};
+pub use search::{LineChange, Replacement, SearchError, SearchMatch, SearchOptions, SearchQuery};
+pub use swap::{Swap, SwapStore, SwapStoreError};
+pub use undo_store::{PurgeStats, UndoStore, UndoStoreError, DEFAULT_MAX_UNDO_FILE_SIZE};
+pub use watcher::{FileWatcher, WatchError};
+pub use word::WordChars;
 
 /// Re-export ropey for convenience
 pub use ropey;
@@ -0,0 +1,270 @@
+//! Streaming file loading for large files
+//!
+//! Files above [`LoadOptions::large_file_threshold`] are read in chunks and
+//! appended to a [`RopeBuilder`], so the whole file never exists as one
+//! `String`. The encoding is detected from the first chunk, the line ending
+//! from the first chunk containing a line break. If a later chunk turns out
+//! not to be UTF-8, the file is loaded again as legacy 8-bit text, so nothing
+//! is ever replaced by U+FFFD.
+
+use crate::{is_binary, normalize_line_endings, BufferError, Encoding, FileFormat, LineEnding};
+use ropey::{Rope, RopeBuilder};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Default size above which files open in large-file mode (50 MiB)
+pub const DEFAULT_LARGE_FILE_THRESHOLD: u64 = 50 * 1024 * 1024;
+
+/// Size of the chunks read from disk
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Options for loading a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// File size in bytes from which large-file mode is used
+    pub large_file_threshold: u64,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            large_file_threshold: DEFAULT_LARGE_FILE_THRESHOLD,
+        }
+    }
+}
+
+/// Stream a file into a rope
+///
+/// With `encoding` set, detection is skipped (and binary files are not
+/// refused). Line endings are normalised to `\n`.
+pub(crate) fn load_streaming(
+    path: &Path,
+    encoding: Option<Encoding>,
+) -> Result<(Rope, FileFormat), BufferError> {
+    let mut file = File::open(path)?;
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let first = read_chunk(&mut file, &mut chunk)?;
+
+    let detected = encoding.is_none();
+    let (encoding, bom) = match encoding {
+        Some(encoding) => {
+            let bom = !encoding.bom().is_empty() && first.starts_with(encoding.bom());
+            (encoding, bom)
+        }
+        None if is_binary(first) => {
+            return Err(BufferError::BinaryFile {
+                path: path.to_path_buf(),
+            })
+        }
+        None => Encoding::detect(first),
+    };
+    // Only UTF-8 detected from the first chunk can be wrong about the rest
+    let strict = detected && encoding == Encoding::Utf8 && !bom;
+    let pending = first.to_vec();
+    if let Some(loaded) = stream(&mut file, &mut chunk, pending, encoding, bom, strict)? {
+        return Ok(loaded);
+    }
+
+    // Not UTF-8 after all: legacy 8-bit, as `Encoding::detect` would have
+    // decided for the whole file. Windows-1252 and ISO-8859-1 only differ in
+    // 0x80-0x9F, so the choice is made once the whole file was seen.
+    let mut file = File::open(path)?;
+    let pending = read_chunk(&mut file, &mut chunk)?.to_vec();
+    let (rope, mut format) = stream(
+        &mut file,
+        &mut chunk,
+        pending,
+        Encoding::Windows1252,
+        false,
+        false,
+    )?
+    .expect("8-bit decoding can't fail");
+    // Bytes 0x80-0x9F decode to C1 controls or characters above U+00FF
+    if !rope
+        .chars()
+        .any(|c| matches!(c, '\u{80}'..='\u{9F}') || c > '\u{FF}')
+    {
+        format.encoding = Encoding::Iso8859_1;
+    }
+    Ok((rope, format))
+}
+
+/// Decode the first chunk (`pending`) and the rest of the file into a rope
+///
+/// A BOM at the start is skipped if `bom` is set. With `strict`, returns None as soon as a chunk isn't valid in the
+/// encoding (instead of decoding it lossily).
+fn stream(
+    file: &mut File,
+    chunk: &mut [u8],
+    mut pending: Vec<u8>,
+    encoding: Encoding,
+    bom: bool,
+    strict: bool,
+) -> Result<Option<(Rope, FileFormat)>, BufferError> {
+    let mut at_end = pending.len() < CHUNK_SIZE;
+    if bom {
+        pending.drain(..encoding.bom().len());
+    }
+    let mut builder = RopeBuilder::new();
+    let mut line_ending = None;
+    let mut held_cr = false;
+
+    loop {
+        let split = if at_end {
+            pending.len()
+        } else {
+            complete_prefix_len(encoding, &pending)
+        };
+        if strict && std::str::from_utf8(&pending[..split]).is_err() {
+            return Ok(None);
+        }
+        let mut text = encoding.decode(&pending[..split]);
+        pending.drain(..split);
+
+        // Detected from the first chunk that has a line break
+        if line_ending.is_none() && text.contains(['\n', '\r']) {
+            line_ending = Some(LineEnding::detect(&text));
+        }
+
+        if held_cr {
+            text.insert(0, '\r');
+        }
+        // A `\r` at the end may be the first half of a `\r\n`
+        held_cr = !at_end && text.ends_with('\r');
+        if held_cr {
+            text.pop();
+        }
+        builder.append(&normalize_line_endings(&text));
+
+        if at_end {
+            let format = FileFormat {
+                line_ending: line_ending.unwrap_or_default(),
+                encoding,
+                bom,
+            };
+            return Ok(Some((builder.finish(), format)));
+        }
+        let read = read_chunk(file, chunk)?;
+        at_end = read.len() < CHUNK_SIZE;
+        pending.extend_from_slice(read);
+    }
+}
+
+/// Fill the buffer as far as possible (short only at end of file)
+fn read_chunk<'a>(file: &mut File, chunk: &'a mut [u8]) -> std::io::Result<&'a [u8]> {
+    let mut len = 0;
+    while len < chunk.len() {
+        match file.read(&mut chunk[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(&chunk[..len])
+}
+
+/// Length of the prefix that doesn't end in a partial character
+fn complete_prefix_len(encoding: Encoding, bytes: &[u8]) -> usize {
+    match encoding {
+        Encoding::Utf8 => {
+            // Find the start of the last character and check if it's complete
+            let tail = bytes.len().saturating_sub(3);
+            let Some(start) = (tail..bytes.len()).rev().find(|&i| bytes[i] & 0xC0 != 0x80) else {
+                return bytes.len();
+            };
+            let needed = match bytes[start] {
+                0xF0.. => 4,
+                0xE0.. => 3,
+                0xC0.. => 2,
+                _ => 1,
+            };
+            if bytes.len() - start < needed {
+                start
+            } else {
+                bytes.len()
+            }
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let len = bytes.len() - bytes.len() % 2;
+            if len < 2 {
+                return len;
+            }
+            let unit = [bytes[len - 2], bytes[len - 1]];
+            let unit = if encoding == Encoding::Utf16Le {
+                u16::from_le_bytes(unit)
+            } else {
+                u16::from_be_bytes(unit)
+            };
+            // Keep a high surrogate together with its low surrogate
+            if (0xD800..0xDC00).contains(&unit) {
+                len - 2
+            } else {
+                len
+            }
+        }
+        _ => bytes.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_prefix_len() {
+        let euro = "€".as_bytes();
+        assert_eq!(complete_prefix_len(Encoding::Utf8, b"abc"), 3);
+        assert_eq!(
+            complete_prefix_len(Encoding::Utf8, &[b'a', euro[0], euro[1]]),
+            1
+        );
+        assert_eq!(
+            complete_prefix_len(Encoding::Utf8, &[b'a', euro[0], euro[1], euro[2]]),
+            4
+        );
+        // Odd byte and high surrogate of U+1F600 are held back
+        assert_eq!(complete_prefix_len(Encoding::Utf16Le, b"a\0b"), 2);
+        assert_eq!(complete_prefix_len(Encoding::Utf16Le, b"a\0\x3D\xD8"), 2);
+    }
+
+    #[test]
+    fn test_load_streaming_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.sql");
+
+        // The first chunk boundary splits the '€', the second one a CRLF
+        let mut text = "a".repeat(CHUNK_SIZE - 1) + "€\r\n";
+        text += &"b".repeat(2 * CHUNK_SIZE - 1 - text.len());
+        text += "\r\nend";
+        std::fs::write(&path, &text).unwrap();
+
+        let (rope, format) = load_streaming(&path, None).unwrap();
+        assert_eq!(format.line_ending, LineEnding::Crlf);
+        assert_eq!(format.encoding, Encoding::Utf8);
+        assert_eq!(rope.len_lines(), 3);
+        assert_eq!(rope.to_string(), text.replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn test_load_streaming_legacy_after_first_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.sql");
+
+        // ASCII in the first chunk, a Latin-1 "üß" in the second
+        let mut bytes = b"-- x\n".repeat(CHUNK_SIZE / 5 + 1);
+        bytes.extend_from_slice(b"-- Gr\xFC\xDF\n");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let (rope, format) = load_streaming(&path, None).unwrap();
+        assert_eq!(format.encoding, Encoding::Iso8859_1);
+        assert!(rope.to_string().ends_with("-- Grüß\n"));
+        assert_eq!(format.encoding.encode(&rope.to_string()).unwrap(), bytes);
+
+        // With a Windows-1252 quote further on
+        bytes.extend_from_slice(b"-- \x93quoted\x94\n");
+        std::fs::write(&path, &bytes).unwrap();
+        let (rope, format) = load_streaming(&path, None).unwrap();
+        assert_eq!(format.encoding, Encoding::Windows1252);
+        assert!(rope.to_string().ends_with("-- “quoted”\n"));
+    }
+}
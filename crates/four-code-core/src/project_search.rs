@@ -0,0 +1,463 @@
+//! Project-wide search (find in files)
+//!
+//! The project tree is walked in parallel with the `ignore` crate, so
+//! `.gitignore`d and hidden files are skipped like in ripgrep. Include and
+//! exclude globs (gitignore syntax) narrow the files down. Matches are sent
+//! per file over a channel as they are found, so results show up while the
+//! search runs; it can be cancelled at any time.
+//!
+//! Binary files, files that are not valid UTF-8 and files larger than
+//! [`MAX_FILE_SIZE`] are skipped.
+//!
+//! [`replace_in_file`] applies a replacement to the matches found in a file,
+//! going through [`Buffer`] so the file is written atomically in its own
+//! encoding and line endings.
+
+use crate::search::line_text;
+use crate::{
+    is_binary, normalize_line_endings, Buffer, BufferError, Replacement, SaveOptions, SearchMatch,
+    SearchQuery,
+};
+use ignore::overrides::OverrideBuilder;
+use ignore::{WalkBuilder, WalkState};
+use ropey::Rope;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The search stops after this many matches
+pub const MAX_PROJECT_MATCHES: usize = 20_000;
+
+/// Files larger than this are not searched
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Chars of context kept before a match for display
+const CONTEXT_CHARS: usize = 40;
+
+/// Chars of a line kept for display
+const PREVIEW_CHARS: usize = 200;
+
+#[derive(Error, Debug)]
+pub enum ProjectSearchError {
+    #[error("Invalid glob: {0}")]
+    InvalidGlob(#[from] ignore::Error),
+}
+
+/// Files to search, as gitignore-style globs (`*.php`, `vendor/`,
+/// `src/**/Test*`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileFilter {
+    /// Only search files matching one of these (all files if empty)
+    pub include: Vec<String>,
+    /// Skip files and directories matching one of these
+    pub exclude: Vec<String>,
+}
+
+/// A match in a project file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectMatch {
+    /// Line and char columns of the match
+    pub range: SearchMatch,
+    /// The part of the line around the match (for display)
+    pub preview: String,
+    /// Char column where the preview starts in the line
+    pub preview_start: usize,
+}
+
+/// The matches in one file, in line order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<ProjectMatch>,
+}
+
+/// A running project search
+///
+/// The search runs on background threads; [`ProjectSearch::poll`] returns
+/// the files found since the last call. Dropping the search cancels it.
+#[derive(Debug)]
+pub struct ProjectSearch {
+    receiver: Receiver<FileMatches>,
+    cancelled: Arc<AtomicBool>,
+    done: Arc<AtomicBool>,
+    files_searched: Arc<AtomicUsize>,
+    match_count: Arc<AtomicUsize>,
+}
+
+impl FileFilter {
+    /// Parse comma-separated globs (`*.php, *.twig`)
+    pub fn parse_globs(text: &str) -> Vec<String> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|glob| !glob.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl ProjectSearch {
+    /// Start searching the files under `root`
+    pub fn start(
+        root: &Path,
+        query: SearchQuery,
+        filter: &FileFilter,
+    ) -> Result<Self, ProjectSearchError> {
+        let mut overrides = OverrideBuilder::new(root);
+        for glob in &filter.include {
+            overrides.add(glob)?;
+        }
+        for glob in &filter.exclude {
+            overrides.add(&format!("!{glob}"))?;
+        }
+        let walker = WalkBuilder::new(root)
+            .overrides(overrides.build()?)
+            .require_git(false)
+            .max_filesize(Some(MAX_FILE_SIZE))
+            .build_parallel();
+
+        let (sender, receiver) = mpsc::channel();
+        let search = Self {
+            receiver,
+            cancelled: Arc::default(),
+            done: Arc::default(),
+            files_searched: Arc::default(),
+            match_count: Arc::default(),
+        };
+
+        let query = Arc::new(query);
+        let cancelled = Arc::clone(&search.cancelled);
+        let done = Arc::clone(&search.done);
+        let files_searched = Arc::clone(&search.files_searched);
+        let match_count = Arc::clone(&search.match_count);
+        std::thread::spawn(move || {
+            walker.run(|| {
+                let sender = sender.clone();
+                let query = Arc::clone(&query);
+                let cancelled = Arc::clone(&cancelled);
+                let files_searched = Arc::clone(&files_searched);
+                let match_count = Arc::clone(&match_count);
+                Box::new(move |entry| {
+                    if cancelled.load(Ordering::Relaxed) {
+                        return WalkState::Quit;
+                    }
+                    let Ok(entry) = entry else {
+                        return WalkState::Continue;
+                    };
+                    if !entry.file_type().is_some_and(|t| t.is_file()) {
+                        return WalkState::Continue;
+                    }
+                    files_searched.fetch_add(1, Ordering::Relaxed);
+                    let Some(matches) = search_file(entry.path(), &query) else {
+                        return WalkState::Continue;
+                    };
+                    let total = match_count.fetch_add(matches.len(), Ordering::Relaxed);
+                    let file = FileMatches {
+                        path: entry.into_path(),
+                        matches,
+                    };
+                    if sender.send(file).is_err() || total >= MAX_PROJECT_MATCHES {
+                        return WalkState::Quit;
+                    }
+                    WalkState::Continue
+                })
+            });
+            done.store(true, Ordering::Release);
+        });
+        Ok(search)
+    }
+
+    /// Files found since the last call
+    pub fn poll(&self) -> Vec<FileMatches> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// Stop searching
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Check if the search finished (or stopped after cancelling)
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// Check if the search was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Check if the search stopped at [`MAX_PROJECT_MATCHES`]
+    pub fn is_truncated(&self) -> bool {
+        self.match_count.load(Ordering::Relaxed) >= MAX_PROJECT_MATCHES
+    }
+
+    /// Number of files searched so far
+    pub fn files_searched(&self) -> usize {
+        self.files_searched.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for ProjectSearch {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Replace matches in a file on disk
+///
+/// Matches no longer found at the same place (the file changed since the
+/// search) are left alone. Returns the number of replaced matches; the file
+/// is only written if there are any.
+pub fn replace_in_file(
+    path: &Path,
+    query: &SearchQuery,
+    matches: &[SearchMatch],
+    replacement: &Replacement,
+    save_options: &SaveOptions,
+) -> Result<usize, BufferError> {
+    let mut buffer = Buffer::from_file(path)?;
+    let matches = query.still_matching(buffer.rope(), matches);
+    if matches.is_empty() {
+        return Ok(0);
+    }
+    // All expanded from the unchanged text: a replacement on the same line
+    // would change what `\b` or `$` see
+    let edits: Vec<(usize, usize, String)> = matches
+        .iter()
+        .map(|m| {
+            let start = buffer.line_col_to_char(m.line, m.start).unwrap_or(0);
+            let end = buffer.line_col_to_char(m.line, m.end).unwrap_or(start);
+            let text = query.replacement_for(buffer.rope(), m, replacement);
+            (start, end, text)
+        })
+        .collect();
+    // From the end, so the earlier positions stay valid
+    for (start, end, text) in edits.iter().rev() {
+        buffer.remove(*start, *end);
+        buffer.insert(*start, text);
+    }
+    buffer.set_save_options(save_options.clone());
+    buffer.save()?;
+    Ok(matches.len())
+}
+
+/// Matches in a file (None if there are none or the file is skipped)
+fn search_file(path: &Path, query: &SearchQuery) -> Option<Vec<ProjectMatch>> {
+    let bytes = std::fs::read(path).ok()?;
+    if is_binary(&bytes) {
+        return None;
+    }
+    let text = std::str::from_utf8(&bytes).ok()?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    // Most files don't match: check the whole text before going by lines
+    if !query.regex().is_match(text) {
+        return None;
+    }
+    // Lines as the file's buffer has them (so the match positions are
+    // valid there)
+    let rope = Rope::from_str(&normalize_line_endings(text));
+    let matches: Vec<ProjectMatch> = query
+        .find_all(&rope)
+        .into_iter()
+        .map(|range| ProjectMatch::new(range, &line_text(&rope, range.line)))
+        .collect();
+    (!matches.is_empty()).then_some(matches)
+}
+
+impl ProjectMatch {
+    /// A match with the part of its line to display: from a little before
+    /// the match (not before the indentation)
+    fn new(range: SearchMatch, line: &str) -> Self {
+        let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+        let preview_start = range
+            .start
+            .saturating_sub(CONTEXT_CHARS)
+            .max(indent.min(range.start));
+        Self {
+            range,
+            preview: line
+                .chars()
+                .skip(preview_start)
+                .take(PREVIEW_CHARS)
+                .collect(),
+            preview_start,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SearchOptions, WordChars};
+
+    /// Run a search to the end, results sorted by path (relative to `root`)
+    fn search_all(
+        root: &Path,
+        pattern: &str,
+        options: SearchOptions,
+        filter: &FileFilter,
+    ) -> Vec<(String, Vec<usize>)> {
+        let query = SearchQuery::new(pattern, options, &WordChars::default()).unwrap();
+        let search = ProjectSearch::start(root, query, filter).unwrap();
+        let mut files = Vec::new();
+        while !search.is_done() {
+            files.extend(search.poll());
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        files.extend(search.poll());
+        let mut files: Vec<_> = files
+            .into_iter()
+            .map(|file| {
+                let path = file.path.strip_prefix(root).unwrap();
+                let lines = file.matches.iter().map(|m| m.range.line).collect();
+                (path.to_string_lossy().replace('\\', "/"), lines)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_search_project() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("src")).unwrap();
+        std::fs::create_dir_all(root.path().join("vendor/lib")).unwrap();
+        std::fs::write(root.path().join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(
+            root.path().join("src/User.php"),
+            "<?php\n$needle = 1;\n// Needle\n",
+        )
+        .unwrap();
+        std::fs::write(root.path().join("src/app.js"), "let x;\r\nneedle;\r\n").unwrap();
+        std::fs::write(root.path().join("vendor/lib/Lib.php"), "needle\n").unwrap();
+        std::fs::write(root.path().join("debug.log"), "needle\n").unwrap();
+        std::fs::write(root.path().join("image.bin"), b"needle\0\x01\x02").unwrap();
+
+        let all = FileFilter::default();
+        assert_eq!(
+            search_all(root.path(), "needle", SearchOptions::default(), &all),
+            [
+                (String::from("src/User.php"), vec![1, 2]),
+                (String::from("src/app.js"), vec![1]),
+                (String::from("vendor/lib/Lib.php"), vec![0]),
+            ]
+        );
+
+        let filter = FileFilter {
+            include: FileFilter::parse_globs("*.php, *.twig"),
+            exclude: FileFilter::parse_globs("vendor/"),
+        };
+        assert_eq!(
+            search_all(root.path(), "needle", SearchOptions::default(), &filter),
+            [(String::from("src/User.php"), vec![1, 2])]
+        );
+
+        // Anchors match at every line (also with CRLF line endings)
+        let regex = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            search_all(root.path(), "^needle;$", regex, &all),
+            [(String::from("src/app.js"), vec![1])]
+        );
+
+        // Lines are counted as in the buffer: a lone CR is a line break
+        std::fs::write(root.path().join("mac.txt"), "a\rb\rcr-needle\r").unwrap();
+        assert_eq!(
+            search_all(root.path(), "cr-needle", SearchOptions::default(), &all),
+            [(String::from("mac.txt"), vec![2])]
+        );
+    }
+
+    #[test]
+    fn test_replace_in_file() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("User.php");
+        std::fs::write(&path, "\u{feff}$user = 1;\r\n$user2 = $user;\r\n").unwrap();
+
+        let query =
+            SearchQuery::new("user", SearchOptions::default(), &WordChars::default()).unwrap();
+        let matches: Vec<SearchMatch> = search_file(&path, &query)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.range)
+            .collect();
+        assert_eq!(matches.len(), 3);
+
+        // Only the included matches are replaced, BOM and CRLF are kept
+        let replacement = Replacement {
+            template: String::from("customer"),
+            preserve_case: false,
+        };
+        let options = SaveOptions::default();
+        let count = replace_in_file(&path, &query, &matches[..2], &replacement, &options);
+        assert_eq!(count.unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\u{feff}$customer = 1;\r\n$customer2 = $user;\r\n"
+        );
+
+        // Replaced matches are not found again
+        let count = replace_in_file(&path, &query, &matches[..2], &replacement, &options);
+        assert_eq!(count.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_replace_in_file_expands_before_replacing() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("a.txt");
+        std::fs::write(&path, "aab\n").unwrap();
+
+        let options = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new(r"(a)\B", options, &WordChars::default()).unwrap();
+        let matches: Vec<SearchMatch> = search_file(&path, &query)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.range)
+            .collect();
+        let replacement = Replacement {
+            template: String::from("<$1>"),
+            preserve_case: false,
+        };
+        let count = replace_in_file(
+            &path,
+            &query,
+            &matches,
+            &replacement,
+            &SaveOptions::default(),
+        );
+        assert_eq!(count.unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "<a><a>b\n");
+    }
+
+    #[test]
+    fn test_preview() {
+        let line = format!("        {}needle", "x".repeat(100));
+        let range = SearchMatch {
+            line: 0,
+            start: 108,
+            end: 114,
+        };
+        let m = ProjectMatch::new(range, &line);
+        assert_eq!(m.preview_start, 68);
+        assert!(m.preview.ends_with("xneedle"));
+
+        // Indentation is left out
+        let m = ProjectMatch::new(
+            SearchMatch {
+                line: 0,
+                start: 4,
+                end: 7,
+            },
+            "    foo();",
+        );
+        assert_eq!((m.preview.as_str(), m.preview_start), ("foo();", 4));
+    }
+}
@@ -0,0 +1,387 @@
+//! Atomic, permission-preserving file writes
+//!
+//! Saving never writes to the target directly. The content goes to a
+//! temporary file in the same directory, is fsynced, and then renamed over
+//! the original, so a crash or a full disk can't leave a truncated file.
+
+use crate::BufferError;
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Names tried for the temporary file before giving up
+const TEMP_ATTEMPTS: u32 = 100;
+
+/// Where to keep a copy of the previous file content on save
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// No backup
+    #[default]
+    None,
+    /// `file~` next to the original
+    Suffix,
+    /// Copy into a backup directory (full path encoded in the file name)
+    Directory(PathBuf),
+}
+
+/// Options for saving a buffer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Backup of the previous content
+    pub backup: BackupMode,
+}
+
+/// Write `contents` to `path` atomically
+///
+/// Symlinks are resolved so the link itself stays intact, and mode and
+/// ownership of an existing file are carried over to the new one.
+pub(crate) fn write_atomic(
+    path: &Path,
+    contents: &[u8],
+    options: &SaveOptions,
+) -> Result<(), BufferError> {
+    // Write through symlinks instead of replacing them
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let existing = fs::metadata(&target).ok();
+
+    if existing.is_some() {
+        backup(&target, &options.backup)?;
+    }
+
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let (tmp_path, file) = create_temp(&dir, &target, existing.as_ref())?;
+
+    let result = write_temp(file, &tmp_path, contents, existing.as_ref()).and_then(|keep_owner| {
+        if keep_owner {
+            fs::rename(&tmp_path, &target).map_err(|source| BufferError::RenameError {
+                path: target.clone(),
+                source,
+            })
+        } else {
+            // Can't give the new file the original owner: overwrite in place
+            // instead (not atomic, but keeps owner and hard links)
+            let _ = fs::remove_file(&tmp_path);
+            write_in_place(&target, contents)
+        }
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result?;
+
+    sync_dir(&dir);
+    Ok(())
+}
+
+/// Create the temporary file next to the target
+///
+/// The file gets the original's mode from the start, so the content is
+/// never readable by more users than the original allows. Names left over
+/// by a crashed save (PIDs get reused) are skipped.
+fn create_temp(
+    dir: &Path,
+    target: &Path,
+    existing: Option<&fs::Metadata>,
+) -> Result<(PathBuf, File), BufferError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if let Some(metadata) = existing {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(metadata.permissions().mode() & 0o7777);
+    }
+    #[cfg(not(unix))]
+    let _ = existing;
+
+    let mut attempt = 0;
+    loop {
+        let tmp_path = temp_path(dir, target);
+        match options.open(&tmp_path) {
+            Ok(file) => return Ok((tmp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < TEMP_ATTEMPTS => {
+                attempt += 1;
+            }
+            Err(source) => {
+                return Err(BufferError::WriteError {
+                    path: tmp_path,
+                    source,
+                })
+            }
+        }
+    }
+}
+
+/// Write and fsync the temporary file, copying permissions and owner
+///
+/// Returns `false` if the original owner could not be restored.
+fn write_temp(
+    mut file: File,
+    tmp_path: &Path,
+    contents: &[u8],
+    existing: Option<&fs::Metadata>,
+) -> Result<bool, BufferError> {
+    let write_error = |source| BufferError::WriteError {
+        path: tmp_path.to_path_buf(),
+        source,
+    };
+
+    file.write_all(contents).map_err(write_error)?;
+    file.sync_all().map_err(|source| BufferError::SyncError {
+        path: tmp_path.to_path_buf(),
+        source,
+    })?;
+
+    let Some(metadata) = existing else {
+        return Ok(true);
+    };
+    // The umask may have dropped bits of the mode given at creation
+    fs::set_permissions(tmp_path, metadata.permissions()).map_err(|source| {
+        BufferError::PermissionError {
+            path: tmp_path.to_path_buf(),
+            source,
+        }
+    })?;
+
+    Ok(copy_owner(tmp_path, metadata))
+}
+
+/// Give the temporary file the owner of the original
+#[cfg(unix)]
+fn copy_owner(tmp_path: &Path, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(tmp_metadata) = fs::metadata(tmp_path) else {
+        return false;
+    };
+    if tmp_metadata.uid() == metadata.uid() && tmp_metadata.gid() == metadata.gid() {
+        return true;
+    }
+    std::os::unix::fs::chown(tmp_path, Some(metadata.uid()), Some(metadata.gid())).is_ok()
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_tmp_path: &Path, _metadata: &fs::Metadata) -> bool {
+    true
+}
+
+/// Overwrite a file in place
+fn write_in_place(path: &Path, contents: &[u8]) -> Result<(), BufferError> {
+    let write_error = |source| BufferError::WriteError {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map_err(write_error)?;
+    file.write_all(contents).map_err(write_error)?;
+    file.sync_all().map_err(|source| BufferError::SyncError {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Copy the current file content to its backup location
+fn backup(path: &Path, mode: &BackupMode) -> Result<(), BufferError> {
+    let backup_path = match mode {
+        BackupMode::None => return Ok(()),
+        BackupMode::Suffix => {
+            let mut name = path.as_os_str().to_owned();
+            name.push("~");
+            PathBuf::from(name)
+        }
+        BackupMode::Directory(dir) => {
+            fs::create_dir_all(dir).map_err(|source| BufferError::BackupError {
+                path: dir.clone(),
+                source,
+            })?;
+            // Like Vim's `backupdir` ending in `//`: encode the full path
+            let name = path.to_string_lossy().replace(['/', '\\'], "%");
+            dir.join(name)
+        }
+    };
+
+    fs::copy(path, &backup_path)
+        .map(|_| ())
+        .map_err(|source| BufferError::BackupError {
+            path: backup_path,
+            source,
+        })
+}
+
+/// Number of temporary file names handed out by this process
+static TEMP_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Temporary file path next to the target (a new name on every call)
+fn temp_path(dir: &Path, target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let count = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    dir.join(format!(
+        ".{name}.four-code-{}-{count}.tmp",
+        std::process::id()
+    ))
+}
+
+/// Create a directory (and its parents) only the user can access
+///
+/// For state directories holding copies of file content (swap files, undo
+/// history). An existing directory is restricted too.
+pub(crate) fn create_private_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+    }
+    #[cfg(not(unix))]
+    fs::create_dir_all(dir)
+}
+
+/// Fsync a directory so the rename is durable (best effort)
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.php");
+        fs::write(&path, "old content that is longer").unwrap();
+
+        write_atomic(&path, b"new", &SaveOptions::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(dir_entries(dir.path()), vec!["index.php"]);
+    }
+
+    #[test]
+    fn test_backup_suffix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.php");
+        fs::write(&path, "old").unwrap();
+
+        let options = SaveOptions {
+            backup: BackupMode::Suffix,
+        };
+        write_atomic(&path, b"new", &options).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("index.php~")).unwrap(),
+            "old"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_backup_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.php");
+        let backup_dir = dir.path().join("backups");
+        fs::write(&path, "old").unwrap();
+
+        let options = SaveOptions {
+            backup: BackupMode::Directory(backup_dir.clone()),
+        };
+        write_atomic(&path, b"new", &options).unwrap();
+
+        let backups = dir_entries(&backup_dir);
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].ends_with("%index.php"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserves_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("script.sh");
+        let link = dir.path().join("link.sh");
+        fs::write(&target, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o754)).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"#!/bin/sh\necho hi\n", &SaveOptions::default()).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "#!/bin/sh\necho hi\n");
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o754);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temp_file_created_with_original_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        fs::write(&path, "SECRET=1\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+
+        // Before anything is written
+        let (tmp_path, _file) = create_temp(dir.path(), &path, Some(&metadata)).unwrap();
+        let mode = fs::metadata(&tmp_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_stale_temp_files_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.php");
+        fs::write(&path, "old").unwrap();
+
+        // Left behind by a crashed process that had the same PID
+        let next = TEMP_COUNTER.load(Ordering::Relaxed);
+        for count in next..next + 5 {
+            let name = format!(".index.php.four-code-{}-{count}.tmp", std::process::id());
+            fs::write(dir.path().join(name), "stale").unwrap();
+        }
+
+        write_atomic(&path, b"new", &SaveOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_missing_directory_fails_with_write_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("does-not-exist").join("file.txt");
+
+        let result = write_atomic(&path, b"x", &SaveOptions::default());
+        assert!(matches!(result, Err(BufferError::WriteError { .. })));
+    }
+}
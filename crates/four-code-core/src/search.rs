@@ -0,0 +1,425 @@
+//! In-file search
+//!
+//! Queries are plain text or regular expressions, optionally case sensitive
+//! and restricted to whole words. The rope is searched line by line: lines
+//! stored in one rope chunk are searched in place, only lines spanning chunk
+//! boundaries are copied (into a reused buffer), so the document is never
+//! copied as a whole. Matches therefore don't span lines.
+//!
+//! Replacements can refer to capture groups (`$1`, `${name}`) of regex
+//! queries and can preserve the case of the replaced text.
+
+use crate::{Position, WordChars};
+use regex::{Regex, RegexBuilder};
+use ropey::Rope;
+use std::borrow::Cow;
+use std::ops::Range;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SearchError {
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
+}
+
+/// Search toggles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Match case exactly
+    pub case_sensitive: bool,
+    /// Only match whole words (per the language's word characters)
+    pub whole_word: bool,
+    /// Treat the query as a regular expression
+    pub regex: bool,
+}
+
+/// A compiled search query
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    regex: Regex,
+    /// The pattern is a regex (replacements expand capture groups)
+    is_regex: bool,
+    whole_word: bool,
+    word_chars: WordChars,
+}
+
+/// A match: char columns `start..end` of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SearchMatch {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Replacement text for matches
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Replacement {
+    /// Text, with `$1` / `${name}` capture group references for regex
+    /// queries (`$$` is a literal `$`)
+    pub template: String,
+    /// Adapt the replacement to the case of the replaced text (`FOO`, `Foo`,
+    /// `foo`)
+    pub preserve_case: bool,
+}
+
+/// A line changed by replacing matches (for previews)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+impl SearchQuery {
+    /// Compile a query
+    pub fn new(
+        pattern: &str,
+        options: SearchOptions,
+        word_chars: &WordChars,
+    ) -> Result<Self, SearchError> {
+        let pattern = if options.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        // `^` and `$` match at line boundaries, also when a whole text is
+        // checked for matches at once
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .crlf(true)
+            .build()?;
+        Ok(Self {
+            regex,
+            is_regex: options.regex,
+            whole_word: options.whole_word,
+            word_chars: word_chars.clone(),
+        })
+    }
+
+    /// The compiled regular expression
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Byte ranges of the matches in a line (without line break)
+    ///
+    /// Empty matches are skipped.
+    pub fn find_in_line<'a>(&'a self, line: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.regex
+            .find_iter(line)
+            .map(|m| m.range())
+            .filter(|range| !range.is_empty())
+            .filter(move |range| !self.whole_word || self.is_whole_word(line, range))
+    }
+
+    /// Check if a match is not preceded or followed by a word character
+    fn is_whole_word(&self, line: &str, range: &Range<usize>) -> bool {
+        let before = line[..range.start].chars().next_back();
+        let after = line[range.end..].chars().next();
+        !before.is_some_and(|c| self.word_chars.is_word_char(c))
+            && !after.is_some_and(|c| self.word_chars.is_word_char(c))
+    }
+
+    /// Find all matches in a rope, in document order
+    pub fn find_all(&self, rope: &Rope) -> Vec<SearchMatch> {
+        self.find_in_lines(rope, 0..rope.len_lines())
+    }
+
+    /// Find the matches in a range of lines of a rope
+    pub fn find_in_lines(&self, rope: &Rope, lines: Range<usize>) -> Vec<SearchMatch> {
+        let mut matches = Vec::new();
+        let mut copy = String::new();
+        let end = lines.end.min(rope.len_lines());
+        for line in lines.start..end {
+            let slice = rope.line(line);
+            let text = match slice.as_str() {
+                Some(text) => text,
+                None => {
+                    copy.clear();
+                    slice.chunks().for_each(|chunk| copy.push_str(chunk));
+                    &copy
+                }
+            };
+            matches.extend(self.matches_in_line(line, text.trim_end_matches(['\n', '\r'])));
+        }
+        matches
+    }
+
+    /// Matches in the text of a line (without line break), as char columns
+    pub fn matches_in_line(&self, line: usize, text: &str) -> Vec<SearchMatch> {
+        // Byte offsets to char columns, counting forward
+        let (mut byte, mut column) = (0, 0);
+        let mut to_column = |offset: usize| {
+            column += text[byte..offset].chars().count();
+            byte = offset;
+            column
+        };
+        self.find_in_line(text)
+            .map(|range| {
+                let start = to_column(range.start);
+                let end = to_column(range.end);
+                SearchMatch { line, start, end }
+            })
+            .collect()
+    }
+
+    /// The matches still found at the same place in a rope (the text may
+    /// have changed since searching)
+    pub fn still_matching(&self, rope: &Rope, matches: &[SearchMatch]) -> Vec<SearchMatch> {
+        let mut kept = Vec::new();
+        for line_matches in matches.chunk_by(|a, b| a.line == b.line) {
+            let line = line_matches[0].line;
+            let found = self.find_in_lines(rope, line..line + 1);
+            kept.extend(line_matches.iter().filter(|m| found.contains(m)));
+        }
+        kept
+    }
+
+    /// Replacement text of a match at byte range `range` of a line
+    pub fn replacement_text(
+        &self,
+        line: &str,
+        range: Range<usize>,
+        replacement: &Replacement,
+    ) -> String {
+        let mut text = String::new();
+        match self.regex.captures_at(line, range.start) {
+            Some(captures) if self.is_regex => captures.expand(&replacement.template, &mut text),
+            _ => text.push_str(&replacement.template),
+        }
+        if replacement.preserve_case {
+            text = preserve_case(&line[range], &text);
+        }
+        text
+    }
+
+    /// Replacement text of a match in a rope
+    pub fn replacement_for(
+        &self,
+        rope: &Rope,
+        m: &SearchMatch,
+        replacement: &Replacement,
+    ) -> String {
+        let line = line_text(rope, m.line);
+        let range = byte_offset(&line, m.start)..byte_offset(&line, m.end);
+        self.replacement_text(&line, range, replacement)
+    }
+
+    /// The lines changed by replacing matches (in document order), before
+    /// and after
+    pub fn preview(
+        &self,
+        rope: &Rope,
+        matches: &[SearchMatch],
+        replacement: &Replacement,
+    ) -> Vec<LineChange> {
+        let mut changes = Vec::new();
+        for line_matches in matches.chunk_by(|a, b| a.line == b.line) {
+            let line = line_matches[0].line;
+            let before = line_text(rope, line);
+            let mut after = String::with_capacity(before.len());
+            let mut copied = 0;
+            for m in line_matches {
+                let range = byte_offset(&before, m.start)..byte_offset(&before, m.end);
+                after.push_str(&before[copied..range.start]);
+                after.push_str(&self.replacement_text(&before, range.clone(), replacement));
+                copied = range.end;
+            }
+            after.push_str(&before[copied..]);
+            changes.push(LineChange {
+                line,
+                before: before.into_owned(),
+                after,
+            });
+        }
+        changes
+    }
+}
+
+/// Text of a line without the line break
+pub(crate) fn line_text(rope: &Rope, line: usize) -> Cow<'_, str> {
+    if line >= rope.len_lines() {
+        return Cow::Borrowed("");
+    }
+    let text = Cow::from(rope.line(line));
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(text.trim_end_matches(['\n', '\r'])),
+        Cow::Owned(text) => Cow::Owned(text.trim_end_matches(['\n', '\r']).to_string()),
+    }
+}
+
+/// Byte offset of a char column
+fn byte_offset(text: &str, column: usize) -> usize {
+    text.char_indices()
+        .nth(column)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+/// Adapt a replacement to the case of the replaced text: all uppercase stays
+/// all uppercase, otherwise the first letter's case is taken over (so
+/// `user` -> `customerId` turns `User` into `CustomerId`)
+fn preserve_case(replaced: &str, replacement: &str) -> String {
+    let is_upper = |c: &char| c.is_uppercase();
+    let letters: Vec<char> = replaced.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(is_upper) {
+        return replacement.to_uppercase();
+    }
+    let mut chars = replacement.chars();
+    match (letters.first(), chars.next()) {
+        (Some(first), Some(c)) if first.is_uppercase() => c.to_uppercase().chain(chars).collect(),
+        (Some(first), Some(c)) if first.is_lowercase() => c.to_lowercase().chain(chars).collect(),
+        _ => replacement.to_string(),
+    }
+}
+
+impl SearchMatch {
+    /// Start position
+    pub fn start_position(&self) -> Position {
+        Position::new(self.line, self.start)
+    }
+
+    /// End position
+    pub fn end_position(&self) -> Position {
+        Position::new(self.line, self.end)
+    }
+
+    /// Check if the match lies within a range
+    pub fn is_within(&self, start: Position, end: Position) -> bool {
+        self.start_position() >= start && self.end_position() <= end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(text: &str, pattern: &str, options: SearchOptions) -> Vec<(usize, usize, usize)> {
+        let query = SearchQuery::new(pattern, options, &WordChars::for_extension("php")).unwrap();
+        query
+            .find_all(&Rope::from_str(text))
+            .into_iter()
+            .map(|m| (m.line, m.start, m.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_literal_and_case() {
+        let text = "Füße foo\nFOO.foo()";
+        let options = SearchOptions::default();
+        assert_eq!(
+            find(text, "foo", options),
+            [(0, 5, 8), (1, 0, 3), (1, 4, 7)]
+        );
+        // Literal: `.` and `(` are no regex syntax
+        assert_eq!(find(text, "o.f", options), [(1, 2, 5)]);
+        assert_eq!(find(text, "()", options), [(1, 7, 9)]);
+
+        let case = SearchOptions {
+            case_sensitive: true,
+            ..options
+        };
+        assert_eq!(find(text, "FOO", case), [(1, 0, 3)]);
+    }
+
+    #[test]
+    fn test_whole_word_and_regex() {
+        let text = "$user = $users[0]; user";
+        let whole = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+        // `$` is a word character in PHP
+        assert_eq!(find(text, "$user", whole), [(0, 0, 5)]);
+        assert_eq!(find(text, "user", whole), [(0, 19, 23)]);
+
+        let regex = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        assert_eq!(find(text, r"\$\w+", regex), [(0, 0, 5), (0, 8, 14)]);
+        // Empty matches are skipped
+        assert_eq!(find(text, "x*", regex), []);
+        assert!(SearchQuery::new("(", regex, &WordChars::default()).is_err());
+    }
+
+    #[test]
+    fn test_replacement() {
+        let rope = Rope::from_str("$user = getUser();\nUSER_ID");
+        let regex = SearchOptions {
+            regex: true,
+            ..SearchOptions::default()
+        };
+        let query = SearchQuery::new(r"get(\w+)\(\)", regex, &WordChars::default()).unwrap();
+        let matches = query.find_all(&rope);
+        let replacement = Replacement {
+            template: String::from("find${1}ById($$id)"),
+            preserve_case: false,
+        };
+        assert_eq!(
+            query.replacement_for(&rope, &matches[0], &replacement),
+            "findUserById($id)"
+        );
+
+        // Literal queries don't expand `$`
+        let query =
+            SearchQuery::new("user", SearchOptions::default(), &WordChars::default()).unwrap();
+        let matches = query.find_all(&rope);
+        let replacement = Replacement {
+            template: String::from("$customer"),
+            preserve_case: false,
+        };
+        assert_eq!(
+            query.replacement_for(&rope, &matches[0], &replacement),
+            "$customer"
+        );
+
+        // Preserve case
+        let replacement = Replacement {
+            template: String::from("customer"),
+            preserve_case: true,
+        };
+        assert_eq!(
+            query.preview(&rope, &matches, &replacement),
+            [
+                LineChange {
+                    line: 0,
+                    before: String::from("$user = getUser();"),
+                    after: String::from("$customer = getCustomer();"),
+                },
+                LineChange {
+                    line: 1,
+                    before: String::from("USER_ID"),
+                    after: String::from("CUSTOMER_ID"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lines_across_chunks() {
+        // Long lines are split over several rope chunks
+        let line = format!("{}needle{}\n", "a".repeat(3000), "b".repeat(3000));
+        let text = line.repeat(20);
+        let rope = Rope::from_str(&text);
+        assert!((0..20).any(|i| rope.line(i).as_str().is_none()));
+
+        let query =
+            SearchQuery::new("needle", SearchOptions::default(), &WordChars::default()).unwrap();
+        let matches = query.find_all(&rope);
+        assert_eq!(matches.len(), 20);
+        assert!(matches.iter().all(|m| m.start == 3000 && m.end == 3006));
+    }
+
+    #[test]
+    fn test_still_matching() {
+        let query =
+            SearchQuery::new("foo", SearchOptions::default(), &WordChars::default()).unwrap();
+        let matches = query.find_all(&Rope::from_str("foo foo\nbar\nfoo"));
+        assert_eq!(matches.len(), 3);
+
+        // The first line was edited, the last one is gone
+        let rope = Rope::from_str("foo xfoo\nbar");
+        assert_eq!(query.still_matching(&rope, &matches), [matches[0]]);
+    }
+}
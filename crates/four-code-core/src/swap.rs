@@ -0,0 +1,294 @@
+//! Crash recovery swap files
+//!
+//! While a buffer has unsaved changes, a snapshot of its content is written
+//! to a recovery directory (debounced by the caller). The swap file is
+//! removed when the buffer is saved or closed normally, so a swap file found
+//! on startup means the editor crashed or the terminal died - unless the
+//! process that wrote it is still running (the file is open in another
+//! instance), in which case it is left alone.
+//!
+//! Layout: `<data_dir>/four-code/swap/<file-hash>.json`
+
+use crate::hash::fnv1a;
+use crate::save::create_private_dir;
+use crate::Buffer;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+/// Format version of swap files (bump on incompatible changes)
+const SWAP_FILE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SwapStoreError {
+    #[error("No data directory available for swap files")]
+    NoDataDir,
+
+    #[error("Failed to access swap file: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid swap file: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// On-disk representation of a swap file
+#[derive(Serialize, Deserialize)]
+struct SwapFile {
+    version: u32,
+    /// Canonical path of the edited file
+    path: PathBuf,
+    /// Process that wrote the swap file
+    pid: u32,
+    /// Buffer content (`\n` line endings)
+    content: String,
+}
+
+/// A recoverable buffer snapshot
+#[derive(Debug, Clone)]
+pub struct Swap {
+    /// Path of the edited file
+    pub path: PathBuf,
+    /// Unsaved content
+    pub content: String,
+    /// Process that wrote the snapshot
+    pub pid: u32,
+    /// When the snapshot was written
+    pub modified: Option<SystemTime>,
+}
+
+/// Directory holding swap files
+#[derive(Debug, Clone)]
+pub struct SwapStore {
+    /// Root directory
+    root: PathBuf,
+}
+
+impl SwapStore {
+    /// Create a store rooted at a directory
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Create a store in the user's data directory
+    pub fn open_default() -> Result<Self, SwapStoreError> {
+        let data_dir = dirs::data_dir().ok_or(SwapStoreError::NoDataDir)?;
+        Ok(Self::new(data_dir.join("four-code").join("swap")))
+    }
+
+    /// Get the root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write a snapshot of a buffer
+    ///
+    /// An unmodified buffer has nothing to recover: its swap file is removed
+    /// instead. Returns `false` if the buffer has no file path.
+    pub fn write(&self, buffer: &Buffer) -> Result<bool, SwapStoreError> {
+        let Some(path) = buffer.path() else {
+            return Ok(false);
+        };
+        if !buffer.is_modified() {
+            self.remove(path)?;
+            return Ok(true);
+        }
+
+        let path = canonical(path);
+        let swap_path = self.swap_file_path(&path);
+        let file = SwapFile {
+            version: SWAP_FILE_VERSION,
+            path,
+            pid: std::process::id(),
+            content: buffer.text(),
+        };
+
+        create_private_dir(&self.root)?;
+        let tmp_path = swap_path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&file)?)?;
+        fs::rename(&tmp_path, &swap_path)?;
+        Ok(true)
+    }
+
+    /// Remove the swap file of a file (if any)
+    pub fn remove(&self, path: &Path) -> Result<(), SwapStoreError> {
+        match fs::remove_file(self.swap_file_path(&canonical(path))) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Find the swap file of a file
+    ///
+    /// None if the process that wrote it is still running.
+    pub fn find(&self, path: &Path) -> Result<Option<Swap>, SwapStoreError> {
+        let path = canonical(path);
+        match Self::read(&self.swap_file_path(&path)) {
+            Ok(swap) if swap.path == path && !is_running(swap.pid) => Ok(Some(swap)),
+            Ok(_) => Ok(None),
+            Err(SwapStoreError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// List all recoverable swap files, newest first
+    ///
+    /// Unreadable swap files and those of running processes are skipped.
+    pub fn list(&self) -> Result<Vec<Swap>, SwapStoreError> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut swaps = Vec::new();
+        for entry in entries {
+            let swap_path = entry?.path();
+            if swap_path.extension().is_some_and(|ext| ext == "json") {
+                match Self::read(&swap_path) {
+                    Ok(swap) if !is_running(swap.pid) => swaps.push(swap),
+                    _ => {}
+                }
+            }
+        }
+        swaps.sort_by_key(|swap| std::cmp::Reverse(swap.modified));
+        Ok(swaps)
+    }
+
+    /// Read a swap file
+    fn read(swap_path: &Path) -> Result<Swap, SwapStoreError> {
+        let json = fs::read(swap_path)?;
+        let modified = fs::metadata(swap_path).and_then(|m| m.modified()).ok();
+        let file: SwapFile = serde_json::from_slice(&json)?;
+        if file.version != SWAP_FILE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported version").into());
+        }
+        Ok(Swap {
+            path: file.path,
+            content: file.content,
+            pid: file.pid,
+            modified,
+        })
+    }
+
+    /// Path of the swap file for a canonical file path
+    fn swap_file_path(&self, path: &Path) -> PathBuf {
+        let hash = fnv1a(path.to_string_lossy().bytes());
+        self.root.join(format!("{hash:016x}.json"))
+    }
+}
+
+/// Check if a process is running (this one included)
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // 0 and negative values address process groups, not a process
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if pid <= 0 {
+        return false;
+    }
+    // SAFETY: signal 0 only checks whether the process exists
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // Exists, but belongs to another user
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(pid: u32) -> bool {
+    pid == std::process::id()
+}
+
+/// Canonical path of a file (the path itself if it can't be resolved)
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Editor;
+
+    /// Higher than any PID the system hands out
+    const DEAD_PID: u32 = i32::MAX as u32;
+
+    /// Pretend the process that wrote the swap file of `path` crashed
+    fn crash(store: &SwapStore, path: &Path) {
+        let swap_path = store.swap_file_path(&canonical(path));
+        let mut file: SwapFile = serde_json::from_slice(&fs::read(&swap_path).unwrap()).unwrap();
+        file.pid = DEAD_PID;
+        fs::write(&swap_path, serde_json::to_vec(&file).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_write_find_and_recover() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.php");
+        fs::write(&path, "<?php\n").unwrap();
+        let store = SwapStore::new(dir.path().join("swap"));
+
+        let mut editor = Editor::open(&path).unwrap();
+        editor.move_to_end();
+        editor.insert_str("echo 'unsaved';\n");
+        assert!(store.write(&editor.buffer).unwrap());
+
+        // Not offered while the writing process (this one) is running
+        assert!(store.find(&path).unwrap().is_none());
+        assert!(store.list().unwrap().is_empty());
+
+        // Crash: reopen from disk and recover
+        crash(&store, &path);
+        let mut editor = Editor::open(&path).unwrap();
+        let swap = store.find(&path).unwrap().unwrap();
+        assert_eq!(swap.pid, DEAD_PID);
+        editor.replace_all(&swap.content);
+        assert_eq!(editor.buffer.text(), "<?php\necho 'unsaved';\n");
+        assert!(editor.is_modified());
+
+        // Undo goes back to the content on disk
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "<?php\n");
+    }
+
+    #[test]
+    fn test_unmodified_buffer_removes_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.php");
+        fs::write(&path, "<?php\n").unwrap();
+        let store = SwapStore::new(dir.path().join("swap"));
+
+        let mut editor = Editor::open(&path).unwrap();
+        editor.insert_char('#');
+        store.write(&editor.buffer).unwrap();
+        crash(&store, &path);
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        editor.undo();
+        store.write(&editor.buffer).unwrap();
+        assert!(store.find(&path).unwrap().is_none());
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_swap_directory_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        fs::write(&path, "SECRET=1\n").unwrap();
+        let store = SwapStore::new(dir.path().join("state").join("swap"));
+
+        let mut editor = Editor::open(&path).unwrap();
+        editor.insert_char('#');
+        store.write(&editor.buffer).unwrap();
+        for dir in [store.root(), store.root().parent().unwrap()] {
+            let mode = fs::metadata(dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+    }
+}
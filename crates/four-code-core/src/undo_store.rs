@@ -0,0 +1,363 @@
+//! Persistent undo history
+//!
+//! Works like Vim's undofile: when a file is saved its undo history is written
+//! to a per-project state directory, and it is restored when the file is
+//! reopened - but only if the content still matches what was saved.
+//!
+//! Layout: `<data_dir>/four-code/undo/<project-hash>/<file-hash>.json`
+
+use crate::hash::fnv1a;
+use crate::save::create_private_dir;
+use crate::{Buffer, History};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+/// Format version of undo files (bump on incompatible changes)
+const UNDO_FILE_VERSION: u32 = 2;
+
+/// Default size cap for a single undo file (1 MiB)
+pub const DEFAULT_MAX_UNDO_FILE_SIZE: usize = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum UndoStoreError {
+    #[error("No data directory available for undo history")]
+    NoDataDir,
+
+    #[error("Failed to access undo history: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid undo history file: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// On-disk representation of a file's undo history
+#[derive(Serialize, Deserialize)]
+struct UndoFile {
+    version: u32,
+    /// Canonical path of the edited file
+    path: PathBuf,
+    /// Content hash of the buffer when the history was written
+    content_hash: u64,
+    history: History,
+}
+
+/// Just the header of an undo file (avoids parsing the history when purging)
+#[derive(Deserialize)]
+struct UndoFileHeader {
+    path: PathBuf,
+}
+
+/// Result of purging stale undo files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PurgeStats {
+    /// Number of undo files removed
+    pub removed: usize,
+    /// Number of undo files kept
+    pub kept: usize,
+}
+
+/// Directory holding persisted undo histories
+#[derive(Debug, Clone)]
+pub struct UndoStore {
+    /// Root directory (contains one directory per project)
+    root: PathBuf,
+    /// Maximum size of a single undo file in bytes
+    max_file_size: usize,
+}
+
+impl UndoStore {
+    /// Create a store rooted at a directory
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_file_size: DEFAULT_MAX_UNDO_FILE_SIZE,
+        }
+    }
+
+    /// Create a store in the user's data directory
+    pub fn open_default() -> Result<Self, UndoStoreError> {
+        let data_dir = dirs::data_dir().ok_or(UndoStoreError::NoDataDir)?;
+        Ok(Self::new(data_dir.join("four-code").join("undo")))
+    }
+
+    /// Set the size cap for a single undo file
+    ///
+    /// Oldest transactions are dropped until the history fits.
+    pub fn with_max_file_size(mut self, bytes: usize) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Get the root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write the undo history of a buffer
+    ///
+    /// Call this after the buffer has been saved. Returns `false` if the
+    /// buffer has no file path.
+    pub fn persist(&self, buffer: &Buffer) -> Result<bool, UndoStoreError> {
+        let Some(path) = buffer.path() else {
+            return Ok(false);
+        };
+        let path = path.canonicalize()?;
+        let undo_path = self.undo_file_path(&path);
+
+        let history = buffer.history();
+        if !history.can_undo() && !history.can_redo() {
+            // Nothing worth keeping - drop any previous history
+            if undo_path.exists() {
+                fs::remove_file(&undo_path)?;
+            }
+            return Ok(true);
+        }
+
+        let mut file = UndoFile {
+            version: UNDO_FILE_VERSION,
+            path,
+            content_hash: buffer.content_hash(),
+            history: history.clone(),
+        };
+        let json = self.serialize_capped(&mut file)?;
+
+        if let Some(dir) = undo_path.parent() {
+            create_private_dir(dir)?;
+        }
+        let tmp_path = undo_path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, &undo_path)?;
+        Ok(true)
+    }
+
+    /// Restore the undo history of a freshly opened buffer
+    ///
+    /// Returns `true` if a history was restored. A history whose content hash
+    /// does not match the buffer (the file was changed outside of four-code)
+    /// is ignored.
+    pub fn restore(&self, buffer: &mut Buffer) -> Result<bool, UndoStoreError> {
+        let Some(path) = buffer.path() else {
+            return Ok(false);
+        };
+        let path = path.canonicalize()?;
+        let undo_path = self.undo_file_path(&path);
+
+        let json = match fs::read(&undo_path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        let file: UndoFile = serde_json::from_slice(&json)?;
+
+        if file.version != UNDO_FILE_VERSION
+            || file.path != path
+            || file.content_hash != buffer.content_hash()
+        {
+            return Ok(false);
+        }
+
+        let mut history = file.history;
+        history.mark_saved();
+        buffer.set_history(history);
+        Ok(true)
+    }
+
+    /// Remove stale undo files
+    ///
+    /// An undo file is stale if its source file no longer exists, was modified
+    /// after the history was written, if it is older than `max_age`, or if it
+    /// cannot be read.
+    pub fn purge_stale(&self, max_age: Duration) -> Result<PurgeStats, UndoStoreError> {
+        let mut stats = PurgeStats::default();
+        let project_dirs = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(stats),
+            Err(e) => return Err(e.into()),
+        };
+
+        for project_dir in project_dirs {
+            let project_dir = project_dir?.path();
+            if !project_dir.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(&project_dir)? {
+                let undo_path = entry?.path();
+                if Self::is_stale(&undo_path, max_age) {
+                    fs::remove_file(&undo_path)?;
+                    stats.removed += 1;
+                } else {
+                    stats.kept += 1;
+                }
+            }
+
+            // Only succeeds if the directory is now empty
+            let _ = fs::remove_dir(&project_dir);
+        }
+
+        Ok(stats)
+    }
+
+    /// Check if an undo file is stale
+    fn is_stale(undo_path: &Path, max_age: Duration) -> bool {
+        let Ok(undo_modified) = fs::metadata(undo_path).and_then(|m| m.modified()) else {
+            return true;
+        };
+        let age = SystemTime::now()
+            .duration_since(undo_modified)
+            .unwrap_or_default();
+        if age > max_age {
+            return true;
+        }
+
+        let header: Option<UndoFileHeader> = fs::read(undo_path)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok());
+        let Some(header) = header else {
+            return true;
+        };
+
+        match fs::metadata(&header.path).and_then(|m| m.modified()) {
+            Ok(source_modified) => source_modified > undo_modified,
+            Err(_) => true,
+        }
+    }
+
+    /// Serialize an undo file, dropping old transactions until it fits the cap
+    fn serialize_capped(&self, file: &mut UndoFile) -> Result<Vec<u8>, UndoStoreError> {
+        loop {
+            let json = serde_json::to_vec(file)?;
+            if json.len() <= self.max_file_size {
+                return Ok(json);
+            }
+
+            let history = &mut file.history;
+            if history.can_redo() {
+                history.discard_redo();
+            } else if history.undo_len() > 0 {
+                history.discard_oldest(history.undo_len().div_ceil(2));
+            } else {
+                return Ok(json);
+            }
+        }
+    }
+
+    /// Path of the undo file for a canonical file path
+    fn undo_file_path(&self, path: &Path) -> PathBuf {
+        let project = project_root(path);
+        self.root
+            .join(format!("{:016x}", path_hash(&project)))
+            .join(format!("{:016x}.json", path_hash(path)))
+    }
+}
+
+/// Find the project root of a file: the nearest ancestor containing `.git`,
+/// falling back to the file's directory
+fn project_root(path: &Path) -> PathBuf {
+    let dir = path.parent().unwrap_or(path);
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+/// Stable hash of a path
+fn path_hash(path: &Path) -> u64 {
+    fnv1a(path.to_string_lossy().bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Editor;
+
+    fn edit_and_save(path: &Path, store: &UndoStore) {
+        let mut editor = Editor::open(path).unwrap();
+        editor.move_to_end();
+        for ch in " World".chars() {
+            editor.type_char(ch);
+        }
+        editor.save().unwrap();
+        assert!(store.persist(&editor.buffer).unwrap());
+    }
+
+    #[test]
+    fn test_persist_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.php");
+        fs::write(&path, "Hello").unwrap();
+        let store = UndoStore::new(dir.path().join("store"));
+
+        edit_and_save(&path, &store);
+
+        let mut editor = Editor::open(&path).unwrap();
+        assert!(store.restore(&mut editor.buffer).unwrap());
+        assert!(!editor.is_modified());
+
+        assert!(editor.undo());
+        assert_eq!(editor.buffer.text(), "Hello");
+        assert_eq!(editor.cursor.position.column, 5);
+    }
+
+    #[test]
+    fn test_restore_ignores_changed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.php");
+        fs::write(&path, "Hello").unwrap();
+        let store = UndoStore::new(dir.path().join("store"));
+
+        edit_and_save(&path, &store);
+        fs::write(&path, "Changed elsewhere").unwrap();
+
+        let mut editor = Editor::open(&path).unwrap();
+        assert!(!store.restore(&mut editor.buffer).unwrap());
+        assert!(!editor.buffer.can_undo());
+    }
+
+    #[test]
+    fn test_size_cap_drops_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.txt");
+        fs::write(&path, "").unwrap();
+        let store = UndoStore::new(dir.path().join("store")).with_max_file_size(600);
+
+        let mut editor = Editor::open(&path).unwrap();
+        for i in 0..20 {
+            editor.replace_selection(&format!("line {i}\n"));
+        }
+        editor.save().unwrap();
+        store.persist(&editor.buffer).unwrap();
+
+        let mut editor = Editor::open(&path).unwrap();
+        assert!(store.restore(&mut editor.buffer).unwrap());
+        let undo_len = editor.buffer.history().undo_len();
+        assert!(undo_len > 0 && undo_len < 20);
+    }
+
+    #[test]
+    fn test_purge_removes_missing_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.php");
+        let deleted = dir.path().join("deleted.php");
+        fs::write(&kept, "Hello").unwrap();
+        fs::write(&deleted, "Hello").unwrap();
+        let store = UndoStore::new(dir.path().join("store"));
+
+        edit_and_save(&kept, &store);
+        edit_and_save(&deleted, &store);
+        fs::remove_file(&deleted).unwrap();
+
+        let stats = store.purge_stale(Duration::from_secs(3600)).unwrap();
+        assert_eq!(
+            stats,
+            PurgeStats {
+                removed: 1,
+                kept: 1
+            }
+        );
+    }
+}
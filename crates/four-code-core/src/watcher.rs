@@ -0,0 +1,138 @@
+//! Watching open files for external modifications
+//!
+//! `composer install`, `git checkout` or a formatter can rewrite a file while
+//! it is open. The watcher reports such files (debounced), and the buffer's
+//! disk state tells whether the content really changed.
+//!
+//! Parent directories are watched instead of the files themselves, so files
+//! replaced by a rename (atomic saves, git) keep being watched.
+
+use notify_debouncer_mini::notify::{self, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Events for a file within this time are reported once
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Error, Debug)]
+pub enum WatchError {
+    #[error("Failed to watch files: {0}")]
+    Notify(#[from] notify::Error),
+}
+
+/// Watches a set of files for changes
+pub struct FileWatcher {
+    /// Debounced notify watcher
+    debouncer: Debouncer<RecommendedWatcher>,
+    /// Debounced events
+    events: Receiver<DebounceEventResult>,
+    /// Watched files (canonical paths)
+    files: HashSet<PathBuf>,
+    /// Watched directories with the number of watched files in them
+    dirs: HashMap<PathBuf, usize>,
+}
+
+impl FileWatcher {
+    /// Create a watcher (watching nothing yet)
+    pub fn new() -> Result<Self, WatchError> {
+        let (sender, events) = channel();
+        Ok(Self {
+            debouncer: new_debouncer(DEBOUNCE_TIMEOUT, sender)?,
+            events,
+            files: HashSet::new(),
+            dirs: HashMap::new(),
+        })
+    }
+
+    /// Start watching a file
+    pub fn watch(&mut self, path: &Path) -> Result<(), WatchError> {
+        let path = canonical(path);
+        if self.files.contains(&path) {
+            return Ok(());
+        }
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return Ok(());
+        };
+
+        if !self.dirs.contains_key(&dir) {
+            self.debouncer
+                .watcher()
+                .watch(&dir, RecursiveMode::NonRecursive)?;
+        }
+        *self.dirs.entry(dir).or_default() += 1;
+        self.files.insert(path);
+        Ok(())
+    }
+
+    /// Stop watching a file
+    pub fn unwatch(&mut self, path: &Path) {
+        let path = canonical(path);
+        if !self.files.remove(&path) {
+            return;
+        }
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        if let Some(count) = self.dirs.get_mut(dir) {
+            *count -= 1;
+            if *count == 0 {
+                self.dirs.remove(dir);
+                let _ = self.debouncer.watcher().unwatch(dir);
+            }
+        }
+    }
+
+    /// Watched files that changed since the last call (non-blocking)
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        while let Ok(result) = self.events.try_recv() {
+            // Watcher errors (e.g. a removed directory) are not fatal
+            let Ok(events) = result else {
+                continue;
+            };
+            for event in events {
+                if self.files.contains(&event.path) && !changed.contains(&event.path) {
+                    changed.push(event.path);
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// Canonical path of a file (the path itself if it can't be resolved)
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Instant;
+
+    #[test]
+    fn test_reports_changed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let watched = dir.path().join("composer.json");
+        let other = dir.path().join("other.txt");
+        fs::write(&watched, "{}").unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(&watched).unwrap();
+        fs::write(&other, "ignored").unwrap();
+        fs::write(&watched, "{\"require\": {}}").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut changed = Vec::new();
+        while changed.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+            changed = watcher.changed_files();
+        }
+        assert_eq!(changed, vec![watched.canonicalize().unwrap()]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Word and subword boundaries
+//!
+//! Words are runs of word characters (letters, digits, `_` and per-language
+//! extras like PHP's `$` or CSS's `-`) or runs of punctuation. Subwords split
+//! words at case changes and separators, so `$userRepository` and
+//! `user_repository` have a stop before `Repository`/`repository`.
+//!
+//! Functions take the chars of one line (without line break) and a column.
+
+/// Characters that belong to words besides letters, digits and `_`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordChars {
+    extra: Vec<char>,
+}
+
+/// Kind of a character for word motions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+impl WordChars {
+    /// Create from a string of extra word characters (e.g. `"$"`)
+    pub fn new(extra: &str) -> Self {
+        Self {
+            extra: extra.chars().collect(),
+        }
+    }
+
+    /// Built-in word characters for a file extension
+    pub fn for_extension(extension: &str) -> Self {
+        match extension {
+            "php" | "phtml" => Self::new("$"),
+            "css" | "scss" | "less" => Self::new("-"),
+            _ => Self::default(),
+        }
+    }
+
+    /// Check if a character is part of a word
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.extra.contains(&c)
+    }
+
+    /// Classify a character
+    fn class(&self, c: char) -> CharClass {
+        if c.is_whitespace() {
+            CharClass::Whitespace
+        } else if self.is_word_char(c) {
+            CharClass::Word
+        } else {
+            CharClass::Punctuation
+        }
+    }
+}
+
+/// Column after the end of the next word (or punctuation run)
+pub fn next_word_end(chars: &[char], column: usize, word_chars: &WordChars) -> usize {
+    let mut i = skip_whitespace_forward(chars, column);
+    if let Some(&c) = chars.get(i) {
+        let class = word_chars.class(c);
+        while i < chars.len() && word_chars.class(chars[i]) == class {
+            i += 1;
+        }
+    }
+    i
+}
+
+/// Column of the start of the previous word (or punctuation run)
+pub fn prev_word_start(chars: &[char], column: usize, word_chars: &WordChars) -> usize {
+    let mut i = skip_whitespace_backward(chars, column);
+    if i > 0 {
+        let class = word_chars.class(chars[i - 1]);
+        while i > 0 && word_chars.class(chars[i - 1]) == class {
+            i -= 1;
+        }
+    }
+    i
+}
+
+/// Column after the end of the next subword
+///
+/// Outside of words this moves like [`next_word_end`].
+pub fn next_subword_end(chars: &[char], column: usize, word_chars: &WordChars) -> usize {
+    let start = skip_whitespace_forward(chars, column);
+    if !chars
+        .get(start)
+        .is_some_and(|&c| word_chars.is_word_char(c))
+    {
+        return next_word_end(chars, column, word_chars);
+    }
+
+    // Leading separators belong to the subword after them
+    let mut i = start;
+    while i < chars.len() && is_separator(chars[i], word_chars) {
+        i += 1;
+    }
+    if i < chars.len() && word_chars.is_word_char(chars[i]) {
+        i += 1;
+    }
+    while i < chars.len() && word_chars.is_word_char(chars[i]) && !is_subword_start(chars, i) {
+        i += 1;
+    }
+    i
+}
+
+/// Column of the start of the previous subword
+///
+/// Outside of words this moves like [`prev_word_start`].
+pub fn prev_subword_start(chars: &[char], column: usize, word_chars: &WordChars) -> usize {
+    let end = skip_whitespace_backward(chars, column);
+    if end == 0 || !word_chars.is_word_char(chars[end - 1]) {
+        return prev_word_start(chars, column, word_chars);
+    }
+
+    // Separators right before the cursor, then the subword before them
+    let mut i = end;
+    while i > 0 && is_separator(chars[i - 1], word_chars) {
+        i -= 1;
+    }
+    if i > 0 && word_chars.is_word_char(chars[i - 1]) {
+        i -= 1;
+    }
+    while i > 0 && word_chars.is_word_char(chars[i - 1]) && !is_subword_start(chars, i) {
+        i -= 1;
+    }
+    // Leading separators belong to the subword after them
+    while i > 0 && is_separator(chars[i - 1], word_chars) {
+        i -= 1;
+    }
+    i
+}
+
+/// Word characters that separate subwords (`_`, `$`, `-`, ...)
+fn is_separator(c: char, word_chars: &WordChars) -> bool {
+    word_chars.is_word_char(c) && !c.is_alphanumeric()
+}
+
+/// Check if a subword starts at `i` (within a word)
+fn is_subword_start(chars: &[char], i: usize) -> bool {
+    let Some(&c) = chars.get(i) else {
+        return false;
+    };
+    if i == 0 {
+        return true;
+    }
+    let prev = chars[i - 1];
+    if !c.is_alphanumeric() {
+        // A separator after letters
+        return prev.is_alphanumeric();
+    }
+    if !prev.is_alphanumeric() {
+        return true;
+    }
+    // camelCase, and the last capital of an acronym (`HTTPServer`)
+    c.is_uppercase() && (!prev.is_uppercase() || chars.get(i + 1).is_some_and(|n| n.is_lowercase()))
+}
+
+fn skip_whitespace_forward(chars: &[char], column: usize) -> usize {
+    let mut i = column.min(chars.len());
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+fn skip_whitespace_backward(chars: &[char], column: usize) -> usize {
+    let mut i = column.min(chars.len());
+    while i > 0 && chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let php = WordChars::for_extension("php");
+        let line = chars("    $user->name = 'x';");
+
+        assert_eq!(next_word_end(&line, 0, &php), 9);
+        assert_eq!(next_word_end(&line, 9, &php), 11);
+        assert_eq!(next_word_end(&line, 11, &php), 15);
+        assert_eq!(prev_word_start(&line, 15, &php), 11);
+        assert_eq!(prev_word_start(&line, 11, &php), 9);
+        assert_eq!(prev_word_start(&line, 9, &php), 4);
+
+        // Without `$` as a word char, it is punctuation
+        let plain = WordChars::default();
+        assert_eq!(next_word_end(&line, 0, &plain), 5);
+    }
+
+    #[test]
+    fn test_subword_motions() {
+        let php = WordChars::for_extension("php");
+        let line = chars("$userRepository HTTPServer user_id");
+
+        assert_eq!(next_subword_end(&line, 0, &php), 5);
+        assert_eq!(next_subword_end(&line, 5, &php), 15);
+        assert_eq!(next_subword_end(&line, 15, &php), 20);
+        assert_eq!(next_subword_end(&line, 20, &php), 26);
+        assert_eq!(next_subword_end(&line, 26, &php), 31);
+        assert_eq!(next_subword_end(&line, 31, &php), 34);
+
+        assert_eq!(prev_subword_start(&line, 34, &php), 31);
+        assert_eq!(prev_subword_start(&line, 31, &php), 27);
+        assert_eq!(prev_subword_start(&line, 26, &php), 20);
+        assert_eq!(prev_subword_start(&line, 20, &php), 16);
+        assert_eq!(prev_subword_start(&line, 15, &php), 5);
+        assert_eq!(prev_subword_start(&line, 5, &php), 0);
+    }
+
+    #[test]
+    fn test_css_word_chars() {
+        let css = WordChars::for_extension("css");
+        let line = chars("margin-top: 0;");
+        assert_eq!(next_word_end(&line, 0, &css), 10);
+        assert_eq!(next_subword_end(&line, 0, &css), 6);
+    }
+}
@@ -27,7 +27,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Php,
-            tree_sitter_php::LANGUAGE_PHP.into(),
             tree_sitter_php::HIGHLIGHTS_QUERY,
             tree_sitter_php::INJECTIONS_QUERY,
             &highlight_names,
@@ -37,7 +36,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::JavaScript,
-            tree_sitter_javascript::LANGUAGE.into(),
             tree_sitter_javascript::HIGHLIGHT_QUERY,
             tree_sitter_javascript::INJECTIONS_QUERY,
             &highlight_names,
@@ -47,7 +45,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::TypeScript,
-            tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
             tree_sitter_typescript::HIGHLIGHTS_QUERY,
             "",
             &highlight_names,
@@ -57,7 +54,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Tsx,
-            tree_sitter_typescript::LANGUAGE_TSX.into(),
             tree_sitter_typescript::HIGHLIGHTS_QUERY,
             "",
             &highlight_names,
@@ -67,7 +63,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Json,
-            tree_sitter_json::LANGUAGE.into(),
             tree_sitter_json::HIGHLIGHTS_QUERY,
             "",
             &highlight_names,
@@ -77,7 +72,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Html,
-            tree_sitter_html::LANGUAGE.into(),
             tree_sitter_html::HIGHLIGHTS_QUERY,
             tree_sitter_html::INJECTIONS_QUERY,
             &highlight_names,
@@ -87,7 +81,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Css,
-            tree_sitter_css::LANGUAGE.into(),
             tree_sitter_css::HIGHLIGHTS_QUERY,
             "",
             &highlight_names,
@@ -97,7 +90,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Yaml,
-            tree_sitter_yaml::LANGUAGE.into(),
             tree_sitter_yaml::HIGHLIGHTS_QUERY,
             "",
             &highlight_names,
@@ -107,7 +99,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Toml,
-            tree_sitter_toml_ng::LANGUAGE.into(),
             tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
             "",
             &highlight_names,
@@ -117,7 +108,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Bash,
-            tree_sitter_bash::LANGUAGE.into(),
             tree_sitter_bash::HIGHLIGHT_QUERY,
             "",
             &highlight_names,
@@ -127,7 +117,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Markdown,
-            tree_sitter_md::LANGUAGE.into(),
             tree_sitter_md::HIGHLIGHT_QUERY_BLOCK,
             tree_sitter_md::INJECTION_QUERY_BLOCK,
             &highlight_names,
@@ -137,7 +126,6 @@ impl Highlighter {
         Self::load_config(
             &mut configs,
             Language::Rust,
-            tree_sitter_rust::LANGUAGE.into(),
             tree_sitter_rust::HIGHLIGHTS_QUERY,
             "",
             &highlight_names,
@@ -153,13 +141,12 @@ impl Highlighter {
     fn load_config(
         configs: &mut HashMap<Language, HighlightConfiguration>,
         language: Language,
-        ts_language: tree_sitter::Language,
         highlights_query: &str,
         injections_query: &str,
         highlight_names: &[String],
     ) {
         match HighlightConfiguration::new(
-            ts_language,
+            language.grammar(),
             language.name(),
             highlights_query,
             injections_query,
@@ -37,6 +37,24 @@ impl Language {
             Language::Rust => "rust",
         }
     }
+
+    /// Get the tree-sitter grammar
+    pub fn grammar(&self) -> tree_sitter::Language {
+        match self {
+            Language::Php => tree_sitter_php::LANGUAGE_PHP.into(),
+            Language::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Language::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Language::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Language::Json => tree_sitter_json::LANGUAGE.into(),
+            Language::Html => tree_sitter_html::LANGUAGE.into(),
+            Language::Css => tree_sitter_css::LANGUAGE.into(),
+            Language::Yaml => tree_sitter_yaml::LANGUAGE.into(),
+            Language::Toml => tree_sitter_toml_ng::LANGUAGE.into(),
+            Language::Bash => tree_sitter_bash::LANGUAGE.into(),
+            Language::Markdown => tree_sitter_md::LANGUAGE.into(),
+            Language::Rust => tree_sitter_rust::LANGUAGE.into(),
+        }
+    }
 }
 
 /// List of all supported languages
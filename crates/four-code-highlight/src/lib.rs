@@ -5,9 +5,12 @@
 
 mod highlighter;
 mod languages;
+mod syntax;
 
 pub use highlighter::{HighlightCache, Highlighter};
 pub use languages::{detect_language, Language, SUPPORTED_LANGUAGES};
+pub use syntax::{Bracket, BracketPair, SyntaxTree};
+pub use tree_sitter::{InputEdit, Point};
 
 use ratatui::style::{Color, Modifier, Style};
 use std::sync::OnceLock;
@@ -0,0 +1,528 @@
+//! Persistent syntax trees for structural editing
+//!
+//! The highlighter works line by line, but structural features (expanding
+//! the selection to the enclosing syntax node, bracket matching) need a tree
+//! of the whole buffer. A [`SyntaxTree`] keeps the last parse and reparses
+//! lazily, only when asked for a tree of a newer buffer revision. Given the
+//! edits since the last parse, the reparse is incremental: only the changed
+//! parts of the tree are parsed again.
+//!
+//! Brackets are the `()`, `[]` and `{}` tokens of the tree, so brackets in
+//! strings and comments (which are part of a string or comment token) are
+//! never matched.
+
+use crate::Language;
+use std::ops::Range;
+use tree_sitter::{InputEdit, Node, Parser, Tree};
+
+/// A matching pair of brackets (byte offsets of the bracket characters)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BracketPair {
+    pub open: usize,
+    pub close: usize,
+}
+
+/// A bracket with its nesting depth (0 for outermost brackets)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bracket {
+    pub byte: usize,
+    pub depth: usize,
+}
+
+/// Parse tree of one buffer
+pub struct SyntaxTree {
+    /// Parser, configured for the current language
+    parser: Parser,
+    /// Current language (None: no tree)
+    language: Option<Language>,
+    /// Last parse
+    tree: Option<Tree>,
+    /// Buffer revision of the last parse
+    revision: Option<u64>,
+}
+
+impl SyntaxTree {
+    /// Create an empty syntax tree (no language)
+    pub fn new() -> Self {
+        Self {
+            parser: Parser::new(),
+            language: None,
+            tree: None,
+            revision: None,
+        }
+    }
+
+    /// Set the language (drops the current tree)
+    pub fn set_language(&mut self, language: Option<Language>) {
+        if self.language == language {
+            return;
+        }
+        self.language =
+            language.filter(|language| self.parser.set_language(&language.grammar()).is_ok());
+        self.invalidate();
+    }
+
+    /// Get the current language
+    pub fn language(&self) -> Option<Language> {
+        self.language
+    }
+
+    /// Drop the current tree, forcing a reparse on the next update
+    pub fn invalidate(&mut self) {
+        self.tree = None;
+        self.revision = None;
+    }
+
+    /// Buffer revision of the last parse (None if there is no tree)
+    pub fn revision(&self) -> Option<u64> {
+        self.revision
+    }
+
+    /// Bring the tree up to date with a buffer revision
+    ///
+    /// Reparses only if the revision changed: incrementally with `edits`, the
+    /// edits since [`SyntaxTree::revision`] in order, or from scratch without
+    /// them. `read(byte)` returns the text from a byte offset on (e.g. to the
+    /// end of a rope chunk; empty at the end of the text), so the buffer is
+    /// never copied. Returns the tree, or None without a language.
+    pub fn update<T: AsRef<[u8]>>(
+        &mut self,
+        revision: u64,
+        edits: Option<impl IntoIterator<Item = InputEdit>>,
+        mut read: impl FnMut(usize) -> T,
+    ) -> Option<&Tree> {
+        self.language?;
+        if self.revision != Some(revision) {
+            let mut old = self.tree.take().filter(|_| edits.is_some());
+            if let (Some(tree), Some(edits)) = (&mut old, edits) {
+                for edit in edits {
+                    tree.edit(&edit);
+                }
+            }
+            self.tree =
+                self.parser
+                    .parse_with_options(&mut |byte, _| read(byte), old.as_ref(), None);
+            self.revision = Some(revision);
+        }
+        self.tree.as_ref()
+    }
+
+    /// Get the last parsed tree (possibly outdated)
+    pub fn tree(&self) -> Option<&Tree> {
+        self.tree.as_ref()
+    }
+
+    /// Byte range of the smallest named node that encloses a byte range and
+    /// is larger than it
+    ///
+    /// Used to grow a selection step by step: identifier, expression,
+    /// statement, block, method, class, ... up to the whole document.
+    pub fn enclosing_range(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let root = self.tree.as_ref()?.root_node();
+        let mut node = root.named_descendant_for_byte_range(range.start, range.end)?;
+        loop {
+            let node_range = node.byte_range();
+            if node_range.start <= range.start && node_range.end >= range.end && node_range != range
+            {
+                return Some(node_range);
+            }
+            node = node.parent()?;
+        }
+    }
+
+    /// Bracket pair to highlight for a cursor at a byte offset
+    ///
+    /// The pair of a bracket at or right before the cursor, else the
+    /// innermost pair around the cursor.
+    pub fn bracket_pair(&self, byte: usize) -> Option<BracketPair> {
+        let root = self.tree.as_ref()?.root_node();
+
+        // A bracket at the cursor, then one right before it
+        for start in [Some(byte), byte.checked_sub(1)].into_iter().flatten() {
+            let Some(node) = root.descendant_for_byte_range(start, start + 1) else {
+                continue;
+            };
+            if node.start_byte() != start || bracket_kind(&node).is_none() {
+                continue;
+            }
+            let pair = node.parent().and_then(|parent| {
+                child_pairs(&parent)
+                    .into_iter()
+                    .find(|pair| pair.open == start || pair.close == start)
+            });
+            if pair.is_some() {
+                return pair;
+            }
+        }
+
+        // The innermost pair enclosing the cursor
+        let mut node = root.descendant_for_byte_range(byte, byte);
+        while let Some(current) = node {
+            let enclosing = child_pairs(&current)
+                .into_iter()
+                .filter(|pair| pair.open < byte && pair.close >= byte)
+                .max_by_key(|pair| pair.open);
+            if enclosing.is_some() {
+                return enclosing;
+            }
+            node = current.parent();
+        }
+        None
+    }
+
+    /// Check if a byte offset is inside a string or comment
+    ///
+    /// A cursor right after a line comment is still in it; one right after a
+    /// closing quote is not.
+    pub fn is_in_literal(&self, byte: usize) -> bool {
+        let Some(tree) = &self.tree else {
+            return false;
+        };
+        let start = byte.saturating_sub(1);
+        let mut node = tree.root_node().descendant_for_byte_range(start, byte);
+        while let Some(current) = node {
+            let kind = current.kind();
+            let inside = current.start_byte() < byte && byte < current.end_byte();
+            if kind.contains("comment") && current.start_byte() < byte && byte <= current.end_byte()
+            {
+                return true;
+            }
+            if is_string_kind(kind) && inside {
+                return true;
+            }
+            node = current.parent();
+        }
+        false
+    }
+
+    /// Brackets within a byte range (e.g. the visible lines), with their
+    /// nesting depth in the whole document
+    pub fn brackets(&self, range: Range<usize>) -> Vec<Bracket> {
+        let mut brackets = Vec::new();
+        if let Some(tree) = &self.tree {
+            collect_brackets(tree.root_node(), 0, &range, &mut brackets);
+        }
+        brackets
+    }
+}
+
+/// Whether a node is an opening or a closing bracket (with the bracket)
+fn bracket_kind(node: &Node<'_>) -> Option<(bool, char)> {
+    match node.kind() {
+        "(" | "[" | "{" if node.end_byte() == node.start_byte() + 1 => {
+            node.kind().chars().next().map(|c| (true, c))
+        }
+        ")" | "]" | "}" if node.end_byte() == node.start_byte() + 1 => {
+            node.kind().chars().next().map(|c| (false, c))
+        }
+        _ => None,
+    }
+}
+
+/// Check if a node kind is a string literal (in any of the grammars)
+fn is_string_kind(kind: &str) -> bool {
+    kind.contains("string")
+        || kind.ends_with("quote_scalar")
+        || matches!(kind, "heredoc" | "nowdoc" | "char_literal")
+}
+
+/// Check if a closing bracket closes an opening one
+fn closes(open: char, close: char) -> bool {
+    matches!((open, close), ('(', ')') | ('[', ']') | ('{', '}'))
+}
+
+/// Bracket pairs among the direct children of a node
+fn child_pairs(node: &Node<'_>) -> Vec<BracketPair> {
+    let mut pairs = Vec::new();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match bracket_kind(&child) {
+            Some((true, bracket)) => open.push((bracket, child.start_byte())),
+            Some((false, bracket)) if open.last().is_some_and(|&(o, _)| closes(o, bracket)) => {
+                let (_, start) = open.pop().expect("checked");
+                pairs.push(BracketPair {
+                    open: start,
+                    close: child.start_byte(),
+                });
+            }
+            _ => {}
+        }
+    }
+    pairs
+}
+
+/// Collect the brackets of a subtree within a byte range
+///
+/// An opening bracket nests the siblings after it, up to its closing bracket.
+fn collect_brackets(node: Node<'_>, depth: usize, range: &Range<usize>, out: &mut Vec<Bracket>) {
+    let mut depth = depth;
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.start_byte() >= range.end {
+            break;
+        }
+        match bracket_kind(&child) {
+            Some((true, _)) => {
+                if child.start_byte() >= range.start {
+                    out.push(Bracket {
+                        byte: child.start_byte(),
+                        depth,
+                    });
+                }
+                depth += 1;
+            }
+            Some((false, _)) => {
+                depth = depth.saturating_sub(1);
+                if child.start_byte() >= range.start {
+                    out.push(Bracket {
+                        byte: child.start_byte(),
+                        depth,
+                    });
+                }
+            }
+            None if child.end_byte() > range.start => collect_brackets(child, depth, range, out),
+            None => {}
+        }
+    }
+}
+
+impl Default for SyntaxTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_EDITS: Option<[InputEdit; 0]> = None;
+
+    fn parse(language: Language, source: &str) -> SyntaxTree {
+        let mut syntax = SyntaxTree::new();
+        syntax.set_language(Some(language));
+        let bytes = source.as_bytes();
+        assert!(syntax.update(1, NO_EDITS, |byte| &bytes[byte..]).is_some());
+        syntax
+    }
+
+    #[test]
+    fn test_enclosing_ranges_grow() {
+        let source = "<?php\nclass A {\n    function f() {\n        return $a + $b;\n    }\n}\n";
+        let syntax = parse(Language::Php, source);
+        let text = |range: &Range<usize>| &source[range.clone()];
+
+        // From the cursor in `$a` outwards
+        let at = source.find("$a").unwrap() + 1;
+        let mut range = at..at;
+        let mut steps = Vec::new();
+        while let Some(next) = syntax.enclosing_range(range.clone()) {
+            assert!(next.start <= range.start && next.end >= range.end);
+            steps.push(text(&next).to_string());
+            range = next;
+        }
+
+        assert!(steps.contains(&String::from("$a + $b")));
+        assert!(steps.contains(&String::from("return $a + $b;")));
+        assert!(steps.iter().any(|s| s.starts_with("function f()")));
+        assert!(steps.iter().any(|s| s.starts_with("class A")));
+        assert_eq!(range, 0..source.len());
+    }
+
+    #[test]
+    fn test_brackets_ignore_strings_and_comments() {
+        let source = "<?php\nif ($a) { f(\"(\", [1]); } // )\n";
+        let syntax = parse(Language::Php, source);
+        let at = |pattern: &str| source.find(pattern).unwrap();
+
+        // On a bracket, and right after one
+        let open = at("{");
+        let close = at("}");
+        let pair = BracketPair { open, close };
+        assert_eq!(syntax.bracket_pair(open), Some(pair));
+        assert_eq!(syntax.bracket_pair(close + 1), Some(pair));
+
+        // The `(` in the string doesn't count
+        let call = BracketPair {
+            open: at("f(") + 1,
+            close: at("); }"),
+        };
+        assert_eq!(syntax.bracket_pair(at("\"(\"") + 1), Some(call));
+
+        // Inside `[1]`: the innermost pair
+        let one = at("1]");
+        assert_eq!(
+            syntax.bracket_pair(one),
+            Some(BracketPair {
+                open: one - 1,
+                close: one + 1
+            })
+        );
+
+        // Rainbow depths, without the brackets in the string and comment
+        let depths: Vec<(char, usize)> = syntax
+            .brackets(0..source.len())
+            .iter()
+            .map(|b| (source.as_bytes()[b.byte] as char, b.depth))
+            .collect();
+        assert_eq!(
+            depths,
+            [
+                ('(', 0),
+                (')', 0),
+                ('{', 0),
+                ('(', 1),
+                ('[', 2),
+                (']', 2),
+                (')', 1),
+                ('}', 0)
+            ]
+        );
+
+        // Only brackets within the range, with their document depth
+        let inner = syntax.brackets(at("[")..at("]") + 1);
+        assert_eq!(inner.iter().map(|b| b.depth).collect::<Vec<_>>(), [2, 2]);
+    }
+
+    #[test]
+    fn test_brackets_in_literals_are_not_matched() {
+        let source = "<?php\n$a = '[x]'; /* (y) */\n";
+        let syntax = parse(Language::Php, source);
+        let at = |pattern: &str| source.find(pattern).unwrap();
+
+        // On and right after a bracket in a string or comment
+        assert_eq!(syntax.bracket_pair(at("[x")), None);
+        assert_eq!(syntax.bracket_pair(at("x]") + 1), None);
+        assert_eq!(syntax.bracket_pair(at("(y")), None);
+        assert_eq!(syntax.bracket_pair(at("y)") + 2), None);
+        assert!(syntax.brackets(0..source.len()).is_empty());
+    }
+
+    #[test]
+    fn test_unmatched_brackets() {
+        let depths = |syntax: &SyntaxTree, source: &str| -> Vec<(usize, usize)> {
+            syntax
+                .brackets(0..source.len())
+                .iter()
+                .map(|b| (b.byte, b.depth))
+                .collect()
+        };
+
+        // A missing closing bracket
+        let source = "<?php\nf(1;\n";
+        let syntax = parse(Language::Php, source);
+        let open = source.find('(').unwrap();
+        assert_eq!(syntax.bracket_pair(open), None);
+        assert_eq!(depths(&syntax, source), [(open, 0)]);
+
+        // A stray closing bracket
+        let source = "<?php\n$a = 1);\n";
+        let syntax = parse(Language::Php, source);
+        assert_eq!(syntax.bracket_pair(source.find(')').unwrap()), None);
+
+        // The inner pair still matches
+        let source = "[1, [2]";
+        let syntax = parse(Language::Json, source);
+        assert_eq!(syntax.bracket_pair(0), None);
+        assert_eq!(
+            syntax.bracket_pair(4),
+            Some(BracketPair { open: 4, close: 6 })
+        );
+        assert_eq!(depths(&syntax, source), [(0, 0), (4, 1), (6, 1)]);
+    }
+
+    #[test]
+    fn test_cursor_next_to_closing_bracket() {
+        let source = "[[1],[2]]";
+        let syntax = parse(Language::Json, source);
+        let outer = BracketPair { open: 0, close: 8 };
+
+        // Right after `]`: its pair
+        assert_eq!(
+            syntax.bracket_pair(4),
+            Some(BracketPair { open: 1, close: 3 })
+        );
+        // Between `]]`: the bracket at the cursor wins
+        assert_eq!(syntax.bracket_pair(8), Some(outer));
+        // At the end of the text, after the last `]`
+        assert_eq!(syntax.bracket_pair(9), Some(outer));
+        // On `]`: its pair
+        assert_eq!(
+            syntax.bracket_pair(7),
+            Some(BracketPair { open: 5, close: 7 })
+        );
+    }
+
+    #[test]
+    fn test_strings_and_comments() {
+        let source = "<?php\n$a = f('x', $b); // note\n";
+        let syntax = parse(Language::Php, source);
+        let at = |pattern: &str| source.find(pattern).unwrap();
+
+        assert!(syntax.is_in_literal(at("x")));
+        assert!(!syntax.is_in_literal(at("x") + 2));
+        assert!(!syntax.is_in_literal(at("$b")));
+        assert!(syntax.is_in_literal(at("note")));
+        assert!(syntax.is_in_literal(at("note") + 4));
+    }
+
+    #[test]
+    fn test_reparse_by_revision() {
+        let mut syntax = SyntaxTree::new();
+        assert!(syntax.update(1, NO_EDITS, |_| "").is_none());
+
+        syntax.set_language(Some(Language::Json));
+        let old = "[1]";
+        syntax.update(1, NO_EDITS, |byte| &old.as_bytes()[byte..]);
+        assert_eq!(syntax.revision(), Some(1));
+        // Same revision: the old tree is kept
+        let new = "[1, 2]";
+        let tree = syntax
+            .update(1, NO_EDITS, |byte| &new.as_bytes()[byte..])
+            .unwrap();
+        assert_eq!(tree.root_node().byte_range(), 0..3);
+        let tree = syntax
+            .update(2, NO_EDITS, |byte| &new.as_bytes()[byte..])
+            .unwrap();
+        assert_eq!(tree.root_node().byte_range(), 0..6);
+    }
+
+    #[test]
+    fn test_incremental_reparse() {
+        let mut syntax = SyntaxTree::new();
+        syntax.set_language(Some(Language::Json));
+        let old = "[1, 2]";
+        syntax.update(1, NO_EDITS, |byte| &old.as_bytes()[byte..]);
+
+        // Insert `, [3]` before the closing bracket
+        let new = "[1, 2, [3]]";
+        let point = |column| tree_sitter::Point::new(0, column);
+        let edit = InputEdit {
+            start_byte: 5,
+            old_end_byte: 5,
+            new_end_byte: 10,
+            start_position: point(5),
+            old_end_position: point(5),
+            new_end_position: point(10),
+        };
+        let tree = syntax
+            .update(2, Some([edit]), |byte| &new.as_bytes()[byte..])
+            .unwrap();
+        assert_eq!(tree.root_node().byte_range(), 0..new.len());
+        assert!(!tree.root_node().has_error());
+
+        // The same tree as a full parse
+        let sexp = tree.root_node().to_sexp();
+        let mut full = SyntaxTree::new();
+        full.set_language(Some(Language::Json));
+        let tree = full
+            .update(1, NO_EDITS, |byte| &new.as_bytes()[byte..])
+            .unwrap();
+        assert_eq!(tree.root_node().to_sexp(), sexp);
+        assert_eq!(
+            syntax.bracket_pair(7),
+            Some(BracketPair { open: 7, close: 9 })
+        );
+    }
+}
@@ -1,8 +1,12 @@
 //! Main application state and event loop
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
-use four_code_core::Editor;
-use four_code_highlight::{global_highlighter, HighlightCache};
+use four_code_core::{
+    normalize_line_endings, replace_in_file, BufferError, Config, Editor, FileIndex, FileWatcher,
+    IndentStyle, Indentation, Position, SaveOptions, SearchConfig, SearchMatch, Swap, SwapStore,
+    TextEdit, UndoStore,
+};
+use four_code_highlight::{global_highlighter, HighlightCache, InputEdit, Point, SyntaxTree};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
@@ -10,11 +14,26 @@ use ratatui::{
     widgets::{Block, Borders, Clear, Paragraph},
     Frame, Terminal,
 };
+use std::collections::HashMap;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
-use crate::EditorWidget;
+use crate::command::Command;
+use crate::diff_prompt::{DiffPrompt, PromptAction};
+use crate::file_finder::{FileFinder, FileFinderAction};
+use crate::hex::{hex_column_x, HexWidget};
+use crate::palette::{CommandPalette, PaletteAction};
+use crate::project_search::{ProjectReplace, ProjectSearchAction, ProjectSearchPanel};
+use crate::search_bar::{SearchAction, SearchBar};
+use crate::{Brackets, EditorWidget};
+
+/// Idle time after the last change before a swap file is written
+const SWAP_DELAY: Duration = Duration::from_secs(2);
+
+/// Number of recently opened files remembered for the file finder
+const MAX_RECENT_FILES: usize = 30;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -33,17 +52,77 @@ pub struct App {
     /// Syntax highlight cache
     highlight_cache: HighlightCache,
 
+    /// Parse tree of the whole buffer (for structural selection and
+    /// bracket matching)
+    syntax: SyntaxTree,
+
+    /// Colour brackets by nesting depth
+    rainbow_brackets: bool,
+
     /// Whether the app should quit
     should_quit: bool,
 
     /// Status message
     status: String,
 
-    /// Last terminal size
-    last_size: (u16, u16),
-
     /// Show help popup
     show_help: bool,
+
+    /// Buffer info popup (when open)
+    buffer_info: Option<String>,
+
+    /// Command palette (when open)
+    palette: Option<CommandPalette>,
+
+    /// Search bar (when open)
+    search: Option<SearchBar>,
+
+    /// Project root (the working directory)
+    root: PathBuf,
+
+    /// Project search panel (when open)
+    project_search: Option<ProjectSearchPanel>,
+
+    /// Index of the project files (built on the first Ctrl+P)
+    file_index: Option<FileIndex>,
+
+    /// File finder (when open)
+    file_finder: Option<FileFinder>,
+
+    /// Recently opened files (absolute paths, most recent first)
+    recent_files: Vec<PathBuf>,
+
+    /// Persistent undo history (None if no data directory is available)
+    undo_store: Option<UndoStore>,
+
+    /// Watches the open file for external modifications
+    watcher: Option<FileWatcher>,
+
+    /// Prompt for a modified buffer whose file changed on disk
+    reload_prompt: Option<DiffPrompt>,
+
+    /// Crash recovery swap files (None if no data directory is available)
+    swap_store: Option<SwapStore>,
+
+    /// Swap file found on startup, with the prompt offering recovery
+    recovery: Option<(Swap, DiffPrompt)>,
+
+    /// Buffer revision of the last swap write
+    swap_revision: u64,
+
+    /// Recovery was postponed: the swap file still holds the unsaved changes
+    /// of the crashed session and must outlive this one
+    recovery_postponed: bool,
+
+    /// Last buffer revision seen, and when it was first seen
+    last_change: (u64, Instant),
+
+    /// The last save was refused because the file changed on disk: the next
+    /// Ctrl+S overwrites it
+    confirm_overwrite: bool,
+
+    /// Text last copied from a block selection (pasted back as a block)
+    block_clipboard: Option<String>,
 }
 
 impl App {
@@ -63,36 +142,144 @@ impl App {
                  echo $greeter->greet('World');\n",
             ),
             highlight_cache: HighlightCache::new(global_highlighter()),
+            syntax: SyntaxTree::new(),
+            rainbow_brackets: true,
             should_quit: false,
             status: String::from("four-code v0.1.0 | F1: Help | Ctrl+Q: Quit"),
-            last_size: (0, 0),
             show_help: false,
+            buffer_info: None,
+            palette: None,
+            search: None,
+            root: std::env::current_dir().unwrap_or_default(),
+            project_search: None,
+            file_index: None,
+            file_finder: None,
+            recent_files: Vec::new(),
+            undo_store: None,
+            watcher: None,
+            reload_prompt: None,
+            swap_store: None,
+            recovery: None,
+            swap_revision: 0,
+            recovery_postponed: false,
+            last_change: (0, Instant::now()),
+            confirm_overwrite: false,
+            block_clipboard: None,
         }
     }
 
     /// Create app with a file
     pub fn with_file(path: &str) -> Result<Self, AppError> {
-        let editor = Editor::open(path).map_err(|e| AppError::Terminal(e.to_string()))?;
+        let mut app = Self::new();
+        app.open(Path::new(path))?;
+        Ok(app)
+    }
+
+    /// Create app for a project directory, with the file finder open
+    pub fn with_dir(path: &str) -> Self {
+        let mut app = Self::new();
+        app.root = std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        app.open_file_finder();
+        app
+    }
+
+    /// Open a file in place of the current buffer
+    fn open(&mut self, path: &Path) -> Result<(), AppError> {
+        // A broken config doesn't keep the file from opening
+        let (config, config_info) = match Config::load() {
+            Ok(config) => (config, String::new()),
+            Err(e) => (Config::default(), format!(" (config ignored: {e})")),
+        };
+        let mut editor = Editor::open_with_options(path, &config.files.load_options())
+            .map_err(|e| AppError::Terminal(e.to_string()))?;
+        let viewport = &self.editor.viewport;
+        editor.set_viewport_size(viewport.height, viewport.width);
+        let large_file = editor.buffer.is_large_file();
+        editor.buffer.set_save_options(SaveOptions {
+            backup: config.files.backup.clone(),
+        });
+        editor.word_chars = config.word_chars(path);
+        editor.rules = config.editing_rules(path);
+        editor.buffer.set_tab_width(config.tab_width(path));
+        editor.buffer.load_editorconfig();
+
+        // Restore undo history from the last session (if the file is unchanged);
+        // not for large files, where hashing the content would stall
+        let undo_store = UndoStore::open_default().ok().filter(|_| !large_file);
+        let restored = undo_store
+            .as_ref()
+            .is_some_and(|store| store.restore(&mut editor.buffer).unwrap_or(false));
+
+        // Offer recovery if a swap file with different content exists (not for
+        // large files, which get no swap files)
+        let swap_store = SwapStore::open_default().ok().filter(|_| !large_file);
+        let recovery = swap_store
+            .as_ref()
+            .and_then(|store| store.find(path).ok().flatten())
+            .filter(|swap| swap.content != editor.buffer.text())
+            .map(|swap| {
+                let message = format!(
+                    "Unsaved changes to {} were recovered (from process {}).",
+                    editor.filename(),
+                    swap.pid
+                );
+                let choices = [('k', "Keep for later"), ('r', "Recover"), ('x', "Discard")];
+                (swap, DiffPrompt::new("Recover File", message, &choices))
+            });
+
+        // Watching is best effort (e.g. inotify limits)
+        let watcher = FileWatcher::new().ok().and_then(|mut watcher| {
+            watcher.watch(path).ok()?;
+            Some(watcher)
+        });
+
         let mut highlight_cache = HighlightCache::new(global_highlighter());
+        let mut syntax = SyntaxTree::new();
 
-        // Detect language from file extension
-        highlight_cache.set_language_from_path(Path::new(path));
+        // Detect language from file extension (no highlighting or parsing for
+        // large files)
+        if !editor.is_read_only() && !large_file {
+            highlight_cache.set_language_from_path(path);
+            syntax.set_language(highlight_cache.current_language());
+        }
 
-        let lang_info = if let Some(lang) = highlight_cache.current_language() {
+        let lang_info = if editor.is_read_only() {
+            String::from(" [binary, read-only]")
+        } else if let Some(lang) = highlight_cache.current_language() {
             format!(" [{}]", lang.name())
         } else {
             String::new()
         };
 
-        let status = format!("Opened: {path}{lang_info}");
-        Ok(Self {
-            editor,
-            highlight_cache,
-            should_quit: false,
-            status,
-            last_size: (0, 0),
-            show_help: false,
-        })
+        let history_info = if restored {
+            " (undo history restored)"
+        } else {
+            ""
+        };
+        let large_info = if large_file { " (large-file mode)" } else { "" };
+        self.status = format!(
+            "Opened: {}{lang_info}{history_info}{large_info}{config_info}",
+            path.display()
+        );
+        let absolute = std::fs::canonicalize(path).unwrap_or_else(|_| self.root.join(path));
+        self.recent_files.retain(|recent| *recent != absolute);
+        self.recent_files.insert(0, absolute);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+        self.editor = editor;
+        self.highlight_cache = highlight_cache;
+        self.syntax = syntax;
+        self.rainbow_brackets = config.editor.rainbow_brackets;
+        self.search = None;
+        self.undo_store = undo_store;
+        self.watcher = watcher;
+        self.reload_prompt = None;
+        self.swap_store = swap_store;
+        self.recovery = recovery;
+        self.swap_revision = 0;
+        self.recovery_postponed = false;
+        self.last_change = (0, Instant::now());
+        self.confirm_overwrite = false;
+        Ok(())
     }
 
     /// Run the application
@@ -104,36 +291,83 @@ impl App {
         if self.editor.path().is_none() {
             self.highlight_cache
                 .set_language(Some(four_code_highlight::Language::Php));
+            self.syntax
+                .set_language(Some(four_code_highlight::Language::Php));
         }
 
         while !self.should_quit {
             terminal.draw(|frame| self.render(frame))?;
             self.handle_events()?;
         }
+
+        // Normal exit: nothing to recover
+        self.remove_swap();
         Ok(())
     }
 
+    /// Remove the swap file of the open file, unless its recovery was
+    /// postponed
+    fn remove_swap(&self) {
+        if self.recovery_postponed {
+            return;
+        }
+        if let (Some(store), Some(path)) = (&self.swap_store, self.editor.path()) {
+            let _ = store.remove(path);
+        }
+    }
+
     /// Render the UI
     fn render(&mut self, frame: &mut Frame) {
         let size = frame.area();
 
-        // Update viewport size if terminal resized
-        if (size.width, size.height) != self.last_size {
-            self.last_size = (size.width, size.height);
-            // Account for borders and status bar
-            let editor_height = size.height.saturating_sub(3) as usize;
-            let editor_width = size.width.saturating_sub(2) as usize;
+        let banner = self.large_file_banner();
+        let banner_height = u16::from(banner.is_some());
+
+        let search_height = self.search.as_ref().map_or(0, SearchBar::height);
+        if let Some(search) = &mut self.search {
+            search.update(&self.editor);
+        }
+
+        // The project search panel takes the lower part of the screen
+        let panel_height = match &mut self.project_search {
+            Some(panel) => {
+                panel.poll();
+                (size.height * 2 / 5).max(8)
+            }
+            None => 0,
+        };
+
+        // Update viewport size if the terminal was resized or a bar or panel
+        // opened or closed (accounting for borders, status bar, banner,
+        // search bar and panel)
+        let editor_height = size
+            .height
+            .saturating_sub(3 + banner_height + search_height + panel_height)
+            as usize;
+        let editor_width = size.width.saturating_sub(2) as usize;
+        let viewport = &self.editor.viewport;
+        if (viewport.height, viewport.width) != (editor_height, editor_width) {
             self.editor.set_viewport_size(editor_height, editor_width);
         }
 
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Min(1),    // Editor
-                Constraint::Length(1), // Status bar
+                Constraint::Length(banner_height), // Large-file banner
+                Constraint::Min(1),                // Editor
+                Constraint::Length(panel_height),  // Project search panel
+                Constraint::Length(search_height), // Search bar
+                Constraint::Length(1),             // Status bar
             ])
             .split(size);
 
+        if let Some(banner) = banner {
+            let banner =
+                Paragraph::new(banner).style(Style::default().fg(Color::Black).bg(Color::Yellow));
+            frame.render_widget(banner, chunks[0]);
+        }
+        let chunks = &chunks[1..];
+
         // Editor title with filename, language, and modified indicator
         let lang_suffix = if let Some(lang) = self.highlight_cache.current_language() {
             format!(" [{}]", lang.name())
@@ -156,35 +390,139 @@ impl App {
         let inner = editor_block.inner(chunks[0]);
         frame.render_widget(editor_block, chunks[0]);
 
-        // Render editor content with syntax highlighting
-        let editor_widget = EditorWidget::new(&self.editor, &mut self.highlight_cache);
-        frame.render_widget(editor_widget, inner);
-
-        // Set cursor position (account for line numbers)
-        let line_num_width = self.editor.buffer.len_lines().to_string().len().max(3) + 1;
-        let cursor_x = inner.x + self.editor.cursor.position.column as u16 + line_num_width as u16;
-        let cursor_y =
-            inner.y + (self.editor.cursor.position.line - self.editor.viewport.top_line) as u16;
+        // Render editor content: hex view for binary files, text with
+        // syntax highlighting otherwise
+        let (cursor_x, cursor_y) = if let Some(hex) = &self.editor.hex {
+            frame.render_widget(HexWidget::new(&self.editor), inner);
+            (
+                inner.x + hex_column_x(hex.cursor_column()) as u16,
+                inner.y + (hex.cursor_row() - self.editor.viewport.top_line) as u16,
+            )
+        } else {
+            let brackets = self.visible_brackets();
+            let ruler = self.editor.buffer.editorconfig().max_line_length;
+            let (matches, current) = self.search.as_ref().map_or((&[][..], None), |search| {
+                (search.matches(), search.current())
+            });
+            let editor_widget = EditorWidget::new(&self.editor, &mut self.highlight_cache)
+                .brackets(brackets)
+                .ruler(ruler)
+                .search_matches(matches, current);
+            frame.render_widget(editor_widget, inner);
+
+            // Account for line numbers and wide characters before the cursor
+            let line_num_width = self.editor.buffer.len_lines().to_string().len().max(3) + 1;
+            let position = self.editor.cursor.position;
+            let cursor_cell = match self.editor.block_selection() {
+                // The moving corner of a block selection may be past the line end
+                Some(block) => block.head.column,
+                None => self
+                    .editor
+                    .buffer
+                    .display_column(position.line, position.column),
+            };
+            (
+                (inner.x as usize + cursor_cell + line_num_width).min(u16::MAX as usize) as u16,
+                inner.y + (self.editor.cursor.position.line - self.editor.viewport.top_line) as u16,
+            )
+        };
 
-        if cursor_y >= inner.y && cursor_y < inner.y + inner.height {
+        // The focused panel or the search bar has the terminal cursor
+        let panel_cursor = match &mut self.project_search {
+            Some(panel) => panel.render(frame, chunks[1]),
+            None => None,
+        };
+        let search_cursor = self
+            .search
+            .as_ref()
+            .map(|search| search.render(frame, chunks[2]));
+        if let Some(position) = panel_cursor.or(search_cursor) {
+            frame.set_cursor_position(position);
+        } else if cursor_y >= inner.y && cursor_y < inner.y + inner.height {
             frame.set_cursor_position((cursor_x.min(inner.x + inner.width - 1), cursor_y));
         }
 
         // Status bar with position info
-        let pos_info = format!(
-            "Ln {}, Col {} | {}",
-            self.editor.cursor.position.line + 1,
-            self.editor.cursor.position.column + 1,
-            &self.status
-        );
+        let format = self.editor.buffer.format();
+        let pos_info = if let Some(hex) = &self.editor.hex {
+            format!(
+                "Offset 0x{:08x} of {} bytes | Binary (read-only) | {}",
+                hex.cursor(),
+                hex.bytes().len(),
+                &self.status
+            )
+        } else {
+            let cursors = match self.editor.cursors().count() {
+                1 => String::new(),
+                count => format!(" ({count} cursors)"),
+            };
+            let matches = self
+                .search
+                .as_ref()
+                .map_or(String::new(), |search| format!(" | {}", search.counter()));
+            format!(
+                "Ln {}, Col {}{}{} | {} | {}{} | {} | {}",
+                self.editor.cursor.position.line + 1,
+                self.editor.cursor.position.column + 1,
+                cursors,
+                matches,
+                self.editor.buffer.indentation().name(),
+                format.encoding.name(),
+                if format.bom { " BOM" } else { "" },
+                format.line_ending.name(),
+                &self.status
+            )
+        };
         let status =
             Paragraph::new(pos_info).style(Style::default().fg(Color::White).bg(Color::DarkGray));
-        frame.render_widget(status, chunks[1]);
+        frame.render_widget(status, chunks[3]);
 
         // Help popup
         if self.show_help {
             self.render_help(frame, size);
         }
+
+        // Buffer info popup
+        if let Some(info) = &self.buffer_info {
+            render_popup(frame, size, " Buffer Info ", info);
+        }
+
+        // Command palette
+        if let Some(palette) = &self.palette {
+            palette.render(frame, size);
+        }
+
+        // File finder
+        if let (Some(finder), Some(index)) = (&mut self.file_finder, &self.file_index) {
+            finder.update(index);
+            finder.render(frame, size, !index.is_ready());
+        }
+
+        // Replace all preview
+        if let Some(search) = &self.search {
+            search.render_preview(frame, size);
+        }
+
+        // File changed on disk
+        if let Some(prompt) = &self.reload_prompt {
+            prompt.render(frame, size);
+        }
+
+        // Swap file found on startup
+        if let Some((_, prompt)) = &self.recovery {
+            prompt.render(frame, size);
+        }
+    }
+
+    /// Banner explaining large-file mode (None for normal files)
+    fn large_file_banner(&self) -> Option<String> {
+        if !self.editor.buffer.is_large_file() {
+            return None;
+        }
+        let size_mb = self.editor.buffer.rope().len_bytes() / (1024 * 1024);
+        Some(format!(
+            " Large file ({size_mb} MiB): syntax highlighting and persistent undo are off"
+        ))
     }
 
     /// Render help popup
@@ -195,64 +533,297 @@ impl App {
 
   Navigation
     Arrows        Move cursor
+    Ctrl+Left/Right Word start/end
+    Ctrl+Alt+Left/Right Subword (camelCase)
+    Ctrl+Up/Down  Paragraph
     Home/End      Line start/end
     Ctrl+Home/End Document start/end
     Page Up/Down  Scroll page
+    Ctrl+]        Matching bracket
 
   Selection
-    Shift+Arrows  Select text
+    Shift+Arrows  Select text (with Ctrl: words)
     Shift+Home/End Select to line start/end
+    Ctrl+W        Expand (Ctrl+Shift+W: shrink)
     Ctrl+A        Select all
 
+  Multiple Cursors
+    Ctrl+D        Add next occurrence
+    Ctrl+Alt+Up/Down Add cursor above/below
+    Alt+Shift+Arrows Block selection
+    Esc           Back to one cursor
+
   Editing
     Backspace     Delete before cursor
     Delete        Delete at cursor
-    Tab           Insert 4 spaces
-    Enter         New line
+    Ctrl+Bksp/Del Delete word
+    Tab/Shift+Tab Indent/outdent (selected lines)
+    Alt+Up/Down   Move lines
+    Ctrl+Shift+D  Duplicate lines
+    Ctrl+Shift+K  Delete lines
+    Ctrl+Shift+J  Join lines
+    Enter         New line (keeps indentation)
+    Ctrl+Z        Undo
+    Ctrl+Y        Redo (also Ctrl+Shift+Z)
 
   Clipboard
     Ctrl+C        Copy
     Ctrl+X        Cut
     Ctrl+V        Paste
 
+  Search
+    Ctrl+F        Find (selected text)
+    Ctrl+Shift+F  Find in project
+    Ctrl+Shift+R  Replace in project
+    F4            Next project result (Shift: previous)
+    Alt+X/F       Check project match / file
+    Ctrl+R        Replace (Tab: switch fields)
+    Enter/F3      Next match (Shift: previous)
+    Alt+C/W/R     Case / whole word / regex
+    Alt+L         In selection
+    Alt+P         Preserve case
+    Enter         Replace (in replace field)
+    Alt+Enter     Replace all (with preview)
+    Esc           Close search
+
   File
+    Ctrl+P        Go to file (fuzzy)
     Ctrl+S        Save
 
   Application
     F1            Toggle this help
+    F2            Command palette (Ctrl+Shift+P)
     Ctrl+Q        Quit
 
   ------------------
   Press F1 or Esc to close";
 
-        // Calculate popup size and position (centered)
-        let popup_width = 40u16;
-        let popup_height = 35u16;
-        let x = size.width.saturating_sub(popup_width) / 2;
-        let y = size.height.saturating_sub(popup_height) / 2;
-        let area = Rect::new(
-            x,
-            y,
-            popup_width.min(size.width),
-            popup_height.min(size.height),
-        );
+        render_popup(frame, size, " Help ", help_text);
+    }
 
-        // Clear the area behind the popup
-        frame.render_widget(Clear, area);
+    /// Open the search bar (with the replace field if `replace`), searching
+    /// from the cursor
+    fn open_search(&mut self, replace: bool) {
+        let mut search = SearchBar::new(&self.editor);
+        if replace {
+            search.show_replace();
+        }
+        search.search(&mut self.editor);
+        self.search = Some(search);
+    }
 
-        let help_block = Block::default()
-            .title(" Help ")
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Yellow))
-            .style(Style::default().bg(Color::Rgb(40, 44, 52)));
+    /// Open the project search panel (with the replace field if
+    /// `replace`), or focus it if it is open (a selection within one line
+    /// becomes the query)
+    fn open_project_search(&mut self, replace: bool) {
+        let query = self
+            .editor
+            .get_selected_text()
+            .filter(|text| !text.contains('\n'));
+        match &mut self.project_search {
+            Some(panel) => {
+                panel.set_focused(true);
+                if let Some(query) = query {
+                    panel.set_query(query);
+                }
+            }
+            None => {
+                let exclude = Config::load()
+                    .map(|config| config.search.exclude)
+                    .unwrap_or_else(|_| SearchConfig::default().exclude);
+                self.project_search = Some(ProjectSearchPanel::new(
+                    self.root.clone(),
+                    query.unwrap_or_default(),
+                    &exclude,
+                ));
+            }
+        }
+        if let (true, Some(panel)) = (replace, &mut self.project_search) {
+            panel.show_replace();
+        }
+    }
 
-        let help_paragraph = Paragraph::new(help_text).block(help_block).style(
-            Style::default()
-                .fg(Color::White)
-                .add_modifier(Modifier::empty()),
-        );
+    /// Replace the checked project matches: the open file is changed in the
+    /// buffer (one undo step, not saved), the others on disk
+    ///
+    /// Matches no longer found where the search found them are skipped.
+    fn replace_in_project(&mut self, replace: ProjectReplace) {
+        let save_options = Config::load()
+            .map(|config| SaveOptions {
+                backup: config.files.backup,
+            })
+            .unwrap_or_default();
+        let (mut files, mut replaced, mut skipped) = (0, 0, 0);
+        let mut in_buffer = false;
+        let mut failed = Vec::new();
+        for (path, matches) in &replace.files {
+            let is_open = self.editor.path().is_some_and(|open| same_file(open, path));
+            let result = if is_open {
+                let current = replace
+                    .query
+                    .still_matching(self.editor.buffer.rope(), matches);
+                let count =
+                    self.editor
+                        .replace_matches(&replace.query, &current, &replace.replacement);
+                in_buffer = count > 0;
+                Ok(count)
+            } else {
+                replace_in_file(
+                    path,
+                    &replace.query,
+                    matches,
+                    &replace.replacement,
+                    &save_options,
+                )
+            };
+            match result {
+                Ok(count) => {
+                    files += usize::from(count > 0);
+                    replaced += count;
+                    skipped += matches.len() - count;
+                }
+                Err(e) => failed.push(format!("{}: {e}", path.display())),
+            }
+        }
+        if in_buffer {
+            self.highlight_cache.invalidate_all();
+        }
+
+        self.status = format!("Replaced {replaced} matches in {files} files");
+        if in_buffer {
+            self.status.push_str(&format!(
+                " ({} changed in the editor, not saved)",
+                self.editor.filename()
+            ));
+        }
+        if skipped > 0 {
+            self.status
+                .push_str(&format!(", {skipped} skipped (changed since the search)"));
+        }
+        if let Some(first) = failed.first() {
+            self.status
+                .push_str(&format!(", {} files failed: {first}", failed.len()));
+        }
+        if let Some(panel) = &mut self.project_search {
+            panel.search_again();
+        }
+    }
+
+    /// Open the file finder (indexing the project on first use)
+    fn open_file_finder(&mut self) {
+        let index = self
+            .file_index
+            .get_or_insert_with(|| FileIndex::new(&self.root));
+        // The open file is left out: Ctrl+P, Enter goes back to the previous
+        // file
+        let root = std::fs::canonicalize(index.root()).unwrap_or_else(|_| self.root.clone());
+        let recent = self
+            .recent_files
+            .iter()
+            .skip(usize::from(self.editor.path().is_some()))
+            .filter_map(|path| path.strip_prefix(&root).ok()?.to_str())
+            .map(|path| path.replace('\\', "/"))
+            .collect();
+        self.file_finder = Some(FileFinder::new(recent));
+    }
 
-        frame.render_widget(help_paragraph, area);
+    /// Open a file picked in the file finder (relative to the project root)
+    fn open_found_file(&mut self, path: &Path) {
+        let path = self.root.join(path);
+        let is_open = self
+            .editor
+            .path()
+            .is_some_and(|open| same_file(open, &path));
+        if !is_open {
+            self.switch_file(&path);
+        }
+    }
+
+    /// Open a file (unless it is the open one) and select a match; the
+    /// editor gets the focus
+    fn open_match(&mut self, path: &Path, m: SearchMatch) {
+        let is_open = self.editor.path().is_some_and(|open| same_file(open, path));
+        if !is_open && !self.switch_file(path) {
+            return;
+        }
+        self.editor
+            .select_range(m.start_position(), m.end_position());
+
+        // Center the match
+        let viewport = &mut self.editor.viewport;
+        viewport.top_line = m.line.saturating_sub(viewport.height / 2);
+        if let Some(panel) = &mut self.project_search {
+            panel.set_focused(false);
+        }
+    }
+
+    /// Open another file in place of the current one (refused while the
+    /// buffer has unsaved changes); returns `false` if not opened
+    fn switch_file(&mut self, path: &Path) -> bool {
+        if self.editor.is_modified() {
+            self.status = format!(
+                "Save {} before opening another file",
+                self.editor.filename()
+            );
+            return false;
+        }
+        // The buffer is saved: nothing to recover (but a postponed recovery
+        // still is)
+        self.remove_swap();
+        match self.open(path) {
+            Ok(()) => true,
+            Err(e) => {
+                self.status = format!("Error: {e}");
+                false
+            }
+        }
+    }
+
+    /// Text of the buffer info popup: file format, indentation and the
+    /// resolved `.editorconfig` settings
+    fn buffer_info(&self) -> String {
+        let buffer = &self.editor.buffer;
+        let format = buffer.format();
+        let mut lines = vec![
+            format!("  File         {}", self.editor.filename()),
+            format!("  Lines        {}", buffer.len_lines()),
+            format!(
+                "  Encoding     {}{}",
+                format.encoding.name(),
+                if format.bom { " BOM" } else { "" }
+            ),
+            format!("  Line ending  {}", format.line_ending.name()),
+            format!("  Indentation  {}", buffer.indentation().name()),
+            format!("  Tab width    {}", buffer.tab_width()),
+            String::new(),
+        ];
+
+        let editorconfig = buffer.editorconfig();
+        if editorconfig.files.is_empty() {
+            lines.push(String::from("  No .editorconfig found"));
+        } else {
+            lines.push(String::from("  .editorconfig (nearest first)"));
+            lines.extend(
+                editorconfig
+                    .files
+                    .iter()
+                    .map(|file| format!("    {}", file.display())),
+            );
+            lines.push(String::new());
+            let entries = editorconfig.entries();
+            if entries.is_empty() {
+                lines.push(String::from("  No settings for this file"));
+            }
+            lines.extend(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| format!("  {key} = {value}")),
+            );
+        }
+
+        lines.push(String::new());
+        lines.push(String::from("  Press any key to close"));
+        lines.join("\n")
     }
 
     /// Handle input events
@@ -263,13 +834,116 @@ impl App {
                 self.handle_key(key);
             }
         }
+        self.check_disk_changes();
+        self.update_swap();
         Ok(())
     }
 
+    /// Write a swap file once the buffer has been idle for a moment
+    fn update_swap(&mut self) {
+        let Some(store) = &self.swap_store else {
+            return;
+        };
+        let revision = self.editor.buffer.revision();
+        if revision == self.swap_revision {
+            return;
+        }
+        if revision != self.last_change.0 {
+            self.last_change = (revision, Instant::now());
+            return;
+        }
+        if self.last_change.1.elapsed() < SWAP_DELAY {
+            return;
+        }
+
+        self.swap_revision = revision;
+        // The crashed session's changes are replaced by the current ones
+        self.recovery_postponed = false;
+        if let Err(e) = store.write(&self.editor.buffer) {
+            self.status = format!("Swap file not written: {e}");
+        }
+    }
+
+    /// React to external modifications of the open file
+    ///
+    /// Unmodified buffers are reloaded, modified ones get the reload prompt.
+    fn check_disk_changes(&mut self) {
+        let Some(watcher) = &self.watcher else {
+            return;
+        };
+        if watcher.changed_files().is_empty() || self.reload_prompt.is_some() {
+            return;
+        }
+        // Our own save, or rewritten with the same content
+        if !self.editor.is_read_only() && !self.editor.buffer.is_changed_on_disk() {
+            return;
+        }
+
+        if self.editor.is_modified() {
+            let message = format!(
+                "{} was changed on disk, but has unsaved changes here.",
+                self.editor.filename()
+            );
+            let choices = [('k', "Keep mine"), ('r', "Reload from disk")];
+            self.reload_prompt = Some(DiffPrompt::new("File Changed on Disk", message, &choices));
+        } else {
+            self.reload();
+        }
+    }
+
+    /// Apply the choice from the recovery prompt
+    fn recover(&mut self, choice: char, swap: Swap) {
+        match choice {
+            'r' => {
+                self.editor.replace_all(&swap.content);
+                self.highlight_cache.invalidate_all();
+                self.status = String::from("Recovered unsaved changes (Ctrl+Z to undo)");
+            }
+            'x' => {
+                if let Some(store) = &self.swap_store {
+                    let _ = store.remove(&swap.path);
+                }
+                self.status = String::from("Discarded recovered changes");
+            }
+            _ => {
+                // Don't overwrite the swap file until the buffer is edited
+                self.swap_revision = self.editor.buffer.revision();
+                self.recovery_postponed = true;
+                self.status = String::from("Recovery postponed: see four-code --recover");
+            }
+        }
+    }
+
+    /// Reload the file from disk
+    fn reload(&mut self) {
+        match self.editor.reload() {
+            Ok(()) => {
+                self.highlight_cache.invalidate_all();
+                self.status = format!("Reloaded {} from disk", self.editor.filename());
+            }
+            Err(e) => self.status = format!("Reload failed: {e}"),
+        }
+    }
+
     /// Handle a key event
     fn handle_key(&mut self, key: KeyEvent) {
-        // Track if we need to invalidate highlighting
-        let line_before = self.editor.cursor.position.line;
+        // Track if we need to invalidate highlighting (from the topmost cursor
+        // or selection)
+        let line_before = self
+            .editor
+            .cursors()
+            .map(|cursor| {
+                cursor
+                    .selection_range()
+                    .map_or(cursor.position.line, |(start, _)| start.line)
+            })
+            .min()
+            .unwrap_or(0);
+
+        // The buffer info popup closes on any key
+        if self.buffer_info.take().is_some() {
+            return;
+        }
 
         // If help is open, only handle F1/Esc to close it
         if self.show_help {
@@ -280,6 +954,106 @@ impl App {
             return;
         }
 
+        // Ctrl+S right after a refused save overwrites the file
+        let confirm_overwrite = std::mem::take(&mut self.confirm_overwrite);
+
+        // Prompts capture all keys while open
+        if let Some((swap, prompt)) = &mut self.recovery {
+            match prompt.handle_key(key) {
+                PromptAction::None => {}
+                PromptAction::Diff => prompt.show_diff(
+                    ("on disk", &self.editor.buffer.text()),
+                    ("recovered", &swap.content),
+                ),
+                PromptAction::Choose(choice) => {
+                    if let Some((swap, _)) = self.recovery.take() {
+                        self.recover(choice, swap);
+                    }
+                }
+            }
+            return;
+        }
+        if let Some(prompt) = &mut self.reload_prompt {
+            match prompt.handle_key(key) {
+                PromptAction::None => {}
+                PromptAction::Diff => match self.editor.buffer.disk_text() {
+                    Ok(disk) => {
+                        prompt.show_diff(("on disk", &disk), ("buffer", &self.editor.buffer.text()))
+                    }
+                    Err(e) => self.status = format!("Error: {e}"),
+                },
+                PromptAction::Choose('r') => {
+                    self.reload_prompt = None;
+                    self.reload();
+                }
+                PromptAction::Choose(_) => {
+                    self.reload_prompt = None;
+                    self.editor.buffer.accept_disk_state();
+                    self.status = String::from("Kept your changes; saving overwrites the file");
+                }
+            }
+            return;
+        }
+
+        // Command palette captures all keys while open
+        if let Some(palette) = &mut self.palette {
+            match palette.handle_key(key) {
+                PaletteAction::None => {}
+                PaletteAction::Close => self.palette = None,
+                PaletteAction::Run(command) => {
+                    self.palette = None;
+                    self.run_command(command);
+                }
+            }
+            return;
+        }
+
+        // File finder captures all keys while open
+        if let Some(finder) = &mut self.file_finder {
+            match finder.handle_key(key) {
+                FileFinderAction::None => {}
+                FileFinderAction::Close => self.file_finder = None,
+                FileFinderAction::Open(path) => {
+                    self.file_finder = None;
+                    self.open_found_file(&path);
+                }
+            }
+            return;
+        }
+
+        // The project search panel captures all keys while focused
+        if let Some(panel) = &mut self.project_search {
+            if panel.is_focused() {
+                match panel.handle_key(key) {
+                    ProjectSearchAction::None => {}
+                    ProjectSearchAction::Close => self.project_search = None,
+                    ProjectSearchAction::Open(path, m) => self.open_match(&path, m),
+                    ProjectSearchAction::Replace(replace) => self.replace_in_project(replace),
+                }
+                return;
+            }
+        }
+
+        // The search bar handles its keys, the editor gets the others
+        if let Some(search) = &mut self.search {
+            match search.handle_key(key, &mut self.editor) {
+                SearchAction::None => return,
+                SearchAction::Close => {
+                    self.search = None;
+                    return;
+                }
+                SearchAction::Replaced { line, count } => {
+                    self.highlight_cache.invalidate_from(line.saturating_sub(1));
+                    self.status = match count {
+                        1 => String::from("Replaced 1 match"),
+                        count => format!("Replaced {count} matches"),
+                    };
+                    return;
+                }
+                SearchAction::Ignored => {}
+            }
+        }
+
         match (key.modifiers, key.code) {
             // === Application Commands ===
 
@@ -288,25 +1062,60 @@ impl App {
                 self.show_help = true;
             }
 
+            // Command palette
+            (KeyModifiers::NONE, KeyCode::F(2)) => {
+                self.palette = Some(CommandPalette::new());
+            }
+            (mods, KeyCode::Char('p' | 'P'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.palette = Some(CommandPalette::new());
+            }
+
+            // Go to file
+            (KeyModifiers::CONTROL, KeyCode::Char('p')) => self.open_file_finder(),
+
+            // Find
+            (KeyModifiers::CONTROL, KeyCode::Char('f')) => self.open_search(false),
+
+            // Replace
+            (KeyModifiers::CONTROL, KeyCode::Char('r')) => self.open_search(true),
+
+            // Find in project
+            (mods, KeyCode::Char('f' | 'F'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.open_project_search(false);
+            }
+
+            // Replace in project
+            (mods, KeyCode::Char('r' | 'R'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.open_project_search(true);
+            }
+
+            // Next / previous project search result
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::F(4)) => {
+                let forward = key.modifiers == KeyModifiers::NONE;
+                if let Some((path, m)) = self
+                    .project_search
+                    .as_mut()
+                    .and_then(|panel| panel.step(forward))
+                {
+                    self.open_match(&path, m);
+                }
+            }
+
             // Quit
             (KeyModifiers::CONTROL, KeyCode::Char('q')) => {
                 self.should_quit = true;
             }
 
             // Save
-            (KeyModifiers::CONTROL, KeyCode::Char('s')) => match self.editor.save() {
-                Ok(()) => {
-                    if let Some(path) = self.editor.path() {
-                        self.status = format!("Saved: {}", path.display());
-                    } else {
-                        self.status =
-                            String::from("No file path. Open a file with: four-code <file>");
-                    }
-                }
-                Err(e) => {
-                    self.status = format!("Error: {e}");
-                }
-            },
+            (KeyModifiers::CONTROL, KeyCode::Char('s')) => {
+                self.save(confirm_overwrite);
+            }
 
             // === Clipboard (Ctrl+C/X/V) ===
 
@@ -315,7 +1124,10 @@ impl App {
                 if let Some(text) = self.editor.get_selected_text() {
                     let len = text.len();
                     match four_code_clipboard::copy(&text) {
-                        Ok(()) => self.status = format!("Copied {len} chars"),
+                        Ok(()) => {
+                            self.remember_block_copy(&text);
+                            self.status = format!("Copied {len} chars");
+                        }
                         Err(e) => self.status = format!("Copy failed: {e}"),
                     }
                 }
@@ -327,6 +1139,7 @@ impl App {
                     let len = text.len();
                     match four_code_clipboard::cut(&text) {
                         Ok(()) => {
+                            self.remember_block_copy(&text);
                             self.editor.delete_selection();
                             self.highlight_cache.invalidate_from(line_before);
                             self.status = format!("Cut {len} chars");
@@ -340,19 +1153,114 @@ impl App {
             (KeyModifiers::CONTROL, KeyCode::Char('v')) => match four_code_clipboard::paste() {
                 Ok(text) => {
                     let len = text.len();
-                    self.editor.replace_selection(&text);
+                    if self.block_clipboard.as_deref() == Some(&normalize_line_endings(&text)) {
+                        self.editor.paste_block(&text);
+                    } else {
+                        self.editor.paste(&text);
+                    }
                     self.highlight_cache.invalidate_from(line_before);
                     self.status = format!("Pasted {len} chars");
                 }
                 Err(e) => self.status = format!("Paste failed: {e}"),
             },
 
+            // === Undo/Redo ===
+
+            // Undo
+            (KeyModifiers::CONTROL, KeyCode::Char('z')) => {
+                if self.editor.undo() {
+                    self.highlight_cache.invalidate_all();
+                } else {
+                    self.status = String::from("Nothing to undo");
+                }
+            }
+
+            // Redo (Ctrl+Y or Ctrl+Shift+Z)
+            (KeyModifiers::CONTROL, KeyCode::Char('y')) => self.redo(),
+            (mods, KeyCode::Char('z' | 'Z'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.redo();
+            }
+
             // Select All
             (KeyModifiers::CONTROL, KeyCode::Char('a')) => {
                 self.editor.select_all();
                 self.status = String::from("Selected all");
             }
 
+            // === Multiple Cursors ===
+
+            // Ctrl+D - select word, then add next occurrence
+            (KeyModifiers::CONTROL, KeyCode::Char('d')) => self.add_next_occurrence(),
+
+            // Ctrl+Alt+Up/Down - add cursor above/below
+            (mods, KeyCode::Up) if mods == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.editor.add_cursor_above();
+            }
+            (mods, KeyCode::Down) if mods == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.editor.add_cursor_below();
+            }
+
+            // Alt+Shift+Arrows - block (column) selection
+            (mods, KeyCode::Up) if mods == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+                self.editor.extend_block_selection(-1, 0);
+            }
+            (mods, KeyCode::Down) if mods == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+                self.editor.extend_block_selection(1, 0);
+            }
+            (mods, KeyCode::Left) if mods == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+                self.editor.extend_block_selection(0, -1);
+            }
+            (mods, KeyCode::Right) if mods == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+                self.editor.extend_block_selection(0, 1);
+            }
+
+            // Ctrl+W / Ctrl+Shift+W - expand/shrink selection to the enclosing
+            // syntax node
+            (KeyModifiers::CONTROL, KeyCode::Char('w')) => self.expand_selection(),
+            (mods, KeyCode::Char('w' | 'W'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.shrink_selection();
+            }
+
+            // === Line Operations ===
+
+            // Alt+Up/Down - move lines
+            (KeyModifiers::ALT, KeyCode::Up) => self.edit_lines(Editor::move_lines_up),
+            (KeyModifiers::ALT, KeyCode::Down) => self.edit_lines(Editor::move_lines_down),
+
+            // Ctrl+Shift+D - duplicate lines
+            (mods, KeyCode::Char('d' | 'D'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.edit_lines(Editor::duplicate_lines);
+            }
+
+            // Ctrl+Shift+K - delete lines
+            (mods, KeyCode::Char('k' | 'K'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.edit_lines(Editor::delete_lines);
+            }
+
+            // Ctrl+Shift+J - join lines
+            (mods, KeyCode::Char('j' | 'J'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.edit_lines(Editor::join_lines);
+            }
+
+            // Ctrl+] (sent as Ctrl+5 by many terminals) - matching bracket
+            (KeyModifiers::CONTROL, KeyCode::Char(']' | '5')) => self.go_to_matching_bracket(),
+
+            // Esc - back to a single cursor
+            (KeyModifiers::NONE, KeyCode::Esc) => {
+                self.editor.clear_extra_cursors();
+                self.editor.clear_selection();
+            }
+
             // === Cursor Movement ===
 
             // Arrow keys (clear selection)
@@ -411,6 +1319,58 @@ impl App {
                 self.editor.move_to_end_select();
             }
 
+            // Ctrl+Left/Right - word start/end, Ctrl+Alt+Left/Right - subword
+            (KeyModifiers::CONTROL, KeyCode::Left) => {
+                self.editor.clear_selection();
+                self.editor.move_word_left();
+            }
+            (KeyModifiers::CONTROL, KeyCode::Right) => {
+                self.editor.clear_selection();
+                self.editor.move_word_right();
+            }
+            (mods, KeyCode::Left) if mods == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.editor.clear_selection();
+                self.editor.move_subword_left();
+            }
+            (mods, KeyCode::Right) if mods == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.editor.clear_selection();
+                self.editor.move_subword_right();
+            }
+
+            // Ctrl+Up/Down - previous/next paragraph
+            (KeyModifiers::CONTROL, KeyCode::Up) => {
+                self.editor.clear_selection();
+                self.editor.move_paragraph_up();
+            }
+            (KeyModifiers::CONTROL, KeyCode::Down) => {
+                self.editor.clear_selection();
+                self.editor.move_paragraph_down();
+            }
+
+            // Ctrl+Shift (+Alt) + Arrows - select by word, subword, paragraph
+            (mods, KeyCode::Left) if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+                self.editor.move_word_left_select();
+            }
+            (mods, KeyCode::Right) if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+                self.editor.move_word_right_select();
+            }
+            (mods, KeyCode::Left)
+                if mods == KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT =>
+            {
+                self.editor.move_subword_left_select();
+            }
+            (mods, KeyCode::Right)
+                if mods == KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT =>
+            {
+                self.editor.move_subword_right_select();
+            }
+            (mods, KeyCode::Up) if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+                self.editor.move_paragraph_up_select();
+            }
+            (mods, KeyCode::Down) if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT => {
+                self.editor.move_paragraph_down_select();
+            }
+
             // Page Up/Down
             (KeyModifiers::NONE, KeyCode::PageUp) => {
                 self.editor.clear_selection();
@@ -423,9 +1383,8 @@ impl App {
 
             // === Text Editing ===
 
-            // Enter (delete selection first if any)
+            // Enter (replaces the selection, if any)
             (KeyModifiers::NONE, KeyCode::Enter) => {
-                self.editor.delete_selection();
                 self.editor.insert_newline();
                 self.highlight_cache.invalidate_from(line_before);
             }
@@ -439,6 +1398,21 @@ impl App {
                     .invalidate_from(self.editor.cursor.position.line.saturating_sub(1));
             }
 
+            // Ctrl+Backspace (most terminals send Ctrl+H) or Alt+Backspace -
+            // delete word before cursor
+            (KeyModifiers::CONTROL | KeyModifiers::ALT, KeyCode::Backspace)
+            | (KeyModifiers::CONTROL, KeyCode::Char('h')) => {
+                self.editor.delete_word_left();
+                self.highlight_cache
+                    .invalidate_from(self.editor.cursor.position.line.saturating_sub(1));
+            }
+
+            // Ctrl+Delete - delete word after cursor
+            (KeyModifiers::CONTROL, KeyCode::Delete) => {
+                self.editor.delete_word_right();
+                self.highlight_cache.invalidate_from(line_before);
+            }
+
             // Delete (delete selection or char at cursor)
             (KeyModifiers::NONE, KeyCode::Delete) => {
                 if !self.editor.delete_selection() {
@@ -447,23 +1421,362 @@ impl App {
                 self.highlight_cache.invalidate_from(line_before);
             }
 
-            // Tab
+            // Tab: indent (selected lines, or up to the next indentation stop)
             (KeyModifiers::NONE, KeyCode::Tab) => {
-                self.editor.delete_selection();
-                self.editor.insert_str("    ");
-                self.highlight_cache.invalidate_line(line_before);
+                self.editor.indent();
+                self.highlight_cache.invalidate_from(line_before);
+            }
+
+            // Shift+Tab: outdent
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::BackTab) => {
+                self.editor.outdent();
+                self.highlight_cache.invalidate_from(line_before);
             }
 
             // Regular character input (replace selection)
             (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
-                self.editor.replace_selection(&c.to_string());
-                self.highlight_cache
-                    .invalidate_line(self.editor.cursor.position.line);
+                // No auto-closing pairs in strings and comments
+                let has_syntax = self.update_syntax();
+                let syntax = &self.syntax;
+                self.editor
+                    .type_char_with(c, |byte| has_syntax && syntax.is_in_literal(byte));
+                if self.editor.has_multiple_cursors() {
+                    self.highlight_cache.invalidate_from(line_before);
+                } else {
+                    self.highlight_cache
+                        .invalidate_line(self.editor.cursor.position.line);
+                }
             }
 
             _ => {}
         }
     }
+
+    /// Save the file and its undo history
+    ///
+    /// With `force`, a file changed on disk is overwritten. Returns whether
+    /// the file was saved.
+    fn save(&mut self, force: bool) -> bool {
+        let result = if force {
+            self.editor.force_save()
+        } else {
+            self.editor.save()
+        };
+        match result {
+            Ok(()) => {
+                if let Some(path) = self.editor.path() {
+                    self.status = format!("Saved: {}", path.display());
+                    if let Some(store) = &self.undo_store {
+                        if let Err(e) = store.persist(&self.editor.buffer) {
+                            self.status
+                                .push_str(&format!(" (undo history not saved: {e})"));
+                        }
+                    }
+                    if let Some(store) = &self.swap_store {
+                        let _ = store.remove(path);
+                    }
+                    self.recovery_postponed = false;
+                    true
+                } else {
+                    self.status = String::from("No file path. Open a file with: four-code <file>");
+                    false
+                }
+            }
+            Err(e @ BufferError::ChangedOnDisk { .. }) => {
+                self.confirm_overwrite = true;
+                self.status = format!("{e}. Press Ctrl+S again to overwrite it");
+                false
+            }
+            Err(e) => {
+                self.status = format!("Error: {e}");
+                false
+            }
+        }
+    }
+
+    /// Run a command from the command palette
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::SetLineEnding(line_ending) => {
+                self.editor.buffer.set_line_ending(line_ending);
+                self.status = format!("Line endings: {} (applied on save)", line_ending.name());
+            }
+            Command::ReopenWithEncoding(encoding) => {
+                if self.editor.is_modified() {
+                    self.status = String::from("Save or undo your changes before reopening");
+                    return;
+                }
+                let was_binary = self.editor.is_read_only();
+                match self.editor.reopen_with_encoding(encoding) {
+                    Ok(()) => {
+                        if let (true, Some(path)) = (was_binary, self.editor.path()) {
+                            self.highlight_cache.set_language_from_path(path);
+                            self.syntax
+                                .set_language(self.highlight_cache.current_language());
+                        }
+                        self.highlight_cache.invalidate_all();
+                        self.status = format!("Reopened with {}", encoding.name());
+                    }
+                    Err(e) => self.status = format!("Error: {e}"),
+                }
+            }
+            Command::SaveWithEncoding(encoding) => {
+                // Keep the old encoding unless saved (or the save waits for
+                // the overwrite to be confirmed)
+                let format = self.editor.buffer.format();
+                self.editor.buffer.set_encoding(encoding);
+                if !self.save(false) && !self.confirm_overwrite {
+                    self.editor.buffer.set_format(format);
+                }
+            }
+            Command::Reload => self.reload(),
+            Command::ForceSave => {
+                self.save(true);
+            }
+            Command::AddCursorAbove => self.editor.add_cursor_above(),
+            Command::AddCursorBelow => self.editor.add_cursor_below(),
+            Command::AddNextOccurrence => self.add_next_occurrence(),
+            Command::ExpandSelection => self.expand_selection(),
+            Command::ShrinkSelection => self.shrink_selection(),
+            Command::GoToMatchingBracket => self.go_to_matching_bracket(),
+            Command::ToggleRainbowBrackets => {
+                self.rainbow_brackets = !self.rainbow_brackets;
+                let state = if self.rainbow_brackets { "on" } else { "off" };
+                self.status = format!("Rainbow brackets {state}");
+            }
+            Command::ShowBufferInfo => self.buffer_info = Some(self.buffer_info()),
+            Command::GoToFile => self.open_file_finder(),
+            Command::FindInProject => self.open_project_search(false),
+            Command::ReplaceInProject => self.open_project_search(true),
+            Command::DuplicateLines => self.edit_lines(Editor::duplicate_lines),
+            Command::MoveLinesUp => self.edit_lines(Editor::move_lines_up),
+            Command::MoveLinesDown => self.edit_lines(Editor::move_lines_down),
+            Command::DeleteLines => self.edit_lines(Editor::delete_lines),
+            Command::JoinLines => self.edit_lines(Editor::join_lines),
+            Command::SortLines => self.edit_lines(Editor::sort_lines),
+            Command::ReindentSelection => {
+                self.editor.reindent_selection();
+                self.highlight_cache.invalidate_all();
+            }
+            Command::ConvertIndentation(style) => {
+                let indentation = match style {
+                    IndentStyle::Tabs => Indentation::tabs(),
+                    IndentStyle::Spaces => {
+                        let current = self.editor.buffer.indentation();
+                        Indentation::spaces(current.level_width(self.editor.buffer.tab_width()))
+                    }
+                };
+                self.editor.convert_indentation(indentation);
+                self.highlight_cache.invalidate_all();
+                self.status = format!("Indentation: {}", indentation.name());
+            }
+        }
+    }
+
+    /// Run a line operation, re-highlighting from the line above the first
+    /// affected one
+    fn edit_lines(&mut self, edit: impl FnOnce(&mut Editor)) {
+        let first_line = self
+            .editor
+            .cursors()
+            .map(|cursor| {
+                cursor
+                    .selection_range()
+                    .map_or(cursor.position.line, |(start, _)| start.line)
+            })
+            .min()
+            .unwrap_or(0);
+        edit(&mut self.editor);
+        self.highlight_cache
+            .invalidate_from(first_line.saturating_sub(1));
+    }
+
+    /// Expand the selection to the enclosing syntax node
+    fn expand_selection(&mut self) {
+        if !self.update_syntax() {
+            self.status = String::from("No syntax tree for this file");
+            return;
+        }
+        let syntax = &self.syntax;
+        let expanded = self.editor.expand_selection(|start, end| {
+            syntax
+                .enclosing_range(start..end)
+                .map(|range| (range.start, range.end))
+        });
+        if !expanded {
+            self.status = String::from("Selection covers the whole document");
+        }
+    }
+
+    /// Bring the syntax tree up to date with the buffer
+    ///
+    /// Reparses incrementally with the buffer's edits since the last parse.
+    /// Returns `false` if there is no tree (unsupported language, large file).
+    fn update_syntax(&mut self) -> bool {
+        let buffer = &self.editor.buffer;
+        if buffer.is_large_file() {
+            return false;
+        }
+        let edits = self
+            .syntax
+            .revision()
+            .and_then(|revision| buffer.edits_since(revision))
+            .map(|edits| edits.iter().map(input_edit));
+        self.syntax
+            .update(buffer.revision(), edits, |byte| buffer.chunk_at_byte(byte))
+            .is_some()
+    }
+
+    /// Matching and rainbow brackets of the visible lines
+    fn visible_brackets(&mut self) -> Brackets {
+        if !self.update_syntax() {
+            return Brackets::default();
+        }
+        let buffer = &self.editor.buffer;
+        let position_at = |byte| {
+            let (line, column) = buffer.byte_to_line_col(byte);
+            Position::new(line, column)
+        };
+
+        let cursor = self.editor.cursor.position;
+        let pair = buffer
+            .line_col_to_byte(cursor.line, cursor.column)
+            .and_then(|byte| self.syntax.bracket_pair(byte))
+            .map(|pair| (position_at(pair.open), position_at(pair.close)));
+
+        let mut depths = HashMap::new();
+        if self.rainbow_brackets {
+            let viewport = &self.editor.viewport;
+            let rope = buffer.rope();
+            let last_line = (viewport.top_line + viewport.height).min(rope.len_lines());
+            let visible =
+                rope.line_to_byte(viewport.top_line.min(last_line))..rope.line_to_byte(last_line);
+            for bracket in self.syntax.brackets(visible) {
+                depths.insert(position_at(bracket.byte), bracket.depth);
+            }
+        }
+        Brackets { pair, depths }
+    }
+
+    /// Move to the bracket matching the one at the cursor
+    ///
+    /// Inside brackets (not on one), moves to the enclosing opening bracket.
+    fn go_to_matching_bracket(&mut self) {
+        let cursor = self.editor.cursor.position;
+        let byte = self
+            .editor
+            .buffer
+            .line_col_to_byte(cursor.line, cursor.column);
+        let pair = match byte {
+            Some(byte) if self.update_syntax() => self.syntax.bracket_pair(byte).map(|p| (byte, p)),
+            _ => None,
+        };
+        let Some((byte, pair)) = pair else {
+            self.status = String::from("No bracket at the cursor");
+            return;
+        };
+        // From one bracket to the other (the cursor may be right after one)
+        let target = if byte == pair.open {
+            pair.close
+        } else if byte == pair.close || byte == pair.close + 1 {
+            pair.open
+        } else if byte == pair.open + 1 {
+            pair.close
+        } else {
+            pair.open
+        };
+        let (line, column) = self.editor.buffer.byte_to_line_col(target);
+        self.editor.go_to(Position::new(line, column));
+    }
+
+    /// Shrink the selection back to the previous expand step
+    fn shrink_selection(&mut self) {
+        if !self.editor.shrink_selection() {
+            self.status = String::from("Nothing to shrink");
+        }
+    }
+
+    /// Remember text copied from a block selection, to paste it as a block
+    fn remember_block_copy(&mut self, text: &str) {
+        self.block_clipboard = self
+            .editor
+            .block_selection()
+            .map(|_| normalize_line_endings(text).into_owned());
+    }
+
+    /// Select the next occurrence of the selection with another cursor
+    fn add_next_occurrence(&mut self) {
+        if !self.editor.add_next_occurrence() {
+            self.status = String::from("No more occurrences");
+        }
+    }
+
+    /// Redo the last undone change
+    fn redo(&mut self) {
+        if self.editor.redo() {
+            self.highlight_cache.invalidate_all();
+        } else {
+            self.status = String::from("Nothing to redo");
+        }
+    }
+}
+
+/// Check if two paths refer to the same file
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || matches!(
+            (std::fs::canonicalize(a), std::fs::canonicalize(b)),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+/// A buffer edit as tree-sitter expects it
+fn input_edit(edit: &TextEdit) -> InputEdit {
+    let point = |(line, column)| Point::new(line, column);
+    InputEdit {
+        start_byte: edit.start_byte,
+        old_end_byte: edit.old_end_byte,
+        new_end_byte: edit.new_end_byte,
+        start_position: point(edit.start_point),
+        old_end_position: point(edit.old_end_point),
+        new_end_position: point(edit.new_end_point),
+    }
+}
+
+/// Render a centered popup with a text (sized to fit the text)
+fn render_popup(frame: &mut Frame, size: Rect, title: &str, text: &str) {
+    let text_width = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let popup_width = (text_width as u16 + 4).max(44);
+    let popup_height = text.lines().count() as u16 + 2;
+    let x = size.width.saturating_sub(popup_width) / 2;
+    let y = size.height.saturating_sub(popup_height) / 2;
+    let area = Rect::new(
+        x,
+        y,
+        popup_width.min(size.width),
+        popup_height.min(size.height),
+    );
+
+    // Clear the area behind the popup
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().bg(Color::Rgb(40, 44, 52)));
+
+    let paragraph = Paragraph::new(text).block(block).style(
+        Style::default()
+            .fg(Color::White)
+            .add_modifier(Modifier::empty()),
+    );
+
+    frame.render_widget(paragraph, area);
 }
 
 impl Default for App {
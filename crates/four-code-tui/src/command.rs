@@ -0,0 +1,132 @@
+//! Editor commands available from the command palette
+
+use four_code_core::{Encoding, IndentStyle, LineEnding, ENCODINGS};
+
+/// A command that can be run from the command palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Change the line ending used when saving
+    SetLineEnding(LineEnding),
+    /// Reload the file decoded with another encoding
+    ReopenWithEncoding(Encoding),
+    /// Save the file in another encoding
+    SaveWithEncoding(Encoding),
+    /// Reload the file from disk, discarding unsaved changes
+    Reload,
+    /// Save even if the file was changed on disk
+    ForceSave,
+    /// Show file format, indentation and `.editorconfig` settings
+    ShowBufferInfo,
+    /// Add a cursor on the line above
+    AddCursorAbove,
+    /// Add a cursor on the line below
+    AddCursorBelow,
+    /// Select the next occurrence of the selection with another cursor
+    AddNextOccurrence,
+    /// Expand the selection to the enclosing syntax node
+    ExpandSelection,
+    /// Shrink the selection back to the previous expand step
+    ShrinkSelection,
+    /// Move to the bracket matching the one at the cursor
+    GoToMatchingBracket,
+    /// Turn colouring brackets by nesting depth on or off
+    ToggleRainbowBrackets,
+    /// Duplicate the selected lines
+    DuplicateLines,
+    /// Move the selected lines up
+    MoveLinesUp,
+    /// Move the selected lines down
+    MoveLinesDown,
+    /// Delete the selected lines
+    DeleteLines,
+    /// Join the selected lines (or the cursor line with the next)
+    JoinLines,
+    /// Sort the selected lines
+    SortLines,
+    /// Re-indent the selected lines with the file's indentation style
+    ReindentSelection,
+    /// Convert the file's indentation to tabs or spaces
+    ConvertIndentation(IndentStyle),
+    /// Open a project file by fuzzy name
+    GoToFile,
+    /// Search all files of the project
+    FindInProject,
+    /// Replace in all files of the project
+    ReplaceInProject,
+}
+
+impl Command {
+    /// Title shown in the command palette
+    pub fn title(&self) -> String {
+        match self {
+            Command::SetLineEnding(line_ending) => {
+                format!("Line Endings: Convert to {}", line_ending.name())
+            }
+            Command::ReopenWithEncoding(encoding) => {
+                format!("Encoding: Reopen with {}", encoding.name())
+            }
+            Command::SaveWithEncoding(encoding) => {
+                format!("Encoding: Save with {}", encoding.name())
+            }
+            Command::Reload => String::from("File: Reload from Disk"),
+            Command::ForceSave => String::from("File: Save and Overwrite Changes on Disk"),
+            Command::ShowBufferInfo => String::from("File: Show Buffer Info"),
+            Command::AddCursorAbove => String::from("Selection: Add Cursor Above"),
+            Command::AddCursorBelow => String::from("Selection: Add Cursor Below"),
+            Command::AddNextOccurrence => String::from("Selection: Add Next Occurrence"),
+            Command::ExpandSelection => String::from("Selection: Expand Selection"),
+            Command::ShrinkSelection => String::from("Selection: Shrink Selection"),
+            Command::GoToMatchingBracket => String::from("Go to: Matching Bracket"),
+            Command::ToggleRainbowBrackets => String::from("View: Toggle Rainbow Brackets"),
+            Command::DuplicateLines => String::from("Lines: Duplicate"),
+            Command::MoveLinesUp => String::from("Lines: Move Up"),
+            Command::MoveLinesDown => String::from("Lines: Move Down"),
+            Command::DeleteLines => String::from("Lines: Delete"),
+            Command::JoinLines => String::from("Lines: Join"),
+            Command::SortLines => String::from("Lines: Sort"),
+            Command::ReindentSelection => String::from("Indentation: Re-indent Selection"),
+            Command::ConvertIndentation(IndentStyle::Tabs) => {
+                String::from("Indentation: Convert to Tabs")
+            }
+            Command::ConvertIndentation(IndentStyle::Spaces) => {
+                String::from("Indentation: Convert to Spaces")
+            }
+            Command::GoToFile => String::from("File: Go to File"),
+            Command::FindInProject => String::from("Search: Find in Project"),
+            Command::ReplaceInProject => String::from("Search: Replace in Project"),
+        }
+    }
+}
+
+/// All commands, in palette order
+pub fn all_commands() -> Vec<Command> {
+    let mut commands = vec![
+        Command::GoToFile,
+        Command::Reload,
+        Command::ForceSave,
+        Command::ShowBufferInfo,
+        Command::AddCursorAbove,
+        Command::AddCursorBelow,
+        Command::AddNextOccurrence,
+        Command::ExpandSelection,
+        Command::ShrinkSelection,
+        Command::GoToMatchingBracket,
+        Command::ToggleRainbowBrackets,
+        Command::DuplicateLines,
+        Command::MoveLinesUp,
+        Command::MoveLinesDown,
+        Command::DeleteLines,
+        Command::JoinLines,
+        Command::SortLines,
+        Command::ReindentSelection,
+        Command::ConvertIndentation(IndentStyle::Tabs),
+        Command::ConvertIndentation(IndentStyle::Spaces),
+        Command::FindInProject,
+        Command::ReplaceInProject,
+        Command::SetLineEnding(LineEnding::Lf),
+        Command::SetLineEnding(LineEnding::Crlf),
+    ];
+    commands.extend(ENCODINGS.iter().map(|e| Command::ReopenWithEncoding(*e)));
+    commands.extend(ENCODINGS.iter().map(|e| Command::SaveWithEncoding(*e)));
+    commands
+}
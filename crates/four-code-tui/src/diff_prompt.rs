@@ -0,0 +1,151 @@
+//! Choice prompt with an optional diff view
+//!
+//! Used when a modified buffer's file was changed on disk (reload / keep
+//! mine) and when a swap file is found on startup (recover / discard).
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use similar::TextDiff;
+
+/// Result of a key press in the prompt
+pub enum PromptAction {
+    /// Keep the prompt open
+    None,
+    /// One of the choices (by key, lowercase); Esc picks the first one
+    Choose(char),
+    /// Show the diff (the caller provides it via [`DiffPrompt::show_diff`])
+    Diff,
+}
+
+/// Prompt state
+#[derive(Debug, Default)]
+pub struct DiffPrompt {
+    /// Popup title
+    title: String,
+    /// Question shown above the choices
+    message: String,
+    /// Choices as (key, label); `d` is reserved for the diff
+    choices: Vec<(char, &'static str)>,
+    /// Unified diff lines (when shown)
+    diff: Option<Vec<String>>,
+    /// First visible diff line
+    scroll: usize,
+}
+
+impl DiffPrompt {
+    /// Create a prompt
+    pub fn new(title: &str, message: String, choices: &[(char, &'static str)]) -> Self {
+        Self {
+            title: format!(" {title} "),
+            message,
+            choices: choices.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    /// Show a diff between two texts
+    pub fn show_diff(&mut self, old: (&str, &str), new: (&str, &str)) {
+        let diff = TextDiff::from_lines(old.1, new.1)
+            .unified_diff()
+            .context_radius(3)
+            .header(old.0, new.0)
+            .to_string();
+        self.diff = Some(diff.lines().map(str::to_string).collect());
+        self.scroll = 0;
+    }
+
+    /// Handle a key event
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptAction {
+        match key.code {
+            KeyCode::Esc => return PromptAction::Choose(self.choices[0].0),
+            KeyCode::Char('d' | 'D') if self.diff.is_none() => return PromptAction::Diff,
+            KeyCode::Char(c) => {
+                let c = c.to_ascii_lowercase();
+                if self.choices.iter().any(|(key, _)| *key == c) {
+                    return PromptAction::Choose(c);
+                }
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll_down(1),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll_down(10),
+            _ => {}
+        }
+        PromptAction::None
+    }
+
+    /// Scroll the diff down (stops at the last line)
+    fn scroll_down(&mut self, lines: usize) {
+        let len = self.diff.as_ref().map_or(0, Vec::len);
+        self.scroll = (self.scroll + lines).min(len.saturating_sub(1));
+    }
+
+    /// Render the prompt (centered; large when showing the diff)
+    pub fn render(&self, frame: &mut Frame, size: Rect) {
+        let mut choices: Vec<String> = self
+            .choices
+            .iter()
+            .map(|(key, label)| format!("[{}] {label}", key.to_ascii_uppercase()))
+            .collect();
+        if self.diff.is_none() {
+            choices.push(String::from("[D] Show diff"));
+        }
+        let mut lines = vec![
+            Line::from(format!("  {}", self.message)),
+            Line::styled(
+                format!("  {}", choices.join("   ")),
+                Style::default().fg(Color::Yellow),
+            ),
+        ];
+
+        let (width, height) = match &self.diff {
+            Some(diff) => {
+                lines.push(Line::from(""));
+                let visible = size.height.saturating_sub(8) as usize;
+                let scroll = self.scroll.min(diff.len().saturating_sub(visible));
+                for line in diff.iter().skip(scroll).take(visible) {
+                    let color = match line.chars().next() {
+                        Some('+') => Color::Green,
+                        Some('-') => Color::Red,
+                        Some('@') => Color::Cyan,
+                        _ => Color::White,
+                    };
+                    lines.push(Line::styled(
+                        format!("  {line}"),
+                        Style::default().fg(color),
+                    ));
+                }
+                (size.width.saturating_sub(8), size.height.saturating_sub(4))
+            }
+            None => (self.message.len().max(56) as u16 + 6, 4),
+        };
+
+        let width = width.min(size.width);
+        let height = height.min(size.height);
+        let area = Rect::new(
+            size.width.saturating_sub(width) / 2,
+            size.height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+
+        frame.render_widget(Clear, area);
+
+        let block = Block::default()
+            .title(self.title.as_str())
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow))
+            .style(Style::default().bg(Color::Rgb(40, 44, 52)));
+
+        let paragraph = Paragraph::new(lines)
+            .block(block)
+            .style(Style::default().fg(Color::White));
+        frame.render_widget(paragraph, area);
+    }
+}
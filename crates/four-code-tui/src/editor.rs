@@ -1,6 +1,6 @@
 //! Editor widget for rendering the text buffer with syntax highlighting
 
-use four_code_core::Editor;
+use four_code_core::{grapheme_width, Editor, Position, SearchMatch};
 use four_code_highlight::HighlightCache;
 use ratatui::{
     buffer::Buffer as RatatuiBuffer,
@@ -8,12 +8,34 @@ use ratatui::{
     style::{Color, Modifier, Style},
     widgets::Widget,
 };
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Colours of bracket nesting levels (rainbow brackets)
+const BRACKET_COLORS: [Color; 3] = [
+    Color::Rgb(255, 215, 0),   // Gold
+    Color::Rgb(218, 112, 214), // Orchid
+    Color::Rgb(23, 159, 255),  // Blue
+];
+
+/// Bracket highlights of the visible lines
+#[derive(Debug, Clone, Default)]
+pub struct Brackets {
+    /// Bracket pair matching the cursor
+    pub pair: Option<(Position, Position)>,
+    /// Nesting depth of visible brackets (for rainbow colours; empty if off)
+    pub depths: HashMap<Position, usize>,
+}
 
 /// Widget for rendering the editor content
 pub struct EditorWidget<'a> {
     editor: &'a Editor,
     highlight_cache: &'a mut HighlightCache,
     line_number_width: usize,
+    brackets: Brackets,
+    ruler: Option<usize>,
+    search_matches: &'a [SearchMatch],
+    current_match: Option<usize>,
 }
 
 impl<'a> EditorWidget<'a> {
@@ -26,12 +48,57 @@ impl<'a> EditorWidget<'a> {
             editor,
             highlight_cache,
             line_number_width,
+            brackets: Brackets::default(),
+            ruler: None,
+            search_matches: &[],
+            current_match: None,
         }
     }
 
-    /// Check if a position is within selection
+    /// Highlight matching and rainbow brackets
+    pub fn brackets(mut self, brackets: Brackets) -> Self {
+        self.brackets = brackets;
+        self
+    }
+
+    /// Draw a vertical ruler after a number of display columns (the
+    /// `.editorconfig` max_line_length)
+    pub fn ruler(mut self, ruler: Option<usize>) -> Self {
+        self.ruler = ruler;
+        self
+    }
+
+    /// Highlight search matches (in document order), `current` being the
+    /// selected one
+    pub fn search_matches(mut self, matches: &'a [SearchMatch], current: Option<usize>) -> Self {
+        self.search_matches = matches;
+        self.current_match = current;
+        self
+    }
+
+    /// Syntax style of a position, with bracket colours applied
+    fn bracket_style(&self, line: usize, col: usize, style: Style) -> Style {
+        let position = Position::new(line, col);
+        let mut style = match self.brackets.depths.get(&position) {
+            Some(depth) => style.fg(BRACKET_COLORS[depth % BRACKET_COLORS.len()]),
+            None => style,
+        };
+        if let Some((open, close)) = self.brackets.pair {
+            if position == open || position == close {
+                style = style
+                    .bg(Color::Rgb(62, 68, 81))
+                    .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+            }
+        }
+        style
+    }
+
+    /// Check if a position is within the selection of any cursor
     fn is_selected(&self, line: usize, col: usize) -> bool {
-        if let Some((start, end)) = self.editor.cursor.selection_range() {
+        self.editor.cursors().any(|cursor| {
+            let Some((start, end)) = cursor.selection_range() else {
+                return false;
+            };
             if line < start.line || line > end.line {
                 return false;
             }
@@ -48,9 +115,16 @@ impl<'a> EditorWidget<'a> {
                 // Middle line of multi-line selection
                 true
             }
-        } else {
-            false
-        }
+        })
+    }
+
+    /// Check if an additional cursor is at a position (the terminal cursor
+    /// only shows the primary one)
+    fn is_extra_cursor(&self, line: usize, col: usize) -> bool {
+        self.editor
+            .extra_cursors
+            .iter()
+            .any(|cursor| cursor.position.line == line && cursor.position.column == col)
     }
 }
 
@@ -61,9 +135,15 @@ impl Widget for EditorWidget<'_> {
         let selection_style = Style::default()
             .bg(Color::Rgb(68, 71, 90)) // Subtle blue-gray selection
             .add_modifier(Modifier::BOLD);
+        let match_style = Style::default().bg(Color::Rgb(97, 80, 30)); // Dim amber
+        let current_match_style = Style::default()
+            .fg(Color::Black)
+            .bg(Color::Rgb(255, 191, 0)); // Amber
 
         let viewport = &self.editor.viewport;
         let cursor_line = self.editor.cursor.position.line;
+        let content_x = area.x + self.line_number_width as u16;
+        let available_width = area.width.saturating_sub(self.line_number_width as u16) as usize;
 
         for (screen_row, y) in (area.y..area.y + area.height).enumerate() {
             let buffer_line = viewport.top_line + screen_row;
@@ -83,14 +163,17 @@ impl Widget for EditorWidget<'_> {
                 buf.set_string(area.x, y, &line_num, num_style);
 
                 // Line content with syntax highlighting
-                let content_x = area.x + self.line_number_width as u16;
-                let available_width =
-                    area.width.saturating_sub(self.line_number_width as u16) as usize;
-
-                let line_str = line.to_string();
-                let line_text: String = line_str
+                // Large files (e.g. SQL dumps) can have lines of several MB:
+                // only take the visible part
+                let visible_chars = if self.editor.buffer.is_large_file() {
+                    available_width
+                } else {
+                    usize::MAX
+                };
+                let line_text: String = line
                     .chars()
                     .filter(|c| *c != '\n' && *c != '\r')
+                    .take(visible_chars)
                     .collect();
 
                 // Get highlighted segments for this line
@@ -100,36 +183,91 @@ impl Widget for EditorWidget<'_> {
                     unsafe { &mut *(self.highlight_cache as *const _ as *mut HighlightCache) };
                 let segments = highlight_cache.get_line(buffer_line, &line_text);
 
+                // Search matches on this line (with their indices)
+                let first_match = self
+                    .search_matches
+                    .partition_point(|m| m.line < buffer_line);
+                let line_matches = self.search_matches[first_match..]
+                    .iter()
+                    .take_while(|m| m.line == buffer_line);
+                let match_at = |col: usize| {
+                    line_matches
+                        .clone()
+                        .position(|m| (m.start..m.end).contains(&col))
+                        .map(|i| first_match + i)
+                };
+
+                // One grapheme cluster at a time: `col` counts chars (for the
+                // selection), `cell` counts display cells
+                let tab_width = self.editor.buffer.tab_width();
                 let mut col = 0;
-                for segment in segments {
-                    for ch in segment.text.chars() {
-                        if col >= available_width {
-                            break;
+                let mut cell = 0;
+                'segments: for segment in segments {
+                    for grapheme in segment.text.graphemes(true) {
+                        let width = grapheme_width(grapheme, cell, tab_width);
+                        if cell + width > available_width {
+                            break 'segments;
                         }
 
-                        let x = content_x + col as u16;
-                        if x >= area.x + area.width {
-                            break;
-                        }
+                        // A block selection is a rectangle of cells
+                        let selected = match self.editor.block_selection() {
+                            Some(block) => block.contains(buffer_line, cell),
+                            None => self.is_selected(buffer_line, col),
+                        };
 
                         // Apply selection style if selected, otherwise use syntax style
-                        let style = if self.is_selected(buffer_line, col) {
-                            // Merge selection background with syntax foreground
-                            selection_style.fg(segment.style.fg.unwrap_or(Color::White))
+                        let syntax_style = self.bracket_style(buffer_line, col, segment.style);
+                        // The current search match stands out from the selection
+                        let search_match = match_at(col);
+                        let mut style =
+                            if search_match.is_some() && search_match == self.current_match {
+                                current_match_style
+                            } else if selected {
+                                // Merge selection background with syntax foreground
+                                selection_style.fg(syntax_style.fg.unwrap_or(Color::White))
+                            } else if search_match.is_some() {
+                                syntax_style.patch(match_style)
+                            } else {
+                                syntax_style
+                            };
+                        if self.is_extra_cursor(buffer_line, col) {
+                            style = style.add_modifier(Modifier::REVERSED);
+                        }
+
+                        // Tabs and control characters are drawn as blanks
+                        let x = content_x + cell as u16;
+                        if grapheme.chars().any(char::is_control) {
+                            buf.set_string(x, y, " ".repeat(width), style);
                         } else {
-                            segment.style
-                        };
+                            buf.set_string(x, y, grapheme, style);
+                        }
+                        col += grapheme.chars().count();
+                        cell += width;
+                    }
+                }
 
-                        buf.set_string(x, y, ch.to_string(), style);
-                        col += 1;
+                // A block selection extends past the end of short lines
+                if let Some(block) = self.editor.block_selection() {
+                    if block.lines().contains(&buffer_line) {
+                        let columns = block.columns();
+                        for fill in columns.start.max(cell)..columns.end.min(available_width) {
+                            buf.set_string(content_x + fill as u16, y, " ", selection_style);
+                        }
                     }
                 }
 
-                // If selection extends beyond line content, show it
-                let line_len = line_text.len();
-                if self.is_selected(buffer_line, line_len) && line_len < available_width {
-                    let x = content_x + line_len as u16;
-                    if x < area.x + area.width {
+                // If selection extends beyond line content, show it, as well
+                // as additional cursors at the line end
+                if cell < available_width {
+                    let x = content_x + cell as u16;
+                    if self.is_extra_cursor(buffer_line, col) {
+                        buf.set_string(
+                            x,
+                            y,
+                            " ",
+                            Style::default().add_modifier(Modifier::REVERSED),
+                        );
+                    } else if self.is_selected(buffer_line, col) {
                         buf.set_string(x, y, " ", selection_style);
                     }
                 }
@@ -138,6 +276,13 @@ impl Widget for EditorWidget<'_> {
                 let tilde = format!("{:>width$}~", "", width = self.line_number_width - 1);
                 buf.set_string(area.x, y, &tilde, Style::default().fg(Color::DarkGray));
             }
+
+            // Ruler at the maximum line length
+            if let Some(ruler) = self.ruler.filter(|&ruler| ruler < available_width) {
+                if let Some(cell) = buf.cell_mut((content_x + ruler as u16, y)) {
+                    cell.set_bg(Color::Rgb(50, 54, 62));
+                }
+            }
         }
     }
 }
@@ -0,0 +1,414 @@
+//! Fuzzy file finder popup (Ctrl+P)
+//!
+//! Ranks the files of the project index against the query while typing.
+//! Typing more narrows down the files matched by the shorter query instead
+//! of going through the whole index again. Recently opened files rank
+//! higher, and are listed first while the query is empty.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use four_code_core::{FileIndex, FuzzyMatch, FuzzyQuery};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Maximum number of files shown at once
+const MAX_VISIBLE: usize = 15;
+
+/// Maximum number of ranked files kept (to scroll through)
+const MAX_RESULTS: usize = 200;
+
+/// Score bonus of the most recently opened file (one less per older one)
+const RECENT_BONUS: i32 = 40;
+
+/// Result of a key press in the finder
+pub enum FileFinderAction {
+    /// Keep the finder open
+    None,
+    /// Close the finder
+    Close,
+    /// Close the finder and open a file
+    Open(PathBuf),
+}
+
+/// A ranked file
+#[derive(Debug)]
+struct Ranked {
+    /// Index into the files
+    file: usize,
+    /// Char positions of the matched query chars
+    positions: Vec<usize>,
+    /// The file was opened recently
+    recent: bool,
+}
+
+/// File finder state
+#[derive(Debug)]
+pub struct FileFinder {
+    /// Query text
+    input: String,
+    /// Index of the selected file in the results
+    selected: usize,
+    /// First visible result
+    scroll: usize,
+    /// Recently opened files (relative paths, most recent first)
+    recent: Vec<String>,
+    /// Index revision the files are from (None: take a new snapshot)
+    revision: Option<u64>,
+    /// The indexed files (relative paths)
+    files: Arc<Vec<String>>,
+    /// Files matching `candidates_query` (narrowed down while typing)
+    candidates: Vec<usize>,
+    /// Query the candidates were matched with (None: all files)
+    candidates_query: Option<String>,
+    /// Query the results were ranked for (None: rank again)
+    results_query: Option<String>,
+    /// Best matching files, best first
+    results: Vec<Ranked>,
+}
+
+impl FileFinder {
+    /// Open the finder; `recent` are the recently opened files (relative
+    /// to the project root, most recent first)
+    pub fn new(recent: Vec<String>) -> Self {
+        Self {
+            input: String::new(),
+            selected: 0,
+            scroll: 0,
+            recent,
+            revision: None,
+            files: Arc::default(),
+            candidates: Vec::new(),
+            candidates_query: None,
+            results_query: None,
+            results: Vec::new(),
+        }
+    }
+
+    /// Rank the files again if the query or the index changed
+    pub fn update(&mut self, index: &FileIndex) {
+        let (revision, files) = index.snapshot();
+        if self.revision != Some(revision) {
+            self.revision = Some(revision);
+            self.files = files;
+            self.candidates_query = None;
+            self.results_query = None;
+        }
+        if self.results_query.as_deref() != Some(self.input.as_str()) {
+            self.rank();
+            self.results_query = Some(self.input.clone());
+        }
+    }
+
+    /// Rank the files for the query
+    fn rank(&mut self) {
+        let query = FuzzyQuery::new(&self.input);
+        let recent: HashMap<&str, usize> = self
+            .recent
+            .iter()
+            .enumerate()
+            .map(|(rank, path)| (path.as_str(), rank))
+            .collect();
+        let recent_bonus = |path: &str| {
+            recent
+                .get(path)
+                .map(|&rank| RECENT_BONUS.saturating_sub(rank as i32).max(1))
+        };
+
+        // Without a query: the recent files, then the others in path order
+        if query.is_empty() {
+            let mut results: Vec<Ranked> = (0..self.files.len())
+                .filter(|&file| recent.contains_key(self.files[file].as_str()))
+                .map(|file| Ranked {
+                    file,
+                    positions: Vec::new(),
+                    recent: true,
+                })
+                .collect();
+            results.sort_by_key(|ranked| recent[self.files[ranked.file].as_str()]);
+            let others = (0..self.files.len())
+                .filter(|&file| !recent.contains_key(self.files[file].as_str()))
+                .take(MAX_RESULTS)
+                .map(|file| Ranked {
+                    file,
+                    positions: Vec::new(),
+                    recent: false,
+                });
+            results.extend(others);
+            results.truncate(MAX_RESULTS);
+            self.set_results(results);
+            self.candidates_query = None;
+            return;
+        }
+
+        // A longer query only matches files the shorter one matched
+        let narrowing = self
+            .candidates_query
+            .as_ref()
+            .is_some_and(|previous| self.input.starts_with(previous.as_str()));
+        let files = &self.files;
+        self.candidates = if narrowing {
+            std::mem::take(&mut self.candidates)
+                .into_iter()
+                .filter(|&file| query.is_match(&files[file]))
+                .collect()
+        } else {
+            (0..files.len())
+                .filter(|&file| query.is_match(&files[file]))
+                .collect()
+        };
+        self.candidates_query = Some(self.input.clone());
+
+        let mut scored: Vec<(i32, usize, FuzzyMatch)> = self
+            .candidates
+            .iter()
+            .filter_map(|&file| {
+                let path = files[file].as_str();
+                let m = query.score(path)?;
+                Some((m.score + recent_bonus(path).unwrap_or(0), file, m))
+            })
+            .collect();
+        // Best first; ties go to the shorter path
+        let order = |a: &(i32, usize, FuzzyMatch), b: &(i32, usize, FuzzyMatch)| {
+            b.0.cmp(&a.0)
+                .then(files[a.1].len().cmp(&files[b.1].len()))
+                .then(files[a.1].cmp(&files[b.1]))
+        };
+        if scored.len() > MAX_RESULTS {
+            scored.select_nth_unstable_by(MAX_RESULTS, order);
+            scored.truncate(MAX_RESULTS);
+        }
+        scored.sort_unstable_by(order);
+        let results = scored
+            .into_iter()
+            .map(|(_, file, m)| Ranked {
+                file,
+                positions: m.positions,
+                recent: recent.contains_key(files[file].as_str()),
+            })
+            .collect();
+        self.set_results(results);
+    }
+
+    /// Replace the results, selecting the best one
+    fn set_results(&mut self, results: Vec<Ranked>) {
+        self.results = results;
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    /// Handle a key event
+    pub fn handle_key(&mut self, key: KeyEvent) -> FileFinderAction {
+        let last = self.results.len().saturating_sub(1);
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) => return FileFinderAction::Close,
+            (_, KeyCode::Enter) => {
+                return match self.results.get(self.selected) {
+                    Some(ranked) => {
+                        FileFinderAction::Open(PathBuf::from(self.files[ranked.file].as_str()))
+                    }
+                    None => FileFinderAction::Close,
+                };
+            }
+            (_, KeyCode::Up) => self.selected = self.selected.saturating_sub(1),
+            (_, KeyCode::Down) => self.selected = (self.selected + 1).min(last),
+            (_, KeyCode::PageUp) => self.selected = self.selected.saturating_sub(MAX_VISIBLE),
+            (_, KeyCode::PageDown) => self.selected = (self.selected + MAX_VISIBLE).min(last),
+            (_, KeyCode::Backspace) => {
+                self.input.pop();
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.input.push(c);
+            }
+            _ => {}
+        }
+        FileFinderAction::None
+    }
+
+    /// Render the finder at the top of the screen (`indexing` while the
+    /// initial walk is running)
+    pub fn render(&mut self, frame: &mut Frame, size: Rect, indexing: bool) {
+        let visible = self.results.len().clamp(1, MAX_VISIBLE);
+        let width = 80u16.min(size.width);
+        let height = (visible as u16 + 3).min(size.height);
+        let area = Rect::new(size.width.saturating_sub(width) / 2, 1, width, height);
+
+        frame.render_widget(Clear, area);
+
+        let mut lines = vec![Line::styled(
+            format!("> {}", self.input),
+            Style::default().fg(Color::Yellow),
+        )];
+        if self.results.is_empty() {
+            lines.push(Line::styled(
+                if indexing {
+                    "  Indexing files..."
+                } else {
+                    "  No matching files"
+                },
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+
+        // Keep the selection in view
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + MAX_VISIBLE {
+            self.scroll = self.selected + 1 - MAX_VISIBLE;
+        }
+        for (i, ranked) in self
+            .results
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(MAX_VISIBLE)
+        {
+            lines.push(self.result_line(ranked, i == self.selected));
+        }
+
+        let title = if indexing {
+            format!(" Go to File ({} files, indexing...) ", self.files.len())
+        } else {
+            format!(" Go to File ({} files) ", self.files.len())
+        };
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow))
+            .style(Style::default().bg(Color::Rgb(40, 44, 52)));
+        let paragraph = Paragraph::new(lines)
+            .block(block)
+            .style(Style::default().fg(Color::White));
+        frame.render_widget(paragraph, area);
+    }
+
+    /// A result row: the path with the matched chars highlighted (recent
+    /// files marked)
+    fn result_line(&self, ranked: &Ranked, selected: bool) -> Line<'static> {
+        let base = if selected {
+            Style::default()
+                .bg(Color::Rgb(68, 71, 90))
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let matched = base.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+
+        let mut spans = vec![Span::styled(
+            if ranked.recent { "  * " } else { "    " },
+            base.fg(Color::DarkGray),
+        )];
+        let mut positions = ranked.positions.iter().peekable();
+        let mut run = String::new();
+        let mut run_matched = false;
+        for (i, c) in self.files[ranked.file].chars().enumerate() {
+            let is_match = positions.next_if_eq(&&i).is_some();
+            if is_match != run_matched && !run.is_empty() {
+                let style = if run_matched { matched } else { base };
+                spans.push(Span::styled(std::mem::take(&mut run), style));
+            }
+            run_matched = is_match;
+            run.push(c);
+        }
+        let style = if run_matched { matched } else { base };
+        spans.push(Span::styled(run, style));
+        Line::from(spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn finder(files: &[&str], recent: &[&str]) -> FileFinder {
+        let mut finder = FileFinder::new(recent.iter().map(|path| path.to_string()).collect());
+        finder.files = Arc::new(files.iter().map(|path| path.to_string()).collect());
+        finder.rank();
+        finder
+    }
+
+    fn type_text(finder: &mut FileFinder, text: &str) {
+        for c in text.chars() {
+            finder.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        finder.rank();
+    }
+
+    fn backspace(finder: &mut FileFinder) {
+        finder.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        finder.rank();
+    }
+
+    fn results(finder: &FileFinder) -> Vec<&str> {
+        finder
+            .results
+            .iter()
+            .map(|ranked| finder.files[ranked.file].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_query_lists_recent_files_first() {
+        let finder = finder(
+            &["src/a.rs", "src/b.rs", "README.md", "docs/c.md"],
+            &["README.md", "src/b.rs"],
+        );
+        assert_eq!(
+            results(&finder),
+            ["README.md", "src/b.rs", "src/a.rs", "docs/c.md"]
+        );
+        assert!(finder.results[1].recent && !finder.results[2].recent);
+    }
+
+    #[test]
+    fn test_narrowing_and_reranking() {
+        let files = ["src/app.rs", "src/apple.rs", "tests/api.rs", "docs/x.md"];
+        let mut finder = finder(&files, &[]);
+        type_text(&mut finder, "ap");
+        assert_eq!(finder.candidates, [0, 1, 2]);
+        assert_eq!(finder.candidates_query.as_deref(), Some("ap"));
+
+        // A longer query only looks at the candidates of the shorter one
+        finder.candidates.retain(|&file| file != 1);
+        type_text(&mut finder, "p");
+        assert_eq!(results(&finder), ["src/app.rs"]);
+
+        // A query that isn't an extension goes through all files again
+        backspace(&mut finder);
+        backspace(&mut finder);
+        assert_eq!(finder.candidates, [0, 1, 2]);
+        type_text(&mut finder, "ppl");
+        assert_eq!(results(&finder), ["src/apple.rs"]);
+    }
+
+    #[test]
+    fn test_recent_files_rank_higher() {
+        let files = ["lib/user.rs", "src/user.rs"];
+        let mut finder = finder(&files, &[]);
+        type_text(&mut finder, "user");
+        assert_eq!(results(&finder), ["lib/user.rs", "src/user.rs"]);
+
+        let mut finder = self::finder(&files, &["src/user.rs"]);
+        type_text(&mut finder, "user");
+        assert_eq!(results(&finder), ["src/user.rs", "lib/user.rs"]);
+
+        // Enter opens the selected file, Esc closes
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        finder.handle_key(key(KeyCode::Down));
+        assert!(matches!(
+            finder.handle_key(key(KeyCode::Enter)),
+            FileFinderAction::Open(path) if path == Path::new("lib/user.rs")
+        ));
+        assert!(matches!(
+            finder.handle_key(key(KeyCode::Esc)),
+            FileFinderAction::Close
+        ));
+    }
+}
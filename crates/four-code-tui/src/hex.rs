@@ -0,0 +1,96 @@
+//! Hex view widget for binary files
+
+use four_code_core::{Editor, BYTES_PER_ROW};
+use ratatui::{
+    buffer::Buffer as RatatuiBuffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::Widget,
+};
+
+/// Width of the offset column including padding
+const OFFSET_WIDTH: usize = 10;
+
+/// Start of the ASCII column
+const ASCII_X: usize = OFFSET_WIDTH + BYTES_PER_ROW * 3 + 2;
+
+/// Screen column of a byte in the hex column
+///
+/// Bytes are grouped by 8 with an extra space between the groups.
+pub fn hex_column_x(column: usize) -> usize {
+    OFFSET_WIDTH + column * 3 + usize::from(column >= BYTES_PER_ROW / 2)
+}
+
+/// Widget rendering a binary file as offset, hex and ASCII columns
+pub struct HexWidget<'a> {
+    editor: &'a Editor,
+}
+
+impl<'a> HexWidget<'a> {
+    /// Create a new hex widget
+    pub fn new(editor: &'a Editor) -> Self {
+        Self { editor }
+    }
+}
+
+impl Widget for HexWidget<'_> {
+    fn render(self, area: Rect, buf: &mut RatatuiBuffer) {
+        let Some(hex) = &self.editor.hex else {
+            return;
+        };
+
+        let offset_style = Style::default().fg(Color::DarkGray);
+        let current_offset_style = Style::default().fg(Color::Yellow);
+        let byte_style = Style::default().fg(Color::White);
+        let zero_style = Style::default().fg(Color::DarkGray);
+        let cursor_style = Style::default()
+            .bg(Color::Rgb(68, 71, 90))
+            .add_modifier(Modifier::BOLD);
+
+        let right = area.x + area.width;
+        let put = |buf: &mut RatatuiBuffer, x: usize, y: u16, text: &str, style: Style| {
+            let x = area.x + x as u16;
+            if x < right {
+                buf.set_stringn(x, y, text, (right - x) as usize, style);
+            }
+        };
+
+        for (screen_row, y) in (area.y..area.y + area.height).enumerate() {
+            let row = self.editor.viewport.top_line + screen_row;
+            if row >= hex.len_rows() {
+                put(buf, 0, y, "~", offset_style);
+                continue;
+            }
+
+            let offset = row * BYTES_PER_ROW;
+            let style = if row == hex.cursor_row() {
+                current_offset_style
+            } else {
+                offset_style
+            };
+            put(buf, 0, y, &format!("{offset:08x}"), style);
+
+            let bytes = hex.row(row);
+            put(buf, ASCII_X - 1, y, "|", offset_style);
+            put(buf, ASCII_X + BYTES_PER_ROW, y, "|", offset_style);
+
+            for (column, &byte) in bytes.iter().enumerate() {
+                let style = if offset + column == hex.cursor() {
+                    cursor_style
+                } else if byte == 0 {
+                    zero_style
+                } else {
+                    byte_style
+                };
+                put(buf, hex_column_x(column), y, &format!("{byte:02x}"), style);
+
+                let (ch, ascii_style) = if byte.is_ascii_graphic() || byte == b' ' {
+                    (char::from(byte), style)
+                } else {
+                    ('.', style.fg(Color::DarkGray))
+                };
+                put(buf, ASCII_X + column, y, &ch.to_string(), ascii_style);
+            }
+        }
+    }
+}
@@ -6,7 +6,14 @@
 //! - Keymap handling
 
 mod app;
+mod command;
+mod diff_prompt;
 mod editor;
+mod file_finder;
+mod hex;
+mod palette;
+mod project_search;
+mod search_bar;
 
 pub use app::App;
-pub use editor::EditorWidget;
+pub use editor::{Brackets, EditorWidget};
@@ -0,0 +1,133 @@
+//! Command palette popup (F2 / Ctrl+Shift+P)
+
+use crate::command::{all_commands, Command};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Maximum number of commands shown at once
+const MAX_VISIBLE: usize = 12;
+
+/// Result of a key press in the palette
+pub enum PaletteAction {
+    /// Keep the palette open
+    None,
+    /// Close the palette
+    Close,
+    /// Close the palette and run a command
+    Run(Command),
+}
+
+/// Command palette state
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+    /// Filter text
+    input: String,
+    /// Index of the selected command in the filtered list
+    selected: usize,
+}
+
+impl CommandPalette {
+    /// Create an empty palette
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Commands matching the filter (every word must appear in the title)
+    fn matches(&self) -> Vec<Command> {
+        let words: Vec<String> = self
+            .input
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        all_commands()
+            .into_iter()
+            .filter(|cmd| {
+                let title = cmd.title().to_lowercase();
+                words.iter().all(|word| title.contains(word.as_str()))
+            })
+            .collect()
+    }
+
+    /// Handle a key event
+    pub fn handle_key(&mut self, key: KeyEvent) -> PaletteAction {
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) => return PaletteAction::Close,
+            (_, KeyCode::Enter) => {
+                return match self.matches().get(self.selected) {
+                    Some(cmd) => PaletteAction::Run(*cmd),
+                    None => PaletteAction::Close,
+                };
+            }
+            (_, KeyCode::Up) => self.selected = self.selected.saturating_sub(1),
+            (_, KeyCode::Down) => {
+                let count = self.matches().len();
+                if self.selected + 1 < count {
+                    self.selected += 1;
+                }
+            }
+            (_, KeyCode::Backspace) => {
+                self.input.pop();
+                self.selected = 0;
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.input.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        PaletteAction::None
+    }
+
+    /// Render the palette at the top of the screen
+    pub fn render(&self, frame: &mut Frame, size: Rect) {
+        let matches = self.matches();
+        let visible = matches.len().clamp(1, MAX_VISIBLE);
+
+        let width = 60u16.min(size.width);
+        let height = (visible as u16 + 3).min(size.height);
+        let area = Rect::new(size.width.saturating_sub(width) / 2, 1, width, height);
+
+        frame.render_widget(Clear, area);
+
+        let mut lines = vec![Line::styled(
+            format!("> {}", self.input),
+            Style::default().fg(Color::Yellow),
+        )];
+        if matches.is_empty() {
+            lines.push(Line::styled(
+                "  No matching commands",
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+
+        // Keep the selection in view
+        let offset = self.selected.saturating_sub(MAX_VISIBLE - 1);
+        for (i, cmd) in matches.iter().enumerate().skip(offset).take(MAX_VISIBLE) {
+            let style = if i == self.selected {
+                Style::default()
+                    .bg(Color::Rgb(68, 71, 90))
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::styled(format!("  {}", cmd.title()), style));
+        }
+
+        let block = Block::default()
+            .title(" Commands ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow))
+            .style(Style::default().bg(Color::Rgb(40, 44, 52)));
+
+        let paragraph = Paragraph::new(lines)
+            .block(block)
+            .style(Style::default().fg(Color::White));
+        frame.render_widget(paragraph, area);
+    }
+}
@@ -0,0 +1,872 @@
+//! Project search and replace panel (Ctrl+Shift+F, Ctrl+Shift+R)
+//!
+//! Query and include / exclude globs at the top, the results grouped by file
+//! below. Results stream in while the search runs; Esc cancels a running
+//! search, or closes the panel. With the replace field open, every match
+//! shows its replacement and can be left out of the replace (a checklist).
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use four_code_core::{
+    FileFilter, FileMatches, ProjectSearch, Replacement, SearchMatch, SearchOptions, SearchQuery,
+    WordChars,
+};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Width of the input labels
+const LABEL_WIDTH: usize = 10;
+
+/// Result of a key press in the panel
+pub enum ProjectSearchAction {
+    /// Handled, nothing else to do
+    None,
+    /// Close the panel
+    Close,
+    /// Open a file at a match
+    Open(PathBuf, SearchMatch),
+    /// Replace the checked matches
+    Replace(ProjectReplace),
+}
+
+/// A replace across files, confirmed in the panel
+pub struct ProjectReplace {
+    /// The query the matches were found with
+    pub query: SearchQuery,
+    pub replacement: Replacement,
+    /// Checked matches per file, in line order
+    pub files: Vec<(PathBuf, Vec<SearchMatch>)>,
+}
+
+/// Input field with the keyboard focus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Query,
+    Replace,
+    Include,
+    Exclude,
+}
+
+/// A row of the results list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    /// File header (index into the results)
+    File(usize),
+    /// Match (file index, match index)
+    Match(usize, usize),
+}
+
+/// Inputs a search was started with
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Inputs {
+    query: String,
+    include: String,
+    exclude: String,
+    options: SearchOptions,
+}
+
+/// Project search panel state
+#[derive(Debug)]
+pub struct ProjectSearchPanel {
+    /// Directory searched
+    root: PathBuf,
+    /// Query, globs and toggles being edited
+    inputs: Inputs,
+    /// Replacement text (None while the replace field is hidden)
+    replace: Option<String>,
+    /// Adapt replacements to the case of the replaced text
+    preserve_case: bool,
+    /// Field typing goes to
+    field: Field,
+    /// The panel has the keyboard focus (otherwise the editor has it)
+    focused: bool,
+    /// The running or finished search
+    search: Option<ProjectSearch>,
+    /// The query of the last search (replacements are computed with it)
+    query: Option<SearchQuery>,
+    /// Inputs of the last search (Enter opens the selected match while they
+    /// are unchanged)
+    searched: Option<Inputs>,
+    /// Files with matches, sorted by path
+    results: Vec<FileMatches>,
+    /// Matches unchecked for replacing
+    excluded: HashSet<(PathBuf, SearchMatch)>,
+    /// Waiting for the replace to be confirmed
+    confirm: bool,
+    /// Selected match (file index, match index)
+    selected: (usize, usize),
+    /// First visible result row
+    scroll: usize,
+    /// Error of an invalid query or glob
+    error: Option<String>,
+}
+
+impl ProjectSearchPanel {
+    /// Open the panel with an initial query (e.g. the selected text) and the
+    /// default exclude globs
+    pub fn new(root: PathBuf, query: String, exclude: &[String]) -> Self {
+        Self {
+            root,
+            inputs: Inputs {
+                query,
+                include: String::new(),
+                exclude: exclude.join(", "),
+                options: SearchOptions::default(),
+            },
+            replace: None,
+            preserve_case: false,
+            field: Field::Query,
+            focused: true,
+            search: None,
+            query: None,
+            searched: None,
+            results: Vec::new(),
+            excluded: HashSet::new(),
+            confirm: false,
+            selected: (0, 0),
+            scroll: 0,
+            error: None,
+        }
+    }
+
+    /// Check if the panel has the keyboard focus
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Give the keyboard focus to the panel (or back to the editor)
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Replace the query and focus its field
+    pub fn set_query(&mut self, query: String) {
+        self.inputs.query = query;
+        self.field = Field::Query;
+    }
+
+    /// Show the replace field and focus it
+    pub fn show_replace(&mut self) {
+        self.replace.get_or_insert_with(String::new);
+        self.field = Field::Replace;
+    }
+
+    /// Search again with the inputs of the last search (e.g. after
+    /// replacing)
+    pub fn search_again(&mut self) {
+        if let Some(inputs) = self.searched.clone() {
+            let editing = std::mem::replace(&mut self.inputs, inputs);
+            self.start();
+            self.inputs = editing;
+        }
+    }
+
+    /// Start a search with the current inputs (cancelling a running one)
+    fn start(&mut self) {
+        self.search = None;
+        self.query = None;
+        self.results.clear();
+        self.excluded.clear();
+        self.confirm = false;
+        self.selected = (0, 0);
+        self.scroll = 0;
+        self.error = None;
+        self.searched = Some(self.inputs.clone());
+        if self.inputs.query.is_empty() {
+            return;
+        }
+
+        let query = match SearchQuery::new(
+            &self.inputs.query,
+            self.inputs.options,
+            &WordChars::default(),
+        ) {
+            Ok(query) => query,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            }
+        };
+        let filter = FileFilter {
+            include: FileFilter::parse_globs(&self.inputs.include),
+            exclude: FileFilter::parse_globs(&self.inputs.exclude),
+        };
+        match ProjectSearch::start(&self.root, query.clone(), &filter) {
+            Ok(search) => {
+                self.search = Some(search);
+                self.query = Some(query);
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Take the results found since the last call (keeps the selection on
+    /// the same match)
+    pub fn poll(&mut self) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        for file in search.poll() {
+            let index = self.results.partition_point(|other| other.path < file.path);
+            if index <= self.selected.0 && !self.results.is_empty() {
+                self.selected.0 += 1;
+            }
+            self.results.insert(index, file);
+        }
+    }
+
+    /// Check if a search is running
+    fn is_running(&self) -> bool {
+        self.search.as_ref().is_some_and(|search| !search.is_done())
+    }
+
+    /// Rows above the results (query, replacement, include, exclude)
+    fn input_rows(&self) -> usize {
+        3 + usize::from(self.replace.is_some())
+    }
+
+    /// The input fields shown, in order
+    fn fields(&self) -> &'static [Field] {
+        if self.replace.is_some() {
+            &[Field::Query, Field::Replace, Field::Include, Field::Exclude]
+        } else {
+            &[Field::Query, Field::Include, Field::Exclude]
+        }
+    }
+
+    /// The result rows: each file followed by its matches
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (file, result) in self.results.iter().enumerate() {
+            rows.push(Row::File(file));
+            rows.extend((0..result.matches.len()).map(|m| Row::Match(file, m)));
+        }
+        rows
+    }
+
+    /// The selected match, to open
+    fn selected_match(&self) -> Option<(PathBuf, SearchMatch)> {
+        let (file, m) = self.selected;
+        let result = self.results.get(file)?;
+        Some((result.path.clone(), result.matches.get(m)?.range))
+    }
+
+    /// Select the next (or previous) match, moving across files
+    fn move_selection(&mut self, forward: bool) {
+        let (file, m) = self.selected;
+        let Some(result) = self.results.get(file) else {
+            return;
+        };
+        self.selected = if forward {
+            if m + 1 < result.matches.len() {
+                (file, m + 1)
+            } else if file + 1 < self.results.len() {
+                (file + 1, 0)
+            } else {
+                (file, m)
+            }
+        } else if m > 0 {
+            (file, m - 1)
+        } else if file > 0 {
+            (file - 1, self.results[file - 1].matches.len() - 1)
+        } else {
+            (file, m)
+        };
+    }
+
+    /// Select the next (or previous) match and open it (F4 / Shift+F4 from
+    /// the editor)
+    pub fn step(&mut self, forward: bool) -> Option<(PathBuf, SearchMatch)> {
+        self.move_selection(forward);
+        self.selected_match()
+    }
+
+    /// Check if a match is checked for replacing
+    fn is_included(&self, path: &Path, m: SearchMatch) -> bool {
+        !self.excluded.contains(&(path.to_path_buf(), m))
+    }
+
+    /// Check or uncheck the selected match
+    fn toggle_match(&mut self) {
+        if let Some(key) = self.selected_match() {
+            if !self.excluded.remove(&key) {
+                self.excluded.insert(key);
+            }
+        }
+    }
+
+    /// Check or uncheck all matches of the selected file (all are checked
+    /// unless all were checked before)
+    fn toggle_file(&mut self) {
+        let Some(result) = self.results.get(self.selected.0) else {
+            return;
+        };
+        let keys = result
+            .matches
+            .iter()
+            .map(|m| (result.path.clone(), m.range));
+        let all_included = result
+            .matches
+            .iter()
+            .all(|m| self.is_included(&result.path, m.range));
+        if all_included {
+            self.excluded.extend(keys);
+        } else {
+            keys.for_each(|key| {
+                self.excluded.remove(&key);
+            });
+        }
+    }
+
+    /// The checked matches per file, skipping files without any
+    fn checked(&self) -> Vec<(PathBuf, Vec<SearchMatch>)> {
+        self.results
+            .iter()
+            .map(|file| {
+                let matches = file
+                    .matches
+                    .iter()
+                    .map(|m| m.range)
+                    .filter(|&m| self.is_included(&file.path, m))
+                    .collect::<Vec<_>>();
+                (file.path.clone(), matches)
+            })
+            .filter(|(_, matches)| !matches.is_empty())
+            .collect()
+    }
+
+    /// The replacement from the replace field and toggle
+    fn replacement(&self) -> Replacement {
+        Replacement {
+            template: self.replace.clone().unwrap_or_default(),
+            preserve_case: self.preserve_case,
+        }
+    }
+
+    /// Ask to confirm replacing the checked matches (once the search is
+    /// done, so all of them are known)
+    fn confirm_replace(&mut self) {
+        if self.replace.is_some() && self.query.is_some() && !self.is_running() {
+            self.confirm = !self.checked().is_empty();
+        }
+    }
+
+    /// The field typing goes to
+    fn field_text(&mut self) -> &mut String {
+        match self.field {
+            Field::Query => &mut self.inputs.query,
+            Field::Replace => self.replace.get_or_insert_with(String::new),
+            Field::Include => &mut self.inputs.include,
+            Field::Exclude => &mut self.inputs.exclude,
+        }
+    }
+
+    /// Toggle a search option and search again if there was a search
+    fn toggle(&mut self, toggle: impl FnOnce(&mut SearchOptions)) {
+        toggle(&mut self.inputs.options);
+        if self.searched.is_some() {
+            self.start();
+        }
+    }
+
+    /// Handle a key event
+    pub fn handle_key(&mut self, key: KeyEvent) -> ProjectSearchAction {
+        // The replace confirmation captures all keys while open
+        if self.confirm {
+            match key.code {
+                KeyCode::Enter | KeyCode::Char('y' | 'Y') => {
+                    self.confirm = false;
+                    if let Some(query) = self.query.clone() {
+                        return ProjectSearchAction::Replace(ProjectReplace {
+                            query,
+                            replacement: self.replacement(),
+                            files: self.checked(),
+                        });
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('n' | 'N') => self.confirm = false,
+                _ => {}
+            }
+            return ProjectSearchAction::None;
+        }
+
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) => {
+                if !self.is_running() {
+                    return ProjectSearchAction::Close;
+                }
+                if let Some(search) = &self.search {
+                    search.cancel();
+                }
+            }
+            (KeyModifiers::ALT, KeyCode::Enter) => self.confirm_replace(),
+            (KeyModifiers::NONE, KeyCode::Enter) => {
+                if self.searched.as_ref() != Some(&self.inputs) {
+                    self.start();
+                } else if let Some((path, m)) = self.selected_match() {
+                    return ProjectSearchAction::Open(path, m);
+                }
+            }
+            (KeyModifiers::NONE, KeyCode::Tab) | (_, KeyCode::BackTab) => {
+                let fields = self.fields();
+                let index = fields.iter().position(|&f| f == self.field).unwrap_or(0);
+                let index = if key.code == KeyCode::Tab {
+                    (index + 1) % fields.len()
+                } else {
+                    (index + fields.len() - 1) % fields.len()
+                };
+                self.field = fields[index];
+            }
+            (KeyModifiers::NONE, KeyCode::Down) => self.move_selection(true),
+            (KeyModifiers::NONE, KeyCode::Up) => self.move_selection(false),
+            (KeyModifiers::NONE, KeyCode::PageDown) => {
+                (0..10).for_each(|_| self.move_selection(true));
+            }
+            (KeyModifiers::NONE, KeyCode::PageUp) => {
+                (0..10).for_each(|_| self.move_selection(false));
+            }
+            (KeyModifiers::ALT, KeyCode::Char('c')) => {
+                self.toggle(|options| options.case_sensitive = !options.case_sensitive);
+            }
+            (KeyModifiers::ALT, KeyCode::Char('w')) => {
+                self.toggle(|options| options.whole_word = !options.whole_word);
+            }
+            (KeyModifiers::ALT, KeyCode::Char('r')) => {
+                self.toggle(|options| options.regex = !options.regex);
+            }
+            (mods, KeyCode::Char('r' | 'R'))
+                if mods == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                self.show_replace();
+            }
+            (KeyModifiers::ALT, KeyCode::Char('p')) if self.replace.is_some() => {
+                self.preserve_case = !self.preserve_case;
+            }
+            (KeyModifiers::ALT, KeyCode::Char('x')) if self.replace.is_some() => {
+                self.toggle_match();
+            }
+            (KeyModifiers::ALT, KeyCode::Char('f')) if self.replace.is_some() => {
+                self.toggle_file();
+            }
+            (KeyModifiers::NONE, KeyCode::Backspace) => {
+                self.field_text().pop();
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.field_text().push(c);
+            }
+            _ => {}
+        }
+        ProjectSearchAction::None
+    }
+
+    /// Panel title with the result counts and search state
+    fn title(&self) -> String {
+        if self.confirm {
+            let files = self.checked();
+            let matches: usize = files.iter().map(|(_, matches)| matches.len()).sum();
+            return format!(
+                " Replace {matches} matches in {} files? [Enter] Replace  [Esc] Cancel ",
+                files.len()
+            );
+        }
+        let matches: usize = self.results.iter().map(|file| file.matches.len()).sum();
+        let mut title = format!(
+            " {} in Project: {matches} matches in {} files",
+            if self.replace.is_some() {
+                "Replace"
+            } else {
+                "Search"
+            },
+            self.results.len()
+        );
+        if let Some(search) = &self.search {
+            if !search.is_done() {
+                title.push_str(&format!(" ({} files searched...)", search.files_searched()));
+            } else if search.is_truncated() {
+                title.push_str(" (stopped: too many matches)");
+            } else if search.is_cancelled() {
+                title.push_str(" (cancelled)");
+            }
+        }
+        title.push(' ');
+        title
+    }
+
+    /// Render the panel; returns the terminal cursor position (in the
+    /// focused field) if the panel has the focus
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) -> Option<(u16, u16)> {
+        let block = Block::default()
+            .title(self.title())
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(if self.focused {
+                Color::Yellow
+            } else {
+                Color::DarkGray
+            }));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let label = |text: &'static str, field: Field| {
+            let style = if self.focused && self.field == field {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            Span::styled(format!("{text:<LABEL_WIDTH$}"), style)
+        };
+        let toggle = |text: &'static str, on: bool| {
+            let style = if on {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            [Span::styled(text, style), Span::raw(" ")]
+        };
+        let hint = |text: &'static str| Span::styled(text, Style::default().fg(Color::DarkGray));
+
+        let options = self.inputs.options;
+        let mut find = vec![
+            label(" Find:", Field::Query),
+            Span::raw(format!("{}  ", self.inputs.query)),
+        ];
+        find.extend(toggle("Aa", options.case_sensitive));
+        find.extend(toggle("W", options.whole_word));
+        find.extend(toggle(".*", options.regex));
+        match &self.error {
+            Some(error) => find.push(Span::styled(
+                format!(" {error}"),
+                Style::default().fg(Color::LightRed),
+            )),
+            None => find.push(hint(
+                " Enter: search/open  Tab: fields  Alt+C/W/R: toggles  Esc: cancel/close",
+            )),
+        }
+        let mut lines = vec![Line::from(find)];
+        if let Some(replace) = &self.replace {
+            let mut spans = vec![
+                label(" Replace:", Field::Replace),
+                Span::raw(format!("{replace}  ")),
+            ];
+            spans.extend(toggle("AB", self.preserve_case));
+            spans.push(hint(
+                " Alt+Enter: replace checked  Alt+X/F: check match/file  Alt+P: preserve case",
+            ));
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::from(vec![
+            label(" Include:", Field::Include),
+            Span::raw(self.inputs.include.as_str()),
+        ]));
+        lines.push(Line::from(vec![
+            label(" Exclude:", Field::Exclude),
+            Span::raw(self.inputs.exclude.as_str()),
+        ]));
+
+        // Keep the selected match in view
+        let rows = self.rows();
+        let visible = (inner.height as usize).saturating_sub(self.input_rows());
+        let selected_row = rows
+            .iter()
+            .position(|&row| row == Row::Match(self.selected.0, self.selected.1))
+            .unwrap_or(0);
+        if selected_row < self.scroll {
+            // Show the file header of the first match too
+            self.scroll = selected_row.saturating_sub(1);
+        } else if selected_row >= self.scroll + visible {
+            self.scroll = selected_row + 1 - visible;
+        }
+
+        for &row in rows.iter().skip(self.scroll).take(visible) {
+            lines.push(match row {
+                Row::File(file) => self.file_line(&self.results[file]),
+                Row::Match(file, m) => self.match_line(file, m),
+            });
+        }
+        frame.render_widget(Paragraph::new(lines), inner);
+
+        if !self.focused {
+            return None;
+        }
+        let row = self
+            .fields()
+            .iter()
+            .position(|&f| f == self.field)
+            .unwrap_or(0);
+        let text = match self.field {
+            Field::Query => &self.inputs.query,
+            Field::Replace => self.replace.as_ref().unwrap_or(&self.inputs.query),
+            Field::Include => &self.inputs.include,
+            Field::Exclude => &self.inputs.exclude,
+        };
+        let x = inner.x + (LABEL_WIDTH + text.chars().count()) as u16;
+        Some((x.min(inner.right().saturating_sub(1)), inner.y + row as u16))
+    }
+
+    /// Path relative to the searched directory
+    fn display_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    /// Checkbox in front of a row (replace mode only)
+    fn checkbox(&self, checked: usize, total: usize) -> Span<'static> {
+        let text = match self.replace {
+            None => "",
+            Some(_) if checked == total => " [x]",
+            Some(_) if checked == 0 => " [ ]",
+            Some(_) => " [-]",
+        };
+        Span::styled(text, Style::default().fg(Color::Gray))
+    }
+
+    /// Header row of a file
+    fn file_line(&self, file: &FileMatches) -> Line<'static> {
+        let total = file.matches.len();
+        let checked = file
+            .matches
+            .iter()
+            .filter(|m| self.is_included(&file.path, m.range))
+            .count();
+        let count = match self.replace {
+            Some(_) if checked != total => format!(" ({checked}/{total})"),
+            _ => format!(" ({total})"),
+        };
+        Line::from(vec![
+            self.checkbox(checked, total),
+            Span::styled(
+                format!(" {}", self.display_path(&file.path).display()),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(count, Style::default().fg(Color::DarkGray)),
+        ])
+    }
+
+    /// Row of a match: line number and the line with the match highlighted
+    /// (and its replacement, in replace mode)
+    fn match_line(&self, file: usize, index: usize) -> Line<'static> {
+        let path = &self.results[file].path;
+        let m = &self.results[file].matches[index];
+        let selected = self.selected == (file, index);
+        let base = if selected {
+            Style::default()
+                .bg(Color::Rgb(68, 71, 90))
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+
+        // Char columns of the match within the preview
+        let start = m.range.start - m.preview_start;
+        let end = (m.range.end - m.preview_start).max(start);
+        let chars: Vec<char> = m.preview.chars().collect();
+        let part = |range: std::ops::Range<usize>| -> String {
+            chars[range.start.min(chars.len())..range.end.min(chars.len())]
+                .iter()
+                .map(|&c| if c == '\t' { ' ' } else { c })
+                .collect()
+        };
+
+        let included = self.is_included(path, m.range);
+        let mut spans = vec![
+            self.checkbox(usize::from(included), 1),
+            Span::styled(
+                format!("   {:>5}: ", m.range.line + 1),
+                base.fg(Color::DarkGray),
+            ),
+            Span::styled(part(0..start), base),
+        ];
+        match (&self.query, &self.replace) {
+            (Some(query), Some(_)) if included => {
+                let byte = |column: usize| {
+                    m.preview
+                        .char_indices()
+                        .nth(column)
+                        .map_or(m.preview.len(), |(offset, _)| offset)
+                };
+                let replaced =
+                    query.replacement_text(&m.preview, byte(start)..byte(end), &self.replacement());
+                spans.push(Span::styled(
+                    part(start..end),
+                    base.fg(Color::Red).add_modifier(Modifier::CROSSED_OUT),
+                ));
+                spans.push(Span::styled(
+                    replaced.replace('\t', " "),
+                    base.fg(Color::Black).bg(Color::Green),
+                ));
+            }
+            _ => spans.push(Span::styled(
+                part(start..end),
+                base.fg(Color::Black).bg(Color::Rgb(255, 191, 0)),
+            )),
+        }
+        spans.push(Span::styled(part(end..chars.len()), base));
+        Line::from(spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    /// Poll until the search is done
+    fn finish(panel: &mut ProjectSearchPanel) {
+        let start = Instant::now();
+        while panel.is_running() {
+            assert!(start.elapsed() < Duration::from_secs(10), "search hangs");
+            panel.poll();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panel.poll();
+    }
+
+    fn project(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            std::fs::write(root.path().join(path), content).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_enter_searches_then_opens() {
+        let root = project(&[("a.txt", "x\nneedle\n"), ("b.txt", "needle needle\n")]);
+        let mut panel = ProjectSearchPanel::new(root.path().to_path_buf(), String::new(), &[]);
+        for c in "needle".chars() {
+            panel.handle_key(key(KeyCode::Char(c)));
+        }
+
+        // Enter with new inputs searches
+        assert!(matches!(
+            panel.handle_key(key(KeyCode::Enter)),
+            ProjectSearchAction::None
+        ));
+        finish(&mut panel);
+        assert_eq!(panel.results.len(), 2);
+        // The selection follows the match first found: start at the top
+        panel.selected = (0, 0);
+        assert_eq!(panel.title(), " Search in Project: 3 matches in 2 files ");
+
+        // Enter with the same inputs opens the selected match
+        panel.handle_key(key(KeyCode::Down));
+        let ProjectSearchAction::Open(path, m) = panel.handle_key(key(KeyCode::Enter)) else {
+            panic!("expected a match to open");
+        };
+        assert_eq!(path, root.path().join("b.txt"));
+        assert_eq!((m.line, m.start, m.end), (0, 0, 6));
+
+        // Editing the query searches again on Enter
+        panel.handle_key(key(KeyCode::Backspace));
+        assert!(matches!(
+            panel.handle_key(key(KeyCode::Enter)),
+            ProjectSearchAction::None
+        ));
+        assert_eq!(panel.searched.as_ref().unwrap().query, "needl");
+    }
+
+    #[test]
+    fn test_esc_cancels_then_closes() {
+        let root = tempfile::tempdir().unwrap();
+        for i in 0..500 {
+            std::fs::write(root.path().join(format!("{i}.txt")), "needle\n").unwrap();
+        }
+        let mut panel =
+            ProjectSearchPanel::new(root.path().to_path_buf(), String::from("needle"), &[]);
+        panel.handle_key(key(KeyCode::Enter));
+        assert!(panel.is_running());
+
+        // Esc while searching cancels, the panel stays open
+        assert!(matches!(
+            panel.handle_key(key(KeyCode::Esc)),
+            ProjectSearchAction::None
+        ));
+        finish(&mut panel);
+        assert!(panel.search.as_ref().unwrap().is_cancelled());
+        assert!(panel.title().contains("(cancelled)"));
+
+        // Esc once the search stopped closes the panel
+        assert!(matches!(
+            panel.handle_key(key(KeyCode::Esc)),
+            ProjectSearchAction::Close
+        ));
+    }
+
+    #[test]
+    fn test_replace_checklist() {
+        let root = project(&[("a.txt", "foo foo\n"), ("b.txt", "foo\n")]);
+        let a = root.path().join("a.txt");
+        let b = root.path().join("b.txt");
+        let alt = |c: char| KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+        let checked_lines = |panel: &ProjectSearchPanel| -> Vec<(PathBuf, Vec<usize>)> {
+            panel
+                .checked()
+                .into_iter()
+                .map(|(path, matches)| (path, matches.iter().map(|m| m.start).collect()))
+                .collect()
+        };
+
+        let mut panel =
+            ProjectSearchPanel::new(root.path().to_path_buf(), String::from("foo"), &[]);
+        panel.handle_key(key(KeyCode::Enter));
+        finish(&mut panel);
+        panel.selected = (0, 0);
+
+        // No checklist without the replace field
+        panel.handle_key(alt('x'));
+        assert!(panel.excluded.is_empty());
+
+        panel.show_replace();
+        for c in "bar".chars() {
+            panel.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(panel.replace.as_deref(), Some("bar"));
+
+        // Alt+X unchecks the selected match
+        panel.handle_key(alt('x'));
+        assert_eq!(
+            checked_lines(&panel),
+            [(a.clone(), vec![4]), (b.clone(), vec![0])]
+        );
+
+        // Alt+F checks the whole file if some match was unchecked, else
+        // unchecks it
+        panel.handle_key(alt('f'));
+        assert_eq!(
+            checked_lines(&panel),
+            [(a.clone(), vec![0, 4]), (b.clone(), vec![0])]
+        );
+        panel.handle_key(alt('f'));
+        assert_eq!(checked_lines(&panel), [(b.clone(), vec![0])]);
+
+        // Nothing to confirm without checked matches
+        panel.handle_key(key(KeyCode::Down));
+        panel.handle_key(key(KeyCode::Down));
+        panel.handle_key(alt('x'));
+        panel.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT));
+        assert!(!panel.confirm);
+
+        panel.handle_key(alt('x'));
+        panel.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT));
+        assert!(panel.title().starts_with(" Replace 1 matches in 1 files?"));
+        let ProjectSearchAction::Replace(replace) = panel.handle_key(key(KeyCode::Enter)) else {
+            panic!("expected a replace");
+        };
+        assert_eq!(replace.replacement.template, "bar");
+        assert_eq!(replace.files.len(), 1);
+        assert_eq!(replace.files[0].0, b);
+    }
+}
@@ -0,0 +1,643 @@
+//! Inline search and replace bar (Ctrl+F, Ctrl+R)
+//!
+//! Matches are searched again whenever the query, a toggle or the buffer
+//! changes; while typing, the first match after the position the search
+//! started from is selected (incremental search). Replace all first shows
+//! the changed lines before and after, then replaces as one undo step.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use four_code_core::{
+    Editor, LineChange, Position, Replacement, SearchMatch, SearchOptions, SearchQuery,
+};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Label in front of the query
+const FIND_LABEL: &str = " Find:    ";
+
+/// Label in front of the replacement
+const REPLACE_LABEL: &str = " Replace: ";
+
+/// Result of a key press in the search bar
+pub enum SearchAction {
+    /// Handled, nothing else to do
+    None,
+    /// Close the search bar
+    Close,
+    /// Matches were replaced, the first one on `line`
+    Replaced { line: usize, count: usize },
+    /// Not a search bar key (handled by the editor)
+    Ignored,
+}
+
+/// Changed lines shown before a replace all
+#[derive(Debug)]
+struct ReplacePreview {
+    /// Changed lines, in document order
+    changes: Vec<LineChange>,
+    /// Number of matches to replace
+    count: usize,
+    /// First visible change
+    scroll: usize,
+}
+
+/// Search bar state
+#[derive(Debug)]
+pub struct SearchBar {
+    /// Query text
+    input: String,
+    /// Case, whole word and regex toggles
+    options: SearchOptions,
+    /// Replacement text (None while the replace field is hidden)
+    replace: Option<String>,
+    /// Typing goes to the replace field
+    replace_focused: bool,
+    /// Adapt replacements to the case of the replaced text
+    preserve_case: bool,
+    /// Only search within this range (find / replace in selection)
+    in_selection: Option<(Position, Position)>,
+    /// The compiled query (None if empty or invalid)
+    query: Option<SearchQuery>,
+    /// Matches of the query, in document order
+    matches: Vec<SearchMatch>,
+    /// Index of the selected match
+    current: Option<usize>,
+    /// Error of an invalid regex
+    error: Option<String>,
+    /// Where the search started (typing searches from here)
+    origin: Position,
+    /// Buffer revision the matches were found in (None: search again)
+    revision: Option<u64>,
+    /// Replace all preview (when open)
+    preview: Option<ReplacePreview>,
+}
+
+impl SearchBar {
+    /// Open the search bar at the cursor
+    ///
+    /// A selection within one line becomes the query, a selection over
+    /// several lines restricts the search to it.
+    pub fn new(editor: &Editor) -> Self {
+        let selection = editor.cursor.selection_range();
+        let multi_line = selection.filter(|(start, end)| start.line != end.line);
+        let input = match multi_line {
+            Some(_) => String::new(),
+            None => editor.get_selected_text().unwrap_or_default(),
+        };
+        Self {
+            input,
+            options: SearchOptions::default(),
+            replace: None,
+            replace_focused: false,
+            preserve_case: false,
+            in_selection: multi_line,
+            query: None,
+            matches: Vec::new(),
+            current: None,
+            error: None,
+            origin: selection.map_or(editor.cursor.position, |(start, _)| start),
+            revision: None,
+            preview: None,
+        }
+    }
+
+    /// Show the replace field and focus it
+    pub fn show_replace(&mut self) {
+        self.replace.get_or_insert_with(String::new);
+        self.replace_focused = true;
+    }
+
+    /// Height of the bar in rows
+    pub fn height(&self) -> u16 {
+        1 + u16::from(self.replace.is_some())
+    }
+
+    /// Matches of the query, in document order
+    pub fn matches(&self) -> &[SearchMatch] {
+        &self.matches
+    }
+
+    /// Index of the selected match
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Match counter for the status bar ("3/17")
+    pub fn counter(&self) -> String {
+        match self.current {
+            Some(current) => format!("{}/{}", current + 1, self.matches.len()),
+            None => format!("-/{}", self.matches.len()),
+        }
+    }
+
+    /// Search again if the query or the buffer changed
+    pub fn update(&mut self, editor: &Editor) {
+        let revision = editor.buffer.revision();
+        if self.revision == Some(revision) {
+            return;
+        }
+        self.revision = Some(revision);
+        self.query = None;
+        self.current = None;
+        self.matches.clear();
+        self.error = None;
+        if self.input.is_empty() {
+            return;
+        }
+        match SearchQuery::new(&self.input, self.options, &editor.word_chars) {
+            Ok(query) => {
+                let rope = editor.buffer.rope();
+                self.matches = match self.in_selection {
+                    Some((start, end)) => {
+                        let mut matches = query.find_in_lines(rope, start.line..end.line + 1);
+                        matches.retain(|m| m.is_within(start, end));
+                        matches
+                    }
+                    None => query.find_all(rope),
+                };
+                self.query = Some(query);
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+
+        // Keep the selected match marked (e.g. after an edit elsewhere)
+        self.current = editor.cursor.selection_range().and_then(|(start, end)| {
+            self.matches
+                .iter()
+                .position(|m| m.start_position() == start && m.end_position() == end)
+        });
+    }
+
+    /// Select the next match after the cursor (wrapping around)
+    pub fn next(&mut self, editor: &mut Editor) {
+        self.update(editor);
+        let from = editor
+            .cursor
+            .selection_range()
+            .map_or(editor.cursor.position, |(_, end)| end);
+        let index = self
+            .matches
+            .iter()
+            .position(|m| m.start_position() >= from)
+            .or((!self.matches.is_empty()).then_some(0));
+        self.select(editor, index);
+    }
+
+    /// Select the previous match before the cursor (wrapping around)
+    pub fn previous(&mut self, editor: &mut Editor) {
+        self.update(editor);
+        let from = editor
+            .cursor
+            .selection_range()
+            .map_or(editor.cursor.position, |(start, _)| start);
+        let index = self
+            .matches
+            .iter()
+            .rposition(|m| m.start_position() < from)
+            .or(self.matches.len().checked_sub(1));
+        self.select(editor, index);
+    }
+
+    /// Search (again, e.g. after the query changed) from where the search
+    /// started
+    pub fn search(&mut self, editor: &mut Editor) {
+        self.revision = None;
+        self.update(editor);
+        let index = self
+            .matches
+            .iter()
+            .position(|m| m.start_position() >= self.origin)
+            .or((!self.matches.is_empty()).then_some(0));
+        self.select(editor, index);
+    }
+
+    /// Select a match
+    fn select(&mut self, editor: &mut Editor, index: Option<usize>) {
+        self.current = index;
+        if let Some(m) = index.and_then(|index| self.matches.get(index)) {
+            editor.select_range(m.start_position(), m.end_position());
+        }
+    }
+
+    /// The replacement from the replace field and toggle
+    fn replacement(&self) -> Replacement {
+        Replacement {
+            template: self.replace.clone().unwrap_or_default(),
+            preserve_case: self.preserve_case,
+        }
+    }
+
+    /// Replace the selected match and select the next one
+    ///
+    /// If no match is selected (e.g. the cursor moved), the next match is
+    /// selected first.
+    fn replace_current(&mut self, editor: &mut Editor) -> SearchAction {
+        self.update(editor);
+        let selected = self
+            .current
+            .and_then(|index| self.matches.get(index))
+            .copied();
+        let (Some(query), Some(m)) = (self.query.clone(), selected) else {
+            self.next(editor);
+            return SearchAction::None;
+        };
+        if editor.cursor.selection_range() != Some((m.start_position(), m.end_position())) {
+            self.next(editor);
+            return SearchAction::None;
+        }
+
+        let replacement = self.replacement();
+        self.keep_range_end(editor, &query, &[m], &replacement);
+        let count = editor.replace_matches(&query, &[m], &replacement);
+        self.next(editor);
+        SearchAction::Replaced {
+            line: m.line,
+            count,
+        }
+    }
+
+    /// Show the lines a replace all would change
+    fn preview_replace_all(&mut self, editor: &Editor) {
+        self.update(editor);
+        let Some(query) = &self.query else {
+            return;
+        };
+        if self.matches.is_empty() {
+            return;
+        }
+        let changes = query.preview(editor.buffer.rope(), &self.matches, &self.replacement());
+        self.preview = Some(ReplacePreview {
+            changes,
+            count: self.matches.len(),
+            scroll: 0,
+        });
+    }
+
+    /// Replace all matches as one undo step
+    fn replace_all(&mut self, editor: &mut Editor) -> SearchAction {
+        self.preview = None;
+        self.update(editor);
+        let (Some(query), Some(first)) = (self.query.clone(), self.matches.first().copied()) else {
+            return SearchAction::None;
+        };
+        let matches = std::mem::take(&mut self.matches);
+        let replacement = self.replacement();
+        self.keep_range_end(editor, &query, &matches, &replacement);
+        let count = editor.replace_matches(&query, &matches, &replacement);
+        self.update(editor);
+        SearchAction::Replaced {
+            line: first.line,
+            count,
+        }
+    }
+
+    /// Move the end of the search range by the length change of the matches
+    /// replaced on its line (call before replacing)
+    fn keep_range_end(
+        &mut self,
+        editor: &Editor,
+        query: &SearchQuery,
+        matches: &[SearchMatch],
+        replacement: &Replacement,
+    ) {
+        let Some((_, end)) = &mut self.in_selection else {
+            return;
+        };
+        for m in matches.iter().filter(|m| m.line == end.line) {
+            let text = query.replacement_for(editor.buffer.rope(), m, replacement);
+            end.column = (end.column + text.chars().count()).saturating_sub(m.end - m.start);
+        }
+    }
+
+    /// The field typing goes to
+    fn focused_field(&mut self) -> &mut String {
+        match &mut self.replace {
+            Some(replace) if self.replace_focused => replace,
+            _ => &mut self.input,
+        }
+    }
+
+    /// Handle a key event
+    pub fn handle_key(&mut self, key: KeyEvent, editor: &mut Editor) -> SearchAction {
+        // The replace all preview captures all keys while open
+        if let Some(preview) = &mut self.preview {
+            match key.code {
+                KeyCode::Enter | KeyCode::Char('y' | 'Y') => return self.replace_all(editor),
+                KeyCode::Esc | KeyCode::Char('n' | 'N') => self.preview = None,
+                KeyCode::Up => preview.scroll = preview.scroll.saturating_sub(1),
+                KeyCode::Down => preview.scroll += 1,
+                KeyCode::PageUp => preview.scroll = preview.scroll.saturating_sub(10),
+                KeyCode::PageDown => preview.scroll += 10,
+                _ => {}
+            }
+            return SearchAction::None;
+        }
+
+        let replace_open = self.replace.is_some();
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) => return SearchAction::Close,
+            (KeyModifiers::NONE, KeyCode::Tab) if replace_open => {
+                self.replace_focused = !self.replace_focused;
+            }
+            (KeyModifiers::ALT, KeyCode::Enter) if replace_open => {
+                self.preview_replace_all(editor);
+            }
+            (KeyModifiers::NONE, KeyCode::Enter) if self.replace_focused => {
+                return self.replace_current(editor);
+            }
+            (KeyModifiers::NONE, KeyCode::Enter | KeyCode::F(3) | KeyCode::Down) => {
+                self.next(editor);
+            }
+            (KeyModifiers::SHIFT, KeyCode::Enter | KeyCode::F(3))
+            | (KeyModifiers::NONE, KeyCode::Up) => {
+                self.previous(editor);
+            }
+            (KeyModifiers::ALT, KeyCode::Char('c')) => {
+                self.options.case_sensitive = !self.options.case_sensitive;
+                self.search(editor);
+            }
+            (KeyModifiers::ALT, KeyCode::Char('w')) => {
+                self.options.whole_word = !self.options.whole_word;
+                self.search(editor);
+            }
+            (KeyModifiers::ALT, KeyCode::Char('r')) => {
+                self.options.regex = !self.options.regex;
+                self.search(editor);
+            }
+            (KeyModifiers::ALT, KeyCode::Char('l')) => {
+                self.in_selection = match self.in_selection {
+                    Some(_) => None,
+                    None => editor.cursor.selection_range(),
+                };
+                if let Some((start, _)) = self.in_selection {
+                    self.origin = start;
+                }
+                self.search(editor);
+            }
+            (KeyModifiers::ALT, KeyCode::Char('p')) if replace_open => {
+                self.preserve_case = !self.preserve_case;
+            }
+            (KeyModifiers::NONE, KeyCode::Backspace) => {
+                self.focused_field().pop();
+                if !self.replace_focused {
+                    self.search(editor);
+                }
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.focused_field().push(c);
+                if !self.replace_focused {
+                    self.search(editor);
+                }
+            }
+            _ => return SearchAction::Ignored,
+        }
+        SearchAction::None
+    }
+
+    /// Render the bar; returns the terminal cursor position (end of the
+    /// focused field)
+    pub fn render(&self, frame: &mut Frame, area: Rect) -> (u16, u16) {
+        let toggle = |label: &'static str, on: bool| {
+            let style = if on {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            [Span::styled(label, style), Span::raw(" ")]
+        };
+        let hint = |text: &'static str| Span::styled(text, Style::default().fg(Color::DarkGray));
+        let label = |text: &'static str| Span::styled(text, Style::default().fg(Color::Yellow));
+
+        let mut find = vec![label(FIND_LABEL), Span::raw(format!("{}  ", self.input))];
+        find.extend(toggle("Aa", self.options.case_sensitive));
+        find.extend(toggle("W", self.options.whole_word));
+        find.extend(toggle(".*", self.options.regex));
+        find.extend(toggle("Sel", self.in_selection.is_some()));
+        match &self.error {
+            Some(error) => find.push(Span::styled(
+                format!(" {error}"),
+                Style::default().fg(Color::LightRed),
+            )),
+            None if !self.input.is_empty() && self.matches.is_empty() => {
+                find.push(Span::styled(
+                    " No results",
+                    Style::default().fg(Color::LightRed),
+                ));
+            }
+            None => find.push(hint(
+                " Enter/Shift+Enter: next/prev  Alt+C/W/R/L: toggles  Esc: close",
+            )),
+        }
+        let mut lines = vec![Line::from(find)];
+
+        if let Some(replace) = &self.replace {
+            let mut spans = vec![label(REPLACE_LABEL), Span::raw(format!("{replace}  "))];
+            spans.extend(toggle("AB", self.preserve_case));
+            spans.push(hint(
+                " Enter: replace  Alt+Enter: all  Alt+P: preserve case  Tab: switch",
+            ));
+            lines.push(Line::from(spans));
+        }
+
+        let bar = Paragraph::new(lines).style(Style::default().bg(Color::Rgb(40, 44, 52)));
+        frame.render_widget(bar, area);
+
+        let (row, text) = match &self.replace {
+            Some(replace) if self.replace_focused => (1, replace),
+            _ => (0, &self.input),
+        };
+        // Both labels have the same width
+        let x = area.x + (FIND_LABEL.len() + text.chars().count()) as u16;
+        (x.min(area.right().saturating_sub(1)), area.y + row)
+    }
+
+    /// Render the replace all preview (centered, when open)
+    pub fn render_preview(&self, frame: &mut Frame, size: Rect) {
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        let mut lines = vec![
+            Line::from(format!(
+                "  Replace {} matches on {} lines?",
+                preview.count,
+                preview.changes.len()
+            )),
+            Line::styled(
+                "  [Enter] Replace all   [Esc] Cancel",
+                Style::default().fg(Color::Yellow),
+            ),
+            Line::from(""),
+        ];
+
+        // Each change is shown as a removed and an added line
+        let visible = size.height.saturating_sub(9) as usize / 2;
+        let scroll = preview
+            .scroll
+            .min(preview.changes.len().saturating_sub(visible));
+        for change in preview.changes.iter().skip(scroll).take(visible) {
+            let number = change.line + 1;
+            lines.push(Line::styled(
+                format!("  {number:>5} - {}", change.before),
+                Style::default().fg(Color::Red),
+            ));
+            lines.push(Line::styled(
+                format!("  {number:>5} + {}", change.after),
+                Style::default().fg(Color::Green),
+            ));
+        }
+
+        let width = size.width.saturating_sub(8);
+        let height = (lines.len() as u16 + 2).min(size.height);
+        let area = Rect::new(
+            size.width.saturating_sub(width) / 2,
+            size.height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        frame.render_widget(Clear, area);
+
+        let block = Block::default()
+            .title(" Replace All ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow))
+            .style(Style::default().bg(Color::Rgb(40, 44, 52)));
+        let paragraph = Paragraph::new(lines)
+            .block(block)
+            .style(Style::default().fg(Color::White));
+        frame.render_widget(paragraph, area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn alt(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT)
+    }
+
+    fn type_text(bar: &mut SearchBar, editor: &mut Editor, text: &str) {
+        for c in text.chars() {
+            bar.handle_key(key(KeyCode::Char(c)), editor);
+        }
+    }
+
+    fn selection(editor: &Editor) -> Option<(Position, Position)> {
+        editor.cursor.selection_range()
+    }
+
+    #[test]
+    fn test_incremental_search_and_navigation() {
+        let mut editor = Editor::with_content("foo bar\nFoo foo");
+        editor.cursor.move_to(0, 4);
+        let mut bar = SearchBar::new(&editor);
+
+        // Typing selects the first match after where the search started
+        type_text(&mut bar, &mut editor, "fo");
+        assert_eq!(bar.counter(), "2/3");
+        assert_eq!(
+            selection(&editor),
+            Some((Position::new(1, 0), Position::new(1, 2)))
+        );
+
+        // Enter / Shift+Enter go to the next / previous match, wrapping
+        bar.handle_key(key(KeyCode::Enter), &mut editor);
+        assert_eq!(bar.counter(), "3/3");
+        bar.handle_key(key(KeyCode::Enter), &mut editor);
+        assert_eq!(bar.counter(), "1/3");
+        bar.handle_key(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT),
+            &mut editor,
+        );
+        assert_eq!(bar.counter(), "3/3");
+
+        // Toggles search again
+        bar.handle_key(alt('c'), &mut editor);
+        assert_eq!(bar.matches().len(), 2);
+        bar.handle_key(alt('r'), &mut editor);
+        type_text(&mut bar, &mut editor, "o$");
+        assert_eq!(bar.matches().len(), 1);
+
+        // An invalid regex is an error, not a crash
+        type_text(&mut bar, &mut editor, "(");
+        assert!(bar.matches().is_empty());
+        assert!(bar.error.is_some());
+
+        assert!(matches!(
+            bar.handle_key(key(KeyCode::Left), &mut editor),
+            SearchAction::Ignored
+        ));
+        assert!(matches!(
+            bar.handle_key(key(KeyCode::Esc), &mut editor),
+            SearchAction::Close
+        ));
+    }
+
+    #[test]
+    fn test_replace_current_and_all() {
+        let mut editor = Editor::with_content("a1 a2 a3");
+        let mut bar = SearchBar::new(&editor);
+        type_text(&mut bar, &mut editor, "a");
+        bar.show_replace();
+        type_text(&mut bar, &mut editor, "b");
+        assert_eq!(bar.counter(), "1/3");
+
+        // Enter in the replace field replaces the selected match
+        let action = bar.handle_key(key(KeyCode::Enter), &mut editor);
+        assert!(matches!(
+            action,
+            SearchAction::Replaced { line: 0, count: 1 }
+        ));
+        assert_eq!(editor.buffer.text(), "b1 a2 a3");
+        assert_eq!(bar.counter(), "1/2");
+
+        // Replace all shows a preview first; Esc cancels it
+        bar.handle_key(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT),
+            &mut editor,
+        );
+        let preview = bar.preview.as_ref().unwrap();
+        assert_eq!(preview.count, 2);
+        assert_eq!(preview.changes[0].after, "b1 b2 b3");
+        bar.handle_key(key(KeyCode::Esc), &mut editor);
+        assert!(bar.preview.is_none());
+        assert_eq!(editor.buffer.text(), "b1 a2 a3");
+
+        bar.handle_key(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT),
+            &mut editor,
+        );
+        let action = bar.handle_key(key(KeyCode::Enter), &mut editor);
+        assert!(matches!(
+            action,
+            SearchAction::Replaced { line: 0, count: 2 }
+        ));
+        assert_eq!(editor.buffer.text(), "b1 b2 b3");
+        assert!(bar.matches().is_empty());
+    }
+
+    #[test]
+    fn test_search_in_selection() {
+        let mut editor = Editor::with_content("x\nx\nx\nx");
+        editor.select_range(Position::new(1, 0), Position::new(2, 1));
+        let mut bar = SearchBar::new(&editor);
+        type_text(&mut bar, &mut editor, "x");
+        assert_eq!(bar.matches().len(), 2);
+
+        // Alt+L searches the whole buffer again
+        bar.handle_key(alt('l'), &mut editor);
+        assert_eq!(bar.matches().len(), 4);
+    }
+}
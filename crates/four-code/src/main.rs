@@ -1,18 +1,27 @@
 //! four-code: Minimalist terminal IDE for PHP developers
 //!
 //! Usage:
-//!   four-code [file]    Open file for editing
-//!   four-code           Open with welcome screen
+//!   four-code [file]        Open file for editing
+//!   four-code [dir]         Open a project directory (picking a file)
+//!   four-code               Open with welcome screen
+//!   four-code --purge-undo  Remove stale persistent undo history
+//!   four-code --recover     List unsaved changes recoverable after a crash
 
 use anyhow::Result;
 use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use four_code_core::{SwapStore, UndoStore};
 use four_code_tui::App;
 use ratatui::{backend::CrosstermBackend, Terminal};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 use std::{env, io};
 
+/// Undo history untouched for this long is purged by `--purge-undo`
+const UNDO_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
 fn main() -> Result<()> {
     // Initialize logging
     tracing_subscriber::fmt()
@@ -25,6 +34,13 @@ fn main() -> Result<()> {
     // Parse arguments
     let args: Vec<String> = env::args().collect();
 
+    if args.get(1).map(String::as_str) == Some("--purge-undo") {
+        return purge_undo();
+    }
+    if args.get(1).map(String::as_str) == Some("--recover") {
+        return list_recoverable();
+    }
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -33,7 +49,9 @@ fn main() -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     // Create and run app
-    let mut app = if args.len() > 1 {
+    let mut app = if args.len() > 1 && Path::new(&args[1]).is_dir() {
+        App::with_dir(&args[1])
+    } else if args.len() > 1 {
         App::with_file(&args[1])?
     } else {
         App::new()
@@ -49,3 +67,52 @@ fn main() -> Result<()> {
     result?;
     Ok(())
 }
+
+/// Remove undo history for deleted, changed or long untouched files
+fn purge_undo() -> Result<()> {
+    let store = UndoStore::open_default()?;
+    let stats = store.purge_stale(UNDO_MAX_AGE)?;
+    println!(
+        "Purged {} stale undo files, kept {} ({})",
+        stats.removed,
+        stats.kept,
+        store.root().display()
+    );
+    Ok(())
+}
+
+/// List files with recoverable unsaved changes (swap files)
+fn list_recoverable() -> Result<()> {
+    let store = SwapStore::open_default()?;
+    let swaps = store.list()?;
+    if swaps.is_empty() {
+        println!("Nothing to recover ({})", store.root().display());
+        return Ok(());
+    }
+
+    println!("Recoverable unsaved changes (open the file to recover):");
+    for swap in swaps {
+        let age = swap
+            .modified
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .map_or_else(|| String::from("unknown age"), format_age);
+        println!(
+            "  {}  ({age}, {} lines, process {})",
+            swap.path.display(),
+            swap.content.lines().count(),
+            swap.pid
+        );
+    }
+    Ok(())
+}
+
+/// Human-readable age ("5 min ago")
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{secs} s ago"),
+        60..3600 => format!("{} min ago", secs / 60),
+        3600..86400 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}